@@ -13,6 +13,14 @@ pub enum BlackBoxFunc {
     RANGE,
     SHA256,
     Blake2s,
+    /// Computes the root of a merkle tree from a leaf and its sibling path.
+    ///
+    /// Inputs are laid out as `[leaf, index, hash_path[0], ..., hash_path[depth - 1]]`:
+    /// - `hash_path[i]` is the sibling of the node on level `i`, counting up from the leaves.
+    /// - bit `i` of `index` is set if the node on level `i` is a right child.
+    ///
+    /// A call therefore has at least the two inputs `leaf` and `index`,
+    /// the depth of the tree is the number of inputs minus two and `index` must fit into `depth` bits.
     ComputeMerkleRoot,
     SchnorrVerify,
     Pedersen,
@@ -22,6 +30,33 @@ pub enum BlackBoxFunc {
     EcdsaSecp256k1,
     FixedBaseScalarMul,
     Keccak256,
+    /// The SHA256 compression function, which applies a single 64 byte block to the hash state.
+    ///
+    /// Inputs are the 16 big endian `u32` words of the block followed by the 8 `u32` words of the state.
+    /// Outputs are the 8 `u32` words of the updated state.
+    Sha256Compression,
+    /// The Keccak-f\[1600\] permutation over a state of 25 `u64` lanes.
+    Keccakf1600,
+    /// Recovers the public key which produced a signature over a hashed message, as done by Ethereum's `ecrecover`.
+    ///
+    /// Inputs are laid out as `[signature[0..64], recovery_id, hashed_message[0..32]]`.
+    /// Outputs are either the 64 bytes of the public key, `x` coordinate first,
+    /// or the 20 bytes of the Ethereum address of the public key.
+    EcdsaSecp256k1Recover,
+    /// Adds two points of the embedded curve, given in affine form.
+    ///
+    /// Inputs are laid out as `[lhs_x, lhs_y, rhs_x, rhs_y]`, the point at infinity is represented by `(0, 0)`.
+    EmbeddedCurveAdd,
+    /// Doubles a point of the embedded curve, given in affine form.
+    EmbeddedCurveDouble,
+    /// Multiplies a point of the embedded curve by a scalar.
+    ///
+    /// Inputs are laid out as `[point_x, point_y, scalar]`.
+    VariableBaseScalarMul,
+    /// Asserts that the input, interpreted as an integer, lies between two constant bounds which are both inclusive.
+    ///
+    /// The bounds are options of the call rather than inputs, so the only input is the value being checked.
+    BoundedRange,
 }
 
 impl std::fmt::Display for BlackBoxFunc {
@@ -46,6 +81,13 @@ impl BlackBoxFunc {
             BlackBoxFunc::XOR => 10,
             BlackBoxFunc::RANGE => 11,
             BlackBoxFunc::Keccak256 => 12,
+            BlackBoxFunc::Sha256Compression => 13,
+            BlackBoxFunc::Keccakf1600 => 14,
+            BlackBoxFunc::EcdsaSecp256k1Recover => 15,
+            BlackBoxFunc::EmbeddedCurveAdd => 16,
+            BlackBoxFunc::EmbeddedCurveDouble => 17,
+            BlackBoxFunc::VariableBaseScalarMul => 18,
+            BlackBoxFunc::BoundedRange => 19,
         }
     }
     pub fn from_u16(index: u16) -> Option<Self> {
@@ -63,6 +105,13 @@ impl BlackBoxFunc {
             10 => BlackBoxFunc::XOR,
             11 => BlackBoxFunc::RANGE,
             12 => BlackBoxFunc::Keccak256,
+            13 => BlackBoxFunc::Sha256Compression,
+            14 => BlackBoxFunc::Keccakf1600,
+            15 => BlackBoxFunc::EcdsaSecp256k1Recover,
+            16 => BlackBoxFunc::EmbeddedCurveAdd,
+            17 => BlackBoxFunc::EmbeddedCurveDouble,
+            18 => BlackBoxFunc::VariableBaseScalarMul,
+            19 => BlackBoxFunc::BoundedRange,
             _ => return None,
         };
         Some(function)
@@ -82,6 +131,13 @@ impl BlackBoxFunc {
             BlackBoxFunc::XOR => "xor",
             BlackBoxFunc::RANGE => "range",
             BlackBoxFunc::Keccak256 => "keccak256",
+            BlackBoxFunc::Sha256Compression => "sha256_compression",
+            BlackBoxFunc::Keccakf1600 => "keccakf1600",
+            BlackBoxFunc::EcdsaSecp256k1Recover => "ecdsa_secp256k1_recover",
+            BlackBoxFunc::EmbeddedCurveAdd => "embedded_curve_add",
+            BlackBoxFunc::EmbeddedCurveDouble => "embedded_curve_double",
+            BlackBoxFunc::VariableBaseScalarMul => "variable_base_scalar_mul",
+            BlackBoxFunc::BoundedRange => "bounded_range",
         }
     }
     pub fn lookup(op_name: &str) -> Option<BlackBoxFunc> {
@@ -99,6 +155,13 @@ impl BlackBoxFunc {
             "xor" => Some(BlackBoxFunc::XOR),
             "range" => Some(BlackBoxFunc::RANGE),
             "keccak256" => Some(BlackBoxFunc::Keccak256),
+            "sha256_compression" => Some(BlackBoxFunc::Sha256Compression),
+            "keccakf1600" => Some(BlackBoxFunc::Keccakf1600),
+            "ecdsa_secp256k1_recover" => Some(BlackBoxFunc::EcdsaSecp256k1Recover),
+            "embedded_curve_add" => Some(BlackBoxFunc::EmbeddedCurveAdd),
+            "embedded_curve_double" => Some(BlackBoxFunc::EmbeddedCurveDouble),
+            "variable_base_scalar_mul" => Some(BlackBoxFunc::VariableBaseScalarMul),
+            "bounded_range" => Some(BlackBoxFunc::BoundedRange),
             _ => None,
         }
     }
@@ -123,7 +186,12 @@ impl BlackBoxFunc {
                 FuncDefinition { name, input_size: InputSize::Variable, output_size: OutputSize(1) }
             }
             BlackBoxFunc::ComputeMerkleRoot => {
-                FuncDefinition { name, input_size: InputSize::Variable, output_size: OutputSize(1) }
+                // The leaf and its index, followed by the hash path
+                FuncDefinition {
+                    name,
+                    input_size: InputSize::AtLeast(2),
+                    output_size: OutputSize(1),
+                }
             }
             BlackBoxFunc::SchnorrVerify => FuncDefinition {
                 name,
@@ -148,7 +216,7 @@ impl BlackBoxFunc {
             BlackBoxFunc::XOR => {
                 FuncDefinition { name, input_size: InputSize::Fixed(2), output_size: OutputSize(1) }
             }
-            BlackBoxFunc::RANGE => {
+            BlackBoxFunc::RANGE | BlackBoxFunc::BoundedRange => {
                 FuncDefinition { name, input_size: InputSize::Fixed(1), output_size: OutputSize(0) }
             }
             BlackBoxFunc::Keccak256 => FuncDefinition {
@@ -156,6 +224,32 @@ impl BlackBoxFunc {
                 input_size: InputSize::Variable,
                 output_size: OutputSize(32),
             },
+            BlackBoxFunc::Sha256Compression => FuncDefinition {
+                name,
+                input_size: InputSize::Fixed(24),
+                output_size: OutputSize(8),
+            },
+            BlackBoxFunc::Keccakf1600 => FuncDefinition {
+                name,
+                input_size: InputSize::Fixed(25),
+                output_size: OutputSize(25),
+            },
+            BlackBoxFunc::EcdsaSecp256k1Recover => FuncDefinition {
+                name,
+                input_size: InputSize::Fixed(97),
+                // The public key, calls which output an address have 20 outputs instead,
+                // see `RecoverOutputMode`
+                output_size: RecoverOutputMode::PublicKey.output_size(),
+            },
+            BlackBoxFunc::EmbeddedCurveAdd => {
+                FuncDefinition { name, input_size: InputSize::Fixed(4), output_size: OutputSize(2) }
+            }
+            BlackBoxFunc::EmbeddedCurveDouble => {
+                FuncDefinition { name, input_size: InputSize::Fixed(2), output_size: OutputSize(2) }
+            }
+            BlackBoxFunc::VariableBaseScalarMul => {
+                FuncDefinition { name, input_size: InputSize::Fixed(3), output_size: OutputSize(2) }
+            }
         }
     }
 }
@@ -167,15 +261,25 @@ impl BlackBoxFunc {
 pub enum InputSize {
     Variable,
     Fixed(u128),
+    /// A variable number of inputs which starts with the given number of fixed inputs.
+    AtLeast(u128),
 }
 
 impl InputSize {
     pub fn fixed_size(&self) -> Option<u128> {
         match self {
-            InputSize::Variable => None,
+            InputSize::Variable | InputSize::AtLeast(_) => None,
             InputSize::Fixed(size) => Some(*size),
         }
     }
+
+    /// Returns the smallest number of inputs which a call can have.
+    pub fn min_size(&self) -> u128 {
+        match self {
+            InputSize::Variable => 0,
+            InputSize::Fixed(size) | InputSize::AtLeast(size) => *size,
+        }
+    }
 }
 
 // Output size Cannot currently vary, so we use a separate struct
@@ -191,6 +295,120 @@ pub struct FuncDefinition {
     pub output_size: OutputSize,
 }
 
+impl FuncDefinition {
+    /// Returns the definition of a hash function whose digest is output in the shape of `output_mode`.
+    ///
+    /// The definitions returned by [`BlackBoxFunc::definition`] output the digest as bytes.
+    pub fn with_hash_output_mode(self, output_mode: HashOutputMode) -> FuncDefinition {
+        FuncDefinition { output_size: output_mode.output_size(), ..self }
+    }
+}
+
+/// How the inputs of a hash function are converted into the bytes of the message.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashInputPacking {
+    /// Each input contributes its `num_bits` rounded up to whole bytes, least significant byte first.
+    #[default]
+    Bytes,
+    /// Each input contributes exactly its `num_bits` least significant bits, most significant bit first.
+    /// The bits of all inputs are concatenated and then split into bytes, so their total must be a multiple of 8.
+    Bits,
+}
+
+impl HashInputPacking {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            HashInputPacking::Bytes => 0,
+            HashInputPacking::Bits => 1,
+        }
+    }
+
+    pub(crate) fn from_u8(index: u8) -> Option<Self> {
+        let input_packing = match index {
+            0 => HashInputPacking::Bytes,
+            1 => HashInputPacking::Bits,
+            _ => return None,
+        };
+        Some(input_packing)
+    }
+}
+
+/// The shape in which the 32 byte digest of a hash function is written to its outputs.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashOutputMode {
+    /// Each byte of the digest is written to a separate output.
+    #[default]
+    Bytes,
+    /// The digest is split into two 128 bit limbs, the most significant limb comes first.
+    Limbs128,
+    /// The digest is interpreted as a big endian integer and reduced into a single field element,
+    /// in the same way as `HashToField128Security`.
+    Field,
+}
+
+impl HashOutputMode {
+    pub fn output_size(&self) -> OutputSize {
+        match self {
+            HashOutputMode::Bytes => OutputSize(32),
+            HashOutputMode::Limbs128 => OutputSize(2),
+            HashOutputMode::Field => OutputSize(1),
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            HashOutputMode::Bytes => 0,
+            HashOutputMode::Limbs128 => 1,
+            HashOutputMode::Field => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(index: u8) -> Option<Self> {
+        let output_mode = match index {
+            0 => HashOutputMode::Bytes,
+            1 => HashOutputMode::Limbs128,
+            2 => HashOutputMode::Field,
+            _ => return None,
+        };
+        Some(output_mode)
+    }
+}
+
+/// What is written to the outputs of `EcdsaSecp256k1Recover`.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoverOutputMode {
+    /// Each of the 64 bytes of the public key, `x` coordinate first.
+    #[default]
+    PublicKey,
+    /// Each of the 20 bytes of the Ethereum address of the public key.
+    Address,
+}
+
+impl RecoverOutputMode {
+    pub fn output_size(&self) -> OutputSize {
+        match self {
+            RecoverOutputMode::PublicKey => OutputSize(64),
+            RecoverOutputMode::Address => OutputSize(20),
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            RecoverOutputMode::PublicKey => 0,
+            RecoverOutputMode::Address => 1,
+        }
+    }
+
+    pub(crate) fn from_u8(index: u8) -> Option<Self> {
+        let output_mode = match index {
+            0 => RecoverOutputMode::PublicKey,
+            1 => RecoverOutputMode::Address,
+            _ => return None,
+        };
+        Some(output_mode)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use strum::IntoEnumIterator;
@@ -1,9 +1,14 @@
 use std::io::{Read, Write};
 
 use crate::{
+    circuit::opcodes::MemoryBlock,
     native_types::{Expression, Witness},
-    serialization::{read_bytes, read_n, read_u16, read_u32, write_bytes, write_u16, write_u32},
+    serialization::{
+        read_bytes, read_n, read_u16, read_u32, read_witnesses, write_bytes, write_u16, write_u32,
+        write_witnesses,
+    },
 };
+use acir_field::FieldElement;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -33,15 +38,47 @@ pub enum Directive {
         radix: u32,
     },
 
+    //decomposition of a: a=\sum b[i]*radix^(n-1-i) where b is an array of n witnesses < radix in big endian form
+    ToBeRadix {
+        a: Expression,
+        b: Vec<Witness>,
+        radix: u32,
+    },
+
     // Sort directive, using a sorting network
     // This directive is used to generate the values of the control bits for the sorting network such that its outputs are properly sorted according to sort_by
     PermutationSort {
         inputs: Vec<Vec<Expression>>, // Array of tuples to sort
         tuple: u32, // tuple size; if 1 then inputs is a single array [a0,a1,..], if 2 then inputs=[(a0,b0),..] is [a0,b0,a1,b1,..], etc..
         bits: Vec<Witness>, // control bits of the network which permutes the inputs into its sorted version
-        sort_by: Vec<u32>, // specify primary index to sort by, then the secondary,... For instance, if tuple is 2 and sort_by is [1,0], then a=[(a0,b0),..] is sorted by bi and then ai.
+        sort_by: Vec<SortKey>, // specify primary key to sort by, then the secondary,... For instance, if tuple is 2 and sort_by is [1,0], then a=[(a0,b0),..] is sorted by bi and then ai.
     },
     Log(LogInfo),
+
+    //Performs euclidian division of a = \sum a[i]*2^(limb_bits*i) by the modulus (as integers), where the a[i] are signed,
+    //and stores the limbs of the quotient in q and the limbs of the rest in r, in little endian form
+    BigIntQuotient {
+        a: Vec<Expression>,
+        limb_bits: u32,
+        modulus: Vec<u8>, // big endian bytes of the modulus
+        q: Vec<Witness>,
+        r: Vec<Witness>,
+    },
+
+    //Performs signed division of a / b, where a and b are integers of bit_size bits in two's complement form,
+    //and stores the quotient truncated toward zero in q and the rest, which has the sign of a, in r, in the same form
+    SignedQuotient {
+        a: Expression,
+        b: Expression,
+        q: Witness,
+        r: Witness,
+        predicate: Option<Expression>,
+        bit_size: u32,
+    },
+
+    // Computes the values read by the operations of a memory block, by replaying its trace in order
+    // This directive is used by the arithmetic fallback of memory blocks, whose constraints only check the consistency of the trace
+    ReadMemory(MemoryBlock),
 }
 
 impl Directive {
@@ -52,6 +89,10 @@ impl Directive {
             Directive::ToLeRadix { .. } => "to_le_radix",
             Directive::PermutationSort { .. } => "permutation_sort",
             Directive::Log { .. } => "log",
+            Directive::BigIntQuotient { .. } => "bigint_quotient",
+            Directive::ReadMemory(_) => "read_memory",
+            Directive::SignedQuotient { .. } => "signed_quotient",
+            Directive::ToBeRadix { .. } => "to_be_radix",
         }
     }
     fn to_u16(&self) -> u16 {
@@ -61,6 +102,10 @@ impl Directive {
             Directive::ToLeRadix { .. } => 2,
             Directive::PermutationSort { .. } => 3,
             Directive::Log { .. } => 4,
+            Directive::BigIntQuotient { .. } => 5,
+            Directive::ReadMemory(_) => 6,
+            Directive::SignedQuotient { .. } => 7,
+            Directive::ToBeRadix { .. } => 8,
         }
     }
 
@@ -84,7 +129,7 @@ impl Directive {
                     pred.write(&mut writer)?;
                 }
             }
-            Directive::ToLeRadix { a, b, radix } => {
+            Directive::ToLeRadix { a, b, radix } | Directive::ToBeRadix { a, b, radix } => {
                 a.write(&mut writer)?;
                 write_u32(&mut writer, b.len() as u32)?;
                 for bit in b {
@@ -105,8 +150,8 @@ impl Directive {
                     write_u32(&mut writer, b.witness_index())?;
                 }
                 write_u32(&mut writer, sort_by.len() as u32)?;
-                for i in sort_by {
-                    write_u32(&mut writer, *i)?;
+                for key in sort_by {
+                    key.write(&mut writer)?;
                 }
             }
             Directive::Log(info) => {
@@ -122,8 +167,47 @@ impl Directive {
                             write_u32(&mut writer, w.witness_index())?;
                         }
                     }
+                    LogInfo::FormattedOutput { template, placeholders, witnesses } => {
+                        write_u32(&mut writer, template.len() as u32)?;
+                        write_bytes(&mut writer, template.as_bytes())?;
+                        write_u32(&mut writer, placeholders.len() as u32)?;
+                        for placeholder in placeholders {
+                            placeholder.write(&mut writer)?;
+                        }
+                        write_witnesses(&mut writer, witnesses)?;
+                    }
                 }
             }
+            Directive::BigIntQuotient { a, limb_bits, modulus, q, r } => {
+                write_u32(&mut writer, a.len() as u32)?;
+                for column in a {
+                    column.write(&mut writer)?;
+                }
+                write_u32(&mut writer, *limb_bits)?;
+                write_u32(&mut writer, modulus.len() as u32)?;
+                write_bytes(&mut writer, modulus)?;
+                for limbs in [q, r] {
+                    write_u32(&mut writer, limbs.len() as u32)?;
+                    for limb in limbs {
+                        write_u32(&mut writer, limb.witness_index())?;
+                    }
+                }
+            }
+            Directive::ReadMemory(block) => block.write(&mut writer)?,
+            Directive::SignedQuotient { a, b, q, r, predicate, bit_size } => {
+                a.write(&mut writer)?;
+                b.write(&mut writer)?;
+                write_u32(&mut writer, q.witness_index())?;
+                write_u32(&mut writer, r.witness_index())?;
+
+                let predicate_is_some = vec![predicate.is_some() as u8];
+                write_bytes(&mut writer, &predicate_is_some)?;
+
+                if let Some(pred) = predicate {
+                    pred.write(&mut writer)?;
+                }
+                write_u32(&mut writer, *bit_size)?;
+            }
         };
 
         Ok(())
@@ -153,7 +237,7 @@ impl Directive {
 
                 Ok(Directive::Quotient { a, b, q, r, predicate })
             }
-            2 => {
+            2 | 8 => {
                 let a = Expression::read(&mut reader)?;
                 let b_len = read_u32(&mut reader)?;
                 let mut b = Vec::with_capacity(b_len as usize);
@@ -163,33 +247,16 @@ impl Directive {
                 }
 
                 let radix = read_u32(&mut reader)?;
-
-                Ok(Directive::ToLeRadix { a, b, radix })
-            }
-            3 => {
-                let tuple = read_u32(&mut reader)?;
-                let a_len = read_u32(&mut reader)?;
-                let mut a = Vec::with_capacity(a_len as usize);
-                for _ in 0..a_len {
-                    let mut element = Vec::new();
-                    for _ in 0..tuple {
-                        element.push(Expression::read(&mut reader)?);
-                    }
-                    a.push(element);
+                if !is_valid_radix(radix) {
+                    return Err(std::io::ErrorKind::InvalidData.into());
                 }
 
-                let bits_len = read_u32(&mut reader)?;
-                let mut bits = Vec::with_capacity(bits_len as usize);
-                for _ in 0..bits_len {
-                    bits.push(Witness(read_u32(&mut reader)?));
-                }
-                let sort_by_len = read_u32(&mut reader)?;
-                let mut sort_by = Vec::with_capacity(sort_by_len as usize);
-                for _ in 0..sort_by_len {
-                    sort_by.push(read_u32(&mut reader)?);
+                match directive_index {
+                    2 => Ok(Directive::ToLeRadix { a, b, radix }),
+                    _ => Ok(Directive::ToBeRadix { a, b, radix }),
                 }
-                Ok(Directive::PermutationSort { inputs: a, tuple, bits, sort_by })
             }
+            3 => Directive::read_permutation_sort(reader, |reader| SortKey::read(reader)),
             4 => {
                 let log_info_index = read_u16(&mut reader)?;
                 let output_len = read_u32(&mut reader)?;
@@ -208,14 +275,151 @@ impl Directive {
                         }
                         LogInfo::WitnessOutput(output_witnesses)
                     }
+                    2 => {
+                        let template_bytes = read_bytes(&mut reader, output_len as usize)?;
+                        let template = String::from_utf8(template_bytes)
+                            .or::<std::io::Error>(Err(std::io::ErrorKind::InvalidData.into()))?;
+                        let placeholders_len = read_u32(&mut reader)?;
+                        let mut placeholders = Vec::with_capacity(placeholders_len as usize);
+                        for _ in 0..placeholders_len {
+                            placeholders.push(LogPlaceholder::read(&mut reader)?);
+                        }
+                        let witnesses = read_witnesses(&mut reader)?;
+                        LogInfo::FormattedOutput { template, placeholders, witnesses }
+                    }
                     _ => return Err(std::io::ErrorKind::InvalidData.into()),
                 };
                 Ok(Directive::Log(log_info))
             }
+            5 => {
+                let a_len = read_u32(&mut reader)?;
+                let mut a = Vec::with_capacity(a_len as usize);
+                for _ in 0..a_len {
+                    a.push(Expression::read(&mut reader)?);
+                }
+                let limb_bits = read_u32(&mut reader)?;
+                let modulus_len = read_u32(&mut reader)?;
+                let modulus = read_bytes(&mut reader, modulus_len as usize)?;
+
+                let mut read_limbs = || -> std::io::Result<Vec<Witness>> {
+                    let limbs_len = read_u32(&mut reader)?;
+                    let mut limbs = Vec::with_capacity(limbs_len as usize);
+                    for _ in 0..limbs_len {
+                        limbs.push(Witness(read_u32(&mut reader)?));
+                    }
+                    Ok(limbs)
+                };
+                let q = read_limbs()?;
+                let r = read_limbs()?;
+                Ok(Directive::BigIntQuotient { a, limb_bits, modulus, q, r })
+            }
+            6 => Ok(Directive::ReadMemory(MemoryBlock::read(&mut reader)?)),
+            7 => {
+                let a = Expression::read(&mut reader)?;
+                let b = Expression::read(&mut reader)?;
+                let q = Witness(read_u32(&mut reader)?);
+                let r = Witness(read_u32(&mut reader)?);
+
+                // Read byte to figure out if there is a predicate
+                let predicate_is_some = read_n::<1, _>(&mut reader)?[0] != 0;
+                let predicate = match predicate_is_some {
+                    true => Some(Expression::read(&mut reader)?),
+                    false => None,
+                };
+                let bit_size = read_u32(&mut reader)?;
+
+                Ok(Directive::SignedQuotient { a, b, q, r, predicate, bit_size })
+            }
 
             _ => Err(std::io::ErrorKind::InvalidData.into()),
         }
     }
+
+    /// Reads a circuit directive serialized before version 1 of the circuit format,
+    /// where the sort keys of a permutation sort were only the indices of the tuple elements.
+    pub(crate) fn read_legacy<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let directive_index = read_u16(&mut reader)?;
+
+        match directive_index {
+            3 => Directive::read_permutation_sort(reader, |reader| {
+                Ok(SortKey::ascending(read_u32(reader)?))
+            }),
+            // The encoding of the other legacy directives is unchanged
+            0 | 1 | 2 | 4 => Directive::read((&directive_index.to_le_bytes()[..]).chain(reader)),
+            _ => Err(std::io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    fn read_permutation_sort<R: Read>(
+        mut reader: R,
+        read_key: impl Fn(&mut R) -> std::io::Result<SortKey>,
+    ) -> std::io::Result<Self> {
+        let tuple = read_u32(&mut reader)?;
+        let a_len = read_u32(&mut reader)?;
+        let mut a = Vec::with_capacity(a_len as usize);
+        for _ in 0..a_len {
+            let mut element = Vec::new();
+            for _ in 0..tuple {
+                element.push(Expression::read(&mut reader)?);
+            }
+            a.push(element);
+        }
+
+        let bits_len = read_u32(&mut reader)?;
+        let mut bits = Vec::with_capacity(bits_len as usize);
+        for _ in 0..bits_len {
+            bits.push(Witness(read_u32(&mut reader)?));
+        }
+        let sort_by_len = read_u32(&mut reader)?;
+        let mut sort_by = Vec::with_capacity(sort_by_len as usize);
+        for _ in 0..sort_by_len {
+            sort_by.push(read_key(&mut reader)?);
+        }
+        Ok(Directive::PermutationSort { inputs: a, tuple, bits, sort_by })
+    }
+}
+
+/// A column by which the PermutationSort directive sorts its tuples, and how its values are ordered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    /// Position of the column in the tuples
+    pub index: u32,
+    /// Whether the largest values come first
+    pub descending: bool,
+    /// If set, the values are compared as integers of this bit size in two's complement form,
+    /// otherwise they are compared as unsigned integers
+    pub signed_bit_size: Option<u32>,
+}
+
+impl SortKey {
+    /// Sorts by the column at `index`, from the smallest unsigned value to the largest
+    pub fn ascending(index: u32) -> Self {
+        SortKey { index, descending: false, signed_bit_size: None }
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_u32(&mut writer, self.index)?;
+        write_bytes(&mut writer, &[self.descending as u8, self.signed_bit_size.is_some() as u8])?;
+        if let Some(bit_size) = self.signed_bit_size {
+            write_u32(&mut writer, bit_size)?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let index = read_u32(&mut reader)?;
+        let [descending, is_signed] = read_n::<2, _>(&mut reader)?;
+        let signed_bit_size = match is_signed != 0 {
+            true => Some(read_u32(&mut reader)?),
+            false => None,
+        };
+        Ok(SortKey { index, descending: descending != 0, signed_bit_size })
+    }
+}
+
+/// Returns whether digits can be computed in base `radix`, which must be between 2 and 256.
+pub fn is_valid_radix(radix: u32) -> bool {
+    (2..=256).contains(&radix)
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -226,6 +430,13 @@ impl Directive {
 pub enum LogInfo {
     FinalizedOutput(String),
     WitnessOutput(Vec<Witness>),
+    /// Formats the values of `witnesses` into `template`, in which each `{}` is replaced by the next placeholder.
+    /// The placeholders consume the witnesses in order, `{{` and `}}` are printed as `{` and `}`.
+    FormattedOutput {
+        template: String,
+        placeholders: Vec<LogPlaceholder>,
+        witnesses: Vec<Witness>,
+    },
 }
 
 impl LogInfo {
@@ -233,6 +444,85 @@ impl LogInfo {
         match self {
             LogInfo::FinalizedOutput(_) => 0,
             LogInfo::WitnessOutput(_) => 1,
+            LogInfo::FormattedOutput { .. } => 2,
+        }
+    }
+}
+
+// Maximum nesting of the arrays of a log placeholder, which bounds the recursion when reading them
+const MAX_PLACEHOLDER_DEPTH: usize = 32;
+
+/// The type of a value formatted into a log message
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogPlaceholder {
+    /// A field element, printed in hexadecimal
+    Field,
+    /// A signed integer in two's complement over `bit_size` bits, printed in decimal
+    SignedInteger { bit_size: u32 },
+    /// A string of `len` bytes, one per witness
+    String { len: u32 },
+    /// An array of `len` values of the same type
+    Array { len: u32, element: Box<LogPlaceholder> },
+}
+
+impl LogPlaceholder {
+    /// Returns the number of witnesses holding the value of the placeholder,
+    /// or None if it does not fit in a `usize`
+    pub fn num_witnesses(&self) -> Option<usize> {
+        match self {
+            LogPlaceholder::Field | LogPlaceholder::SignedInteger { .. } => Some(1),
+            LogPlaceholder::String { len } => Some(*len as usize),
+            LogPlaceholder::Array { len, element } => {
+                (*len as usize).checked_mul(element.num_witnesses()?)
+            }
+        }
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        match self {
+            LogPlaceholder::Field => {
+                write_u16(&mut writer, 0)?;
+            }
+            LogPlaceholder::SignedInteger { bit_size } => {
+                write_u16(&mut writer, 1)?;
+                write_u32(&mut writer, *bit_size)?;
+            }
+            LogPlaceholder::String { len } => {
+                write_u16(&mut writer, 2)?;
+                write_u32(&mut writer, *len)?;
+            }
+            LogPlaceholder::Array { len, element } => {
+                write_u16(&mut writer, 3)?;
+                write_u32(&mut writer, *len)?;
+                element.write(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: R) -> std::io::Result<Self> {
+        LogPlaceholder::read_nested(reader, 0)
+    }
+
+    // Reads a placeholder nested in `depth` arrays
+    fn read_nested<R: Read>(mut reader: R, depth: usize) -> std::io::Result<Self> {
+        match read_u16(&mut reader)? {
+            0 => Ok(LogPlaceholder::Field),
+            1 => {
+                // The values of signed integers must fit in a field element
+                let bit_size = read_u32(&mut reader)?;
+                if bit_size == 0 || bit_size >= FieldElement::max_num_bits() {
+                    return Err(std::io::ErrorKind::InvalidData.into());
+                }
+                Ok(LogPlaceholder::SignedInteger { bit_size })
+            }
+            2 => Ok(LogPlaceholder::String { len: read_u32(&mut reader)? }),
+            3 if depth < MAX_PLACEHOLDER_DEPTH => {
+                let len = read_u32(&mut reader)?;
+                let element = Box::new(LogPlaceholder::read_nested(reader, depth + 1)?);
+                Ok(LogPlaceholder::Array { len, element })
+            }
+            _ => Err(std::io::ErrorKind::InvalidData.into()),
         }
     }
 }
@@ -263,32 +553,80 @@ fn serialization_roundtrip() {
         r: Witness(2u32),
         predicate: Some(Expression::default()),
     };
+    let signed_quotient = Directive::SignedQuotient {
+        a: Expression::from(Witness(3u32)),
+        b: Expression::default(),
+        q: Witness(1u32),
+        r: Witness(2u32),
+        predicate: Some(Expression::one()),
+        bit_size: 32,
+    };
 
     let to_le_radix = Directive::ToLeRadix {
         a: Expression::default(),
         b: vec![Witness(1u32), Witness(2u32), Witness(3u32), Witness(4u32)],
         radix: 4,
     };
+    let to_be_radix = Directive::ToBeRadix {
+        a: Expression::from(Witness(5u32)),
+        b: vec![Witness(1u32), Witness(2u32)],
+        radix: 256,
+    };
 
     let permutation_sort = Directive::PermutationSort {
         inputs: vec![vec![Expression::default()], vec![Expression::default()]],
         tuple: 1,
         bits: vec![Witness(1u32), Witness(2u32)],
-        sort_by: vec![0, 1],
+        sort_by: vec![
+            SortKey::ascending(0),
+            SortKey { index: 1, descending: true, signed_bit_size: Some(32) },
+        ],
     };
 
     let log_string = Directive::Log(LogInfo::FinalizedOutput("test string to log".to_owned()));
     let log_witnesses =
         Directive::Log(LogInfo::WitnessOutput(vec![Witness(1u32), Witness(2u32), Witness(3u32)]));
+    let log_formatted = Directive::Log(LogInfo::FormattedOutput {
+        template: "balance = {}, nonce = {}, history = {}".to_owned(),
+        placeholders: vec![
+            LogPlaceholder::Field,
+            LogPlaceholder::SignedInteger { bit_size: 64 },
+            LogPlaceholder::Array { len: 2, element: Box::new(LogPlaceholder::String { len: 3 }) },
+        ],
+        witnesses: (1..9).map(Witness).collect(),
+    });
+
+    let bigint_quotient = Directive::BigIntQuotient {
+        a: vec![Expression::default(), Expression::from(Witness(1u32))],
+        limb_bits: 64,
+        modulus: vec![1, 0, 0],
+        q: vec![Witness(2u32)],
+        r: vec![Witness(3u32), Witness(4u32)],
+    };
+
+    let read_memory = Directive::ReadMemory(MemoryBlock {
+        id: crate::circuit::opcodes::BlockId(2),
+        len: 1,
+        trace: vec![crate::circuit::opcodes::MemOp {
+            operation: Expression::one(),
+            index: Expression::zero(),
+            value: Expression::from(Witness(1u32)),
+        }],
+    });
 
     let directives = vec![
         invert,
         quotient_none,
         quotient_predicate,
+        signed_quotient,
         to_le_radix,
+        to_be_radix,
         log_string,
         log_witnesses,
+        log_formatted,
         permutation_sort,
+        bigint_quotient,
+        read_memory,
     ];
 
     for directive in directives {
@@ -296,3 +634,40 @@ fn serialization_roundtrip() {
         assert_eq!(dir, got_dir);
     }
 }
+
+#[test]
+fn rejects_invalid_radix() {
+    for radix in [0, 1, 257] {
+        let directive =
+            Directive::ToBeRadix { a: Expression::default(), b: vec![Witness(1u32)], radix };
+        let mut bytes = Vec::new();
+        directive.write(&mut bytes).unwrap();
+        assert!(Directive::read(&*bytes).is_err(), "radix {radix}");
+    }
+}
+
+#[test]
+fn rejects_invalid_log_placeholders() {
+    let nested = |depth| {
+        (0..depth).fold(LogPlaceholder::Field, |element, _| LogPlaceholder::Array {
+            len: 1,
+            element: Box::new(element),
+        })
+    };
+    let read_placeholder = |placeholder| {
+        let directive = Directive::Log(LogInfo::FormattedOutput {
+            template: "{}".to_owned(),
+            placeholders: vec![placeholder],
+            witnesses: vec![Witness(1u32)],
+        });
+        let mut bytes = Vec::new();
+        directive.write(&mut bytes).unwrap();
+        Directive::read(&*bytes)
+    };
+
+    assert!(read_placeholder(nested(MAX_PLACEHOLDER_DEPTH)).is_ok());
+    assert!(read_placeholder(nested(MAX_PLACEHOLDER_DEPTH + 1)).is_err());
+    for bit_size in [0, FieldElement::max_num_bits(), u32::MAX] {
+        assert!(read_placeholder(LogPlaceholder::SignedInteger { bit_size }).is_err());
+    }
+}
@@ -3,6 +3,8 @@ pub mod directives;
 pub mod opcodes;
 pub use opcodes::Opcode;
 
+use opcodes::LookupTable;
+
 use crate::native_types::Witness;
 use crate::serialization::{read_u32, write_u32};
 use serde::{Deserialize, Serialize};
@@ -10,7 +12,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::io::prelude::*;
 
-const VERSION_NUMBER: u32 = 0;
+// Version 1 changed the encoding of black box function calls, starting with their optional predicate
+const VERSION_NUMBER: u32 = 1;
+// Circuits serialized with this version are still read, they have no lookup tables
+const LEGACY_VERSION_NUMBER: u32 = 0;
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct Circuit {
@@ -26,6 +31,8 @@ pub struct Circuit {
     pub public_parameters: PublicInputs,
     /// The set of public inputs calculated within the circuit.
     pub return_values: PublicInputs,
+    /// The tables which can be referenced by name by the lookup opcodes of the circuit.
+    pub tables: Vec<LookupTable>,
 }
 
 impl Circuit {
@@ -58,6 +65,11 @@ impl Circuit {
             write_u32(&mut writer, public_output_index)?;
         }
 
+        write_u32(&mut writer, self.tables.len() as u32)?;
+        for table in &self.tables {
+            table.write(&mut writer)?;
+        }
+
         write_u32(&mut writer, self.opcodes.len() as u32)?;
         for opcode in &self.opcodes {
             opcode.write(&mut writer)?;
@@ -71,7 +83,8 @@ impl Circuit {
         //
         // We may also not want to do that if we do not want to couple serialization
         // with other breaking changes
-        if version_number != VERSION_NUMBER {
+        let is_legacy = version_number == LEGACY_VERSION_NUMBER;
+        if version_number != VERSION_NUMBER && !is_legacy {
             return Err(std::io::ErrorKind::InvalidData.into());
         }
 
@@ -90,6 +103,12 @@ impl Circuit {
             return_values.0.insert(return_value_index);
         }
 
+        let num_tables = if is_legacy { 0 } else { read_u32(&mut reader)? };
+        let mut tables = Vec::new();
+        for _ in 0..num_tables {
+            tables.push(LookupTable::read(&mut reader)?);
+        }
+
         let num_opcodes = read_u32(&mut reader)?;
 
         let mut opcodes = Vec::new();
@@ -97,11 +116,14 @@ impl Circuit {
             .try_reserve_exact(num_opcodes as usize)
             .map_err(|_| std::io::ErrorKind::InvalidData)?;
         for _ in 0..num_opcodes {
-            let opcode = Opcode::read(&mut reader)?;
+            let opcode = match is_legacy {
+                true => Opcode::read_legacy(&mut reader)?,
+                false => Opcode::read(&mut reader)?,
+            };
             opcodes.push(opcode)
         }
 
-        Ok(Self { current_witness_index, opcodes, public_parameters, return_values })
+        Ok(Self { current_witness_index, opcodes, public_parameters, return_values, tables })
     }
 }
 
@@ -129,6 +151,10 @@ impl std::fmt::Display for Circuit {
         write!(f, "return value indices : ")?;
         write_public_inputs(f, &self.return_values)?;
 
+        for table in &self.tables {
+            writeln!(f, "lookup table {} : {} rows", table.name, table.rows.len())?;
+        }
+
         for opcode in &self.opcodes {
             writeln!(f, "{opcode}")?
         }
@@ -161,27 +187,40 @@ mod test {
     use std::collections::BTreeSet;
 
     use super::{
-        opcodes::{BlackBoxFuncCall, FunctionInput, OracleData},
+        directives::{Directive, SortKey},
+        opcodes::{BlackBoxFuncCall, FunctionInput, Lookup, LookupTable, OracleData},
         Circuit, Opcode, PublicInputs,
     };
+    use crate::circuit::black_box_functions::{HashInputPacking, HashOutputMode};
     use crate::native_types::{Expression, Witness};
     use acir_field::FieldElement;
 
     fn and_opcode() -> Opcode {
-        Opcode::BlackBoxFuncCall(BlackBoxFuncCall {
-            name: crate::BlackBoxFunc::AND,
-            inputs: vec![
-                FunctionInput { witness: Witness(1), num_bits: 4 },
-                FunctionInput { witness: Witness(2), num_bits: 4 },
-            ],
-            outputs: vec![Witness(3)],
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+            lhs: FunctionInput::witness(Witness(1), 4),
+            rhs: FunctionInput::witness(Witness(2), 4),
+            output: Witness(3),
+            predicate: None,
+        })
+    }
+    fn constant_and_opcode() -> Opcode {
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+            lhs: FunctionInput::witness(Witness(1), 4),
+            rhs: FunctionInput::constant(FieldElement::from(7u128), 4),
+            output: Witness(3),
+            predicate: None,
         })
     }
     fn range_opcode() -> Opcode {
-        Opcode::BlackBoxFuncCall(BlackBoxFuncCall {
-            name: crate::BlackBoxFunc::RANGE,
-            inputs: vec![FunctionInput { witness: Witness(1), num_bits: 8 }],
-            outputs: vec![],
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+            input: FunctionInput::witness(Witness(1), 8),
+            predicate: None,
+        })
+    }
+    fn predicated_range_opcode() -> Opcode {
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+            input: FunctionInput::witness(Witness(1), 8),
+            predicate: Some(Expression::from(Witness(2))),
         })
     }
     fn oracle_opcode() -> Opcode {
@@ -198,13 +237,37 @@ mod test {
         })
     }
 
+    fn lookup_opcode() -> Opcode {
+        Opcode::Lookup(Lookup {
+            table: String::from("double"),
+            keys: vec![Expression::from(Witness(1))],
+            values: vec![Expression::from(Witness(5))],
+        })
+    }
+    fn double_table() -> LookupTable {
+        LookupTable {
+            name: String::from("double"),
+            rows: (0..4_u128)
+                .map(|x| vec![FieldElement::from(x), FieldElement::from(2 * x)])
+                .collect(),
+        }
+    }
+
     #[test]
     fn serialization_roundtrip() {
         let circuit = Circuit {
             current_witness_index: 5,
-            opcodes: vec![and_opcode(), range_opcode(), oracle_opcode()],
+            opcodes: vec![
+                and_opcode(),
+                constant_and_opcode(),
+                range_opcode(),
+                predicated_range_opcode(),
+                oracle_opcode(),
+                lookup_opcode(),
+            ],
             public_parameters: PublicInputs(BTreeSet::from_iter(vec![Witness(2), Witness(12)])),
             return_values: PublicInputs(BTreeSet::from_iter(vec![Witness(4), Witness(12)])),
+            tables: vec![double_table()],
         };
 
         fn read_write(circuit: Circuit) -> (Circuit, Circuit) {
@@ -229,11 +292,14 @@ mod test {
                     q_c: FieldElement::from(8u128),
                 }),
                 range_opcode(),
+                predicated_range_opcode(),
                 and_opcode(),
+                constant_and_opcode(),
                 oracle_opcode(),
             ],
             public_parameters: PublicInputs(BTreeSet::from_iter(vec![Witness(2)])),
             return_values: PublicInputs(BTreeSet::from_iter(vec![Witness(2)])),
+            tables: vec![double_table()],
         };
 
         let json = serde_json::to_string_pretty(&circuit).unwrap();
@@ -241,4 +307,77 @@ mod test {
         let deserialized = serde_json::from_str(&json).unwrap();
         assert_eq!(circuit, deserialized);
     }
+
+    #[test]
+    fn deserializes_witness_function_inputs() {
+        let json = r#"{ "witness": 1, "num_bits": 8 }"#;
+        let input: FunctionInput = serde_json::from_str(json).unwrap();
+        assert_eq!(input, FunctionInput::witness(Witness(1), 8));
+    }
+
+    #[test]
+    fn reads_legacy_circuits() {
+        // Written by the encoder of the last release before version 1 of the circuit format
+        let bytes = include_bytes!("../../test_data/legacy_circuit.bin");
+
+        let inputs = |start: u32, len: u32| -> Vec<FunctionInput> {
+            (start..start + len).map(|i| FunctionInput::witness(Witness(i), 8)).collect()
+        };
+        let expected = Circuit {
+            current_witness_index: 300,
+            opcodes: vec![
+                Opcode::Arithmetic(Expression::from(Witness(1))),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+                    lhs: FunctionInput::witness(Witness(1), 8),
+                    rhs: FunctionInput::witness(Witness(2), 8),
+                    output: Witness(3),
+                    predicate: None,
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                    input: FunctionInput::witness(Witness(1), 8),
+                    predicate: None,
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+                    public_key_x: inputs(1, 32),
+                    public_key_y: inputs(33, 32),
+                    signature: inputs(65, 64),
+                    hashed_message: inputs(129, 32),
+                    require_low_s: true,
+                    output: Witness(200),
+                    predicate: None,
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify {
+                    public_key_x: FunctionInput::witness(Witness(1), 8),
+                    public_key_y: FunctionInput::witness(Witness(2), 8),
+                    signature: inputs(3, 64),
+                    message: inputs(67, 4),
+                    output: Witness(201),
+                    predicate: None,
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SHA256 {
+                    inputs: inputs(1, 3),
+                    var_message_size: None,
+                    input_packing: HashInputPacking::default(),
+                    output_mode: HashOutputMode::default(),
+                    outputs: (202..234).map(Witness).collect(),
+                    predicate: None,
+                }),
+                Opcode::Directive(Directive::PermutationSort {
+                    inputs: vec![
+                        vec![Expression::from(Witness(1))],
+                        vec![Expression::from(Witness(2))],
+                    ],
+                    tuple: 1,
+                    bits: vec![Witness(235)],
+                    sort_by: vec![SortKey::ascending(0)],
+                }),
+            ],
+            public_parameters: PublicInputs(BTreeSet::from([Witness(1)])),
+            return_values: PublicInputs(BTreeSet::new()),
+            tables: Vec::new(),
+        };
+
+        let circuit = Circuit::read(&bytes[..]).unwrap();
+        assert_eq!(circuit, expected);
+    }
 }
@@ -2,17 +2,25 @@ use std::io::{Read, Write};
 
 use super::directives::{Directive, LogInfo};
 use crate::native_types::Expression;
-use crate::serialization::{read_n, write_bytes};
+use crate::serialization::{read_n, read_u32, write_bytes, write_u32};
 
 use serde::{Deserialize, Serialize};
 
+mod bigint;
 mod black_box_function_call;
 mod block;
+mod lookup;
 mod oracle_data;
+mod recursion;
 
-pub use black_box_function_call::{BlackBoxFuncCall, FunctionInput};
-pub use block::{BlockId, MemOp, MemoryBlock};
+pub use bigint::{BigIntCall, BigIntOp};
+pub use black_box_function_call::{
+    check_range_bounds, BlackBoxFuncCall, ConstantOrWitness, FunctionInput,
+};
+pub use block::{BlockId, MemOp, MemoryBlock, MemoryOpError};
+pub use lookup::{Lookup, LookupTable};
 pub use oracle_data::OracleData;
+pub use recursion::RecursiveAggregation;
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Opcode {
@@ -34,6 +42,25 @@ pub enum Opcode {
     /// RAM is required for Aztec Backend as dynamic memory implementation in Barrentenberg requires an intialisation phase and can only handle constant values for operations.
     RAM(MemoryBlock),
     Oracle(OracleData),
+    /// Arithmetic over big integers represented as limbs, modulo a declared modulus
+    BigInt(BigIntCall),
+    /// Verifies an inner proof and outputs the updated aggregation object, see [`RecursiveAggregation`]
+    RecursiveAggregation(RecursiveAggregation),
+    /// Asserts that a tuple of expressions is a row of one of the circuit's lookup tables
+    Lookup(Lookup),
+    /// Initializes the memory block `id` with one element per expression of `init`.
+    /// The block can then be accessed by the [MemoryOp][Opcode::MemoryOp] opcodes which follow it.
+    MemoryInit {
+        id: BlockId,
+        init: Vec<Expression>,
+    },
+    /// A single read (0) or write (1) operation on the memory block `id`.
+    /// Unlike the operations in the trace of a [MemoryBlock], memory operations are placed inline with the other opcodes,
+    /// and the operations on a block are applied in the order of the opcodes.
+    MemoryOp {
+        id: BlockId,
+        op: MemOp,
+    },
 }
 
 impl Opcode {
@@ -43,11 +70,16 @@ impl Opcode {
         match self {
             Opcode::Arithmetic(_) => "arithmetic",
             Opcode::Directive(directive) => directive.name(),
-            Opcode::BlackBoxFuncCall(g) => g.name.name(),
+            Opcode::BlackBoxFuncCall(g) => g.name(),
             Opcode::Block(_) => "block",
             Opcode::RAM(_) => "ram",
             Opcode::ROM(_) => "rom",
             Opcode::Oracle(data) => &data.name,
+            Opcode::BigInt(call) => call.name(),
+            Opcode::RecursiveAggregation(_) => "recursive_aggregation",
+            Opcode::Lookup(_) => "lookup",
+            Opcode::MemoryInit { .. } => "memory_init",
+            Opcode::MemoryOp { .. } => "memory_op",
         }
     }
 
@@ -62,6 +94,11 @@ impl Opcode {
             Opcode::ROM(_) => 4,
             Opcode::RAM(_) => 5,
             Opcode::Oracle { .. } => 6,
+            Opcode::BigInt(_) => 7,
+            Opcode::RecursiveAggregation(_) => 8,
+            Opcode::Lookup(_) => 9,
+            Opcode::MemoryInit { .. } => 10,
+            Opcode::MemoryOp { .. } => 11,
         }
     }
 
@@ -88,6 +125,21 @@ impl Opcode {
                 mem_block.write(writer)
             }
             Opcode::Oracle(data) => data.write(writer),
+            Opcode::BigInt(call) => call.write(writer),
+            Opcode::RecursiveAggregation(aggregation) => aggregation.write(writer),
+            Opcode::Lookup(lookup) => lookup.write(writer),
+            Opcode::MemoryInit { id, init } => {
+                write_u32(&mut writer, id.0)?;
+                write_u32(&mut writer, init.len() as u32)?;
+                for value in init {
+                    value.write(&mut writer)?;
+                }
+                Ok(())
+            }
+            Opcode::MemoryOp { id, op } => {
+                write_u32(&mut writer, id.0)?;
+                op.write(writer)
+            }
         }
     }
     pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
@@ -125,6 +177,45 @@ impl Opcode {
                 let data = OracleData::read(reader)?;
                 Ok(Opcode::Oracle(data))
             }
+            7 => {
+                let call = BigIntCall::read(reader)?;
+                Ok(Opcode::BigInt(call))
+            }
+            8 => {
+                let aggregation = RecursiveAggregation::read(reader)?;
+                Ok(Opcode::RecursiveAggregation(aggregation))
+            }
+            9 => {
+                let lookup = Lookup::read(reader)?;
+                Ok(Opcode::Lookup(lookup))
+            }
+            10 => {
+                let id = BlockId(read_u32(&mut reader)?);
+                let init_len = read_u32(&mut reader)?;
+                let mut init = Vec::with_capacity(init_len as usize);
+                for _ in 0..init_len {
+                    init.push(Expression::read(&mut reader)?);
+                }
+                Ok(Opcode::MemoryInit { id, init })
+            }
+            11 => {
+                let id = BlockId(read_u32(&mut reader)?);
+                let op = MemOp::read(reader)?;
+                Ok(Opcode::MemoryOp { id, op })
+            }
+            _ => Err(std::io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    /// Reads an opcode serialized before version 1 of the circuit format, which only had the first 7 opcode categories.
+    pub(crate) fn read_legacy<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let opcode_index = read_n::<1, _>(&mut reader)?[0];
+
+        match opcode_index {
+            1 => Ok(Opcode::BlackBoxFuncCall(BlackBoxFuncCall::read_legacy(reader)?)),
+            2 => Ok(Opcode::Directive(Directive::read_legacy(reader)?)),
+            // The encoding of the other legacy opcodes is unchanged
+            0 | 3..=6 => Opcode::read((&[opcode_index][..]).chain(reader)),
             _ => Err(std::io::ErrorKind::InvalidData.into()),
         }
     }
@@ -164,9 +255,30 @@ impl std::fmt::Display for Opcode {
                     r.witness_index()
                 )
             }
+            Opcode::Directive(Directive::SignedQuotient { a, b, q, r, predicate, bit_size }) => {
+                write!(f, "DIR::SIGNED_QUOTIENT ")?;
+                if let Some(pred) = predicate {
+                    writeln!(f, "PREDICATE = {pred}")?;
+                }
+
+                write!(
+                    f,
+                    "(out : _{},  (_{}, {}), _{}, bits: {})",
+                    a,
+                    q.witness_index(),
+                    b,
+                    r.witness_index(),
+                    bit_size
+                )
+            }
             Opcode::BlackBoxFuncCall(g) => write!(f, "{g}"),
-            Opcode::Directive(Directive::ToLeRadix { a, b, radix: _ }) => {
-                write!(f, "DIR::TORADIX ")?;
+            Opcode::Directive(
+                directive @ (Directive::ToLeRadix { a, b, .. } | Directive::ToBeRadix { a, b, .. }),
+            ) => {
+                match directive {
+                    Directive::ToLeRadix { .. } => write!(f, "DIR::TORADIX ")?,
+                    _ => write!(f, "DIR::TOBERADIX ")?,
+                }
                 write!(
                     f,
                     // TODO (Note): this assumes that the decomposed bits have contiguous witness indices
@@ -198,7 +310,16 @@ impl std::fmt::Display for Opcode {
                     witnesses.first().unwrap().witness_index(),
                     witnesses.last().unwrap().witness_index()
                 ),
+                LogInfo::FormattedOutput { template, .. } => write!(f, "Log: {template}"),
             },
+            Opcode::Directive(Directive::BigIntQuotient { a, q, r, .. }) => {
+                write!(f, "DIR::BIGINT_QUOTIENT ")?;
+                write!(f, "(columns: {}, q: {} limbs, r: {} limbs)", a.len(), q.len(), r.len())
+            }
+            Opcode::Directive(Directive::ReadMemory(block)) => {
+                write!(f, "DIR::READ_MEMORY ")?;
+                write!(f, "(id: {}, len: {}) ", block.id.0, block.trace.len())
+            }
             Opcode::Block(block) => {
                 write!(f, "BLOCK ")?;
                 write!(f, "(id: {}, len: {}) ", block.id.0, block.trace.len())
@@ -215,6 +336,21 @@ impl std::fmt::Display for Opcode {
                 write!(f, "ORACLE: ")?;
                 write!(f, "{data}")
             }
+            Opcode::BigInt(call) => write!(f, "{call}"),
+            Opcode::RecursiveAggregation(aggregation) => write!(f, "{aggregation}"),
+            Opcode::Lookup(lookup) => write!(f, "{lookup}"),
+            Opcode::MemoryInit { id, init } => {
+                write!(f, "INIT ")?;
+                write!(f, "(id: {}, len: {}) ", id.0, init.len())
+            }
+            Opcode::MemoryOp { id, op } => {
+                write!(f, "MEM ")?;
+                write!(
+                    f,
+                    "(id: {}, op: {}, index: {}, value: {}) ",
+                    id.0, op.operation, op.index, op.value
+                )
+            }
         }
     }
 }
@@ -227,8 +363,11 @@ impl std::fmt::Debug for Opcode {
 
 #[test]
 fn serialization_roundtrip() {
+    use crate::circuit::black_box_functions::{
+        HashInputPacking, HashOutputMode, RecoverOutputMode,
+    };
     use crate::native_types::Witness;
-    use crate::BlackBoxFunc;
+    use acir_field::FieldElement;
 
     fn read_write(opcode: Opcode) -> (Opcode, Opcode) {
         let mut bytes = Vec::new();
@@ -239,19 +378,136 @@ fn serialization_roundtrip() {
 
     let opcode_arith = Opcode::Arithmetic(Expression::default());
 
-    let opcode_black_box_func = Opcode::BlackBoxFuncCall(BlackBoxFuncCall {
-        name: BlackBoxFunc::AES,
+    let opcode_black_box_func = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES {
         inputs: vec![
-            FunctionInput { witness: Witness(1u32), num_bits: 12 },
-            FunctionInput { witness: Witness(24u32), num_bits: 32 },
+            FunctionInput::witness(Witness(1u32), 12),
+            FunctionInput::witness(Witness(24u32), 32),
         ],
         outputs: vec![Witness(123u32), Witness(245u32)],
+        predicate: None,
+    });
+
+    let opcode_black_box_func_constant = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES {
+        inputs: vec![
+            FunctionInput::witness(Witness(1u32), 12),
+            FunctionInput::constant(FieldElement::from(24u128), 32),
+        ],
+        outputs: vec![Witness(123u32), Witness(245u32)],
+        predicate: Some(Expression::from(Witness(3u32))),
+    });
+
+    let byte_inputs = |start: u32, len: u32| -> Vec<FunctionInput> {
+        (start..start + len).map(|index| FunctionInput::witness(Witness(index), 8)).collect()
+    };
+    let opcode_ecdsa = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+        public_key_x: byte_inputs(1, 32),
+        public_key_y: byte_inputs(33, 32),
+        signature: byte_inputs(65, 64),
+        hashed_message: byte_inputs(129, 32),
+        require_low_s: false,
+        output: Witness(161),
+        predicate: None,
+    });
+
+    let opcode_bigint = Opcode::BigInt(BigIntCall {
+        modulus: vec![0xff; 32],
+        limb_bits: 64,
+        op: BigIntOp::Mul {
+            lhs: (1..5).map(Witness).collect(),
+            rhs: (5..9).map(Witness).collect(),
+            output: (9..13).map(Witness).collect(),
+        },
+    });
+
+    let opcode_recursive_aggregation = Opcode::RecursiveAggregation(RecursiveAggregation {
+        verification_key: (1..5).map(Witness).collect(),
+        proof: (5..9).map(Witness).collect(),
+        public_inputs: vec![Witness(9)],
+        input_aggregation_object: Vec::new(),
+        output_aggregation_object: (10..26).map(Witness).collect(),
+    });
+
+    let opcode_scalar_mul = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::VariableBaseScalarMul {
+        point_x: FunctionInput::witness(Witness(1), FieldElement::max_num_bits()),
+        point_y: FunctionInput::witness(Witness(2), FieldElement::max_num_bits()),
+        scalar: FunctionInput::constant(FieldElement::from(5_u128), FieldElement::max_num_bits()),
+        outputs: (Witness(3), Witness(4)),
+        predicate: Some(Expression::from(Witness(5))),
     });
 
+    let opcode_ecdsa_recover = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1Recover {
+        signature: byte_inputs(1, 64),
+        recovery_id: FunctionInput::witness(Witness(65), 1),
+        hashed_message: byte_inputs(66, 32),
+        require_low_s: true,
+        output_mode: RecoverOutputMode::Address,
+        outputs: (98..118).map(Witness).collect(),
+        predicate: None,
+    });
+
+    let opcode_sha256 = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SHA256 {
+        inputs: byte_inputs(1, 64),
+        var_message_size: Some(FunctionInput::witness(Witness(65), 32)),
+        input_packing: HashInputPacking::Bits,
+        output_mode: HashOutputMode::Limbs128,
+        outputs: vec![Witness(66), Witness(67)],
+        predicate: None,
+    });
+
+    let opcode_custom = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Custom {
+        name: "poseidon".into(),
+        inputs: byte_inputs(1, 3),
+        outputs: vec![Witness(4u32)],
+        predicate: None,
+    });
+
+    let opcode_bounded_range = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BoundedRange {
+        input: FunctionInput::witness(Witness(1), 10),
+        min: FieldElement::from(24_u128),
+        max: FieldElement::from(999_u128),
+        predicate: None,
+    });
+
+    let opcode_lookup = Opcode::Lookup(Lookup {
+        table: "xor_4".into(),
+        keys: vec![Expression::from(Witness(1)), Expression::from(Witness(2))],
+        values: vec![Expression::from(Witness(3))],
+    });
+
+    let opcode_memory_init = Opcode::MemoryInit {
+        id: BlockId(3),
+        init: vec![Expression::from(Witness(1)), Expression::from_field(FieldElement::one())],
+    };
+
+    let opcode_memory_op = Opcode::MemoryOp {
+        id: BlockId(3),
+        op: MemOp {
+            operation: Expression::one(),
+            index: Expression::from(Witness(2)),
+            value: Expression::from(Witness(3)),
+        },
+    };
+
     let opcode_directive =
         Opcode::Directive(Directive::Invert { x: Witness(1234u32), result: Witness(56789u32) });
 
-    let opcodes = vec![opcode_arith, opcode_black_box_func, opcode_directive];
+    let opcodes = vec![
+        opcode_arith,
+        opcode_black_box_func,
+        opcode_black_box_func_constant,
+        opcode_ecdsa,
+        opcode_ecdsa_recover,
+        opcode_scalar_mul,
+        opcode_bigint,
+        opcode_recursive_aggregation,
+        opcode_sha256,
+        opcode_custom,
+        opcode_bounded_range,
+        opcode_lookup,
+        opcode_memory_init,
+        opcode_memory_op,
+        opcode_directive,
+    ];
 
     for opcode in opcodes {
         let (op, got_op) = read_write(opcode);
@@ -259,6 +515,130 @@ fn serialization_roundtrip() {
     }
 }
 
+#[test]
+fn rejects_black_box_call_with_invalid_layout() {
+    use crate::native_types::Witness;
+    use crate::BlackBoxFunc;
+
+    // AND takes exactly two inputs
+    let inputs = vec![FunctionInput::witness(Witness(1), 8)];
+    let call = BlackBoxFuncCall::from_parts(BlackBoxFunc::AND, inputs, vec![Witness(2)], None);
+    assert!(call.is_none());
+
+    let mut bytes = Vec::new();
+    bytes.extend(BlackBoxFunc::AND.to_u16().to_le_bytes());
+    // A single witness input with 8 bits
+    bytes.extend(1u32.to_le_bytes());
+    bytes.push(0);
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(8u32.to_le_bytes());
+    // A single output and no predicate
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(2u32.to_le_bytes());
+    bytes.push(0);
+    assert!(BlackBoxFuncCall::read(&*bytes).is_err());
+}
+
+#[test]
+fn rejects_recover_call_with_outputs_of_another_mode() {
+    use crate::circuit::black_box_functions::RecoverOutputMode;
+    use crate::native_types::Witness;
+
+    let byte_inputs = |start: u32, len: u32| -> Vec<FunctionInput> {
+        (start..start + len).map(|index| FunctionInput::witness(Witness(index), 8)).collect()
+    };
+    let call = BlackBoxFuncCall::EcdsaSecp256k1Recover {
+        signature: byte_inputs(1, 64),
+        recovery_id: FunctionInput::witness(Witness(65), 1),
+        hashed_message: byte_inputs(66, 32),
+        require_low_s: true,
+        output_mode: RecoverOutputMode::PublicKey,
+        // The outputs of an address
+        outputs: (98..118).map(Witness).collect(),
+        predicate: None,
+    };
+
+    let mut bytes = Vec::new();
+    call.write(&mut bytes).unwrap();
+    assert!(BlackBoxFuncCall::read(&*bytes).is_err());
+}
+
+#[test]
+fn rejects_recover_call_with_hashed_message_of_wrong_size() {
+    use crate::circuit::black_box_functions::RecoverOutputMode;
+    use crate::native_types::Witness;
+
+    let byte_inputs = |start: u32, len: u32| -> Vec<FunctionInput> {
+        (start..start + len).map(|index| FunctionInput::witness(Witness(index), 8)).collect()
+    };
+    for message_len in [31, 32, 33] {
+        let call = BlackBoxFuncCall::EcdsaSecp256k1Recover {
+            signature: byte_inputs(1, 64),
+            recovery_id: FunctionInput::witness(Witness(65), 1),
+            hashed_message: byte_inputs(66, message_len),
+            require_low_s: true,
+            output_mode: RecoverOutputMode::Address,
+            outputs: (100..120).map(Witness).collect(),
+            predicate: None,
+        };
+
+        let mut bytes = Vec::new();
+        call.write(&mut bytes).unwrap();
+        assert_eq!(
+            BlackBoxFuncCall::read(&*bytes).is_ok(),
+            message_len == 32,
+            "{message_len} bytes"
+        );
+    }
+}
+
+#[test]
+fn rejects_merkle_root_call_without_leaf_and_index() {
+    use crate::circuit::black_box_functions::InputSize;
+    use crate::native_types::Witness;
+    use crate::BlackBoxFunc;
+
+    let definition = BlackBoxFunc::ComputeMerkleRoot.definition();
+    assert_eq!(definition.input_size, InputSize::AtLeast(2));
+
+    for num_inputs in 0..4 {
+        let inputs =
+            (1..=num_inputs).map(|index| FunctionInput::witness(Witness(index), 254)).collect();
+        let call = BlackBoxFuncCall::from_parts(
+            BlackBoxFunc::ComputeMerkleRoot,
+            inputs,
+            vec![Witness(10)],
+            None,
+        );
+        match call {
+            Some(BlackBoxFuncCall::ComputeMerkleRoot { leaf, index, hash_path, .. }) => {
+                assert_eq!(leaf, FunctionInput::witness(Witness(1), 254));
+                assert_eq!(index, FunctionInput::witness(Witness(2), 254));
+                assert_eq!(hash_path.len() as u32, num_inputs - 2);
+            }
+            None => assert!(num_inputs < 2, "{num_inputs} inputs"),
+            Some(call) => panic!("unexpected call {call:?}"),
+        }
+    }
+}
+
+#[test]
+fn rejects_bounded_range_with_invalid_bounds() {
+    use crate::native_types::Witness;
+    use acir_field::FieldElement;
+
+    let call = BlackBoxFuncCall::BoundedRange {
+        input: FunctionInput::witness(Witness(1), FieldElement::max_num_bits()),
+        min: FieldElement::from(5_u128),
+        max: FieldElement::from(4_u128),
+        predicate: None,
+    };
+
+    let mut bytes = Vec::new();
+    call.write(&mut bytes).unwrap();
+    assert!(BlackBoxFuncCall::read(&*bytes).is_err());
+}
+
 #[test]
 fn panic_regression_187() {
     // See: https://github.com/noir-lang/acvm/issues/187
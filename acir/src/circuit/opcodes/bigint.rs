@@ -0,0 +1,143 @@
+use std::io::{Read, Write};
+
+use crate::native_types::Witness;
+use crate::serialization::{
+    read_bytes, read_n, read_u32, read_witnesses, write_bytes, write_u32, write_witnesses,
+};
+use serde::{Deserialize, Serialize};
+
+/// An operation on big integers which do not fit in a field element.
+///
+/// Each integer is represented by a list of witnesses holding its limbs in little endian order,
+/// where each limb is an integer of at most `limb_bits` bits.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum BigIntOp {
+    /// Computes `output = (lhs + rhs) % modulus`
+    Add { lhs: Vec<Witness>, rhs: Vec<Witness>, output: Vec<Witness> },
+    /// Computes `output = (lhs * rhs) % modulus`
+    Mul { lhs: Vec<Witness>, rhs: Vec<Witness>, output: Vec<Witness> },
+    /// Computes `output = input % modulus`, `input` can have more limbs than the modulus
+    ModReduce { input: Vec<Witness>, output: Vec<Witness> },
+    /// Sets `output` to 1 if `lhs < rhs` and to 0 otherwise
+    Compare { lhs: Vec<Witness>, rhs: Vec<Witness>, output: Witness },
+}
+
+impl BigIntOp {
+    pub fn name(&self) -> &str {
+        match self {
+            BigIntOp::Add { .. } => "bigint_add",
+            BigIntOp::Mul { .. } => "bigint_mul",
+            BigIntOp::ModReduce { .. } => "bigint_mod_reduce",
+            BigIntOp::Compare { .. } => "bigint_compare",
+        }
+    }
+
+    fn to_u8(&self) -> u8 {
+        match self {
+            BigIntOp::Add { .. } => 0,
+            BigIntOp::Mul { .. } => 1,
+            BigIntOp::ModReduce { .. } => 2,
+            BigIntOp::Compare { .. } => 3,
+        }
+    }
+}
+
+/// Applies a [`BigIntOp`] to integers modulo `modulus`
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct BigIntCall {
+    /// The modulus, as big endian bytes
+    pub modulus: Vec<u8>,
+    /// Number of bits in each limb of the operands and the result
+    pub limb_bits: u32,
+    pub op: BigIntOp,
+}
+
+impl BigIntCall {
+    pub fn name(&self) -> &str {
+        self.op.name()
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_u32(&mut writer, self.modulus.len() as u32)?;
+        write_bytes(&mut writer, &self.modulus)?;
+        write_u32(&mut writer, self.limb_bits)?;
+        write_bytes(&mut writer, &[self.op.to_u8()])?;
+
+        match &self.op {
+            BigIntOp::Add { lhs, rhs, output } | BigIntOp::Mul { lhs, rhs, output } => {
+                write_witnesses(&mut writer, lhs)?;
+                write_witnesses(&mut writer, rhs)?;
+                write_witnesses(&mut writer, output)?;
+            }
+            BigIntOp::ModReduce { input, output } => {
+                write_witnesses(&mut writer, input)?;
+                write_witnesses(&mut writer, output)?;
+            }
+            BigIntOp::Compare { lhs, rhs, output } => {
+                write_witnesses(&mut writer, lhs)?;
+                write_witnesses(&mut writer, rhs)?;
+                write_u32(&mut writer, output.witness_index())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let modulus_len = read_u32(&mut reader)?;
+        let modulus = read_bytes(&mut reader, modulus_len as usize)?;
+        let limb_bits = read_u32(&mut reader)?;
+
+        let op = match read_n::<1, _>(&mut reader)?[0] {
+            0 => BigIntOp::Add {
+                lhs: read_witnesses(&mut reader)?,
+                rhs: read_witnesses(&mut reader)?,
+                output: read_witnesses(&mut reader)?,
+            },
+            1 => BigIntOp::Mul {
+                lhs: read_witnesses(&mut reader)?,
+                rhs: read_witnesses(&mut reader)?,
+                output: read_witnesses(&mut reader)?,
+            },
+            2 => BigIntOp::ModReduce {
+                input: read_witnesses(&mut reader)?,
+                output: read_witnesses(&mut reader)?,
+            },
+            3 => BigIntOp::Compare {
+                lhs: read_witnesses(&mut reader)?,
+                rhs: read_witnesses(&mut reader)?,
+                output: Witness(read_u32(&mut reader)?),
+            },
+            _ => return Err(std::io::ErrorKind::InvalidData.into()),
+        };
+        Ok(BigIntCall { modulus, limb_bits, op })
+    }
+}
+
+impl std::fmt::Display for BigIntCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn limbs(witnesses: &[Witness]) -> String {
+            match (witnesses.first(), witnesses.last()) {
+                (Some(first), Some(last)) => {
+                    format!("[_{}..._{}]", first.witness_index(), last.witness_index())
+                }
+                _ => "[]".to_owned(),
+            }
+        }
+
+        let uppercase_name = self.name().to_uppercase();
+        write!(f, "{uppercase_name} ")?;
+        match &self.op {
+            BigIntOp::Add { lhs, rhs, output } | BigIntOp::Mul { lhs, rhs, output } => {
+                write!(f, "({}, {}) -> {}", limbs(lhs), limbs(rhs), limbs(output))?
+            }
+            BigIntOp::ModReduce { input, output } => {
+                write!(f, "({}) -> {}", limbs(input), limbs(output))?
+            }
+            BigIntOp::Compare { lhs, rhs, output } => {
+                write!(f, "({}, {}) -> _{}", limbs(lhs), limbs(rhs), output.witness_index())?
+            }
+        }
+        let modulus: String = self.modulus.iter().map(|byte| format!("{byte:02x}")).collect();
+        write!(f, " MODULUS = 0x{modulus}, LIMB BITS = {}", self.limb_bits)
+    }
+}
@@ -1,57 +1,815 @@
 use std::io::{Read, Write};
 
-use crate::native_types::Witness;
-use crate::serialization::{read_u16, read_u32, write_u16, write_u32};
+use crate::circuit::black_box_functions::{HashInputPacking, HashOutputMode, RecoverOutputMode};
+use crate::native_types::{Expression, Witness};
+use crate::serialization::{
+    read_bytes, read_field_element, read_n, read_u16, read_u32, write_bytes, write_u16, write_u32,
+};
 use crate::BlackBoxFunc;
+use acir_field::FieldElement;
 use serde::{Deserialize, Serialize};
 
+/// An operand of a black box function, which is either
+/// a witness or a constant which is known at compile time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstantOrWitness {
+    Constant(FieldElement),
+    Witness(Witness),
+}
+
+impl ConstantOrWitness {
+    fn to_u8(self) -> u8 {
+        match self {
+            ConstantOrWitness::Witness(_) => 0,
+            ConstantOrWitness::Constant(_) => 1,
+        }
+    }
+}
+
 // Note: Some functions will not use all of the witness
 // So we need to supply how many bits of the witness is needed
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SerializedFunctionInput")]
 pub struct FunctionInput {
-    pub witness: Witness,
+    pub input: ConstantOrWitness,
     pub num_bits: u32,
 }
 
+// Function inputs used to be witnesses only, which are still accepted when deserializing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedFunctionInput {
+    Input { input: ConstantOrWitness, num_bits: u32 },
+    Witness { witness: Witness, num_bits: u32 },
+}
+
+impl From<SerializedFunctionInput> for FunctionInput {
+    fn from(serialized: SerializedFunctionInput) -> Self {
+        match serialized {
+            SerializedFunctionInput::Input { input, num_bits } => FunctionInput { input, num_bits },
+            SerializedFunctionInput::Witness { witness, num_bits } => {
+                FunctionInput::witness(witness, num_bits)
+            }
+        }
+    }
+}
+
+impl FunctionInput {
+    pub fn witness(witness: Witness, num_bits: u32) -> FunctionInput {
+        FunctionInput { input: ConstantOrWitness::Witness(witness), num_bits }
+    }
+
+    pub fn constant(value: FieldElement, num_bits: u32) -> FunctionInput {
+        FunctionInput { input: ConstantOrWitness::Constant(value), num_bits }
+    }
+
+    /// Returns the witness of this input, or `None` if the input is a constant
+    pub fn to_witness(&self) -> Option<Witness> {
+        match self.input {
+            ConstantOrWitness::Witness(witness) => Some(witness),
+            ConstantOrWitness::Constant(_) => None,
+        }
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_bytes(&mut writer, &[self.input.to_u8()])?;
+        match self.input {
+            ConstantOrWitness::Witness(witness) => {
+                write_u32(&mut writer, witness.witness_index())?;
+            }
+            ConstantOrWitness::Constant(value) => {
+                write_bytes(&mut writer, &value.to_be_bytes())?;
+            }
+        }
+        write_u32(&mut writer, self.num_bits)?;
+        Ok(())
+    }
+
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        const FIELD_ELEMENT_NUM_BYTES: usize = FieldElement::max_num_bytes() as usize;
+
+        let input = match read_n::<1, _>(&mut reader)?[0] {
+            0 => ConstantOrWitness::Witness(Witness(read_u32(&mut reader)?)),
+            1 => ConstantOrWitness::Constant(read_field_element::<FIELD_ELEMENT_NUM_BYTES, _>(
+                &mut reader,
+            )?),
+            _ => return Err(std::io::ErrorKind::InvalidData.into()),
+        };
+        let num_bits = read_u32(&mut reader)?;
+        Ok(FunctionInput { input, num_bits })
+    }
+}
+
+impl From<&FunctionInput> for Expression {
+    fn from(function_input: &FunctionInput) -> Expression {
+        match function_input.input {
+            ConstantOrWitness::Constant(value) => Expression::from_field(value),
+            ConstantOrWitness::Witness(witness) => Expression::from(witness),
+        }
+    }
+}
+
+impl std::fmt::Display for FunctionInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.input {
+            ConstantOrWitness::Constant(value) => {
+                write!(f, "({value}, num_bits: {})", self.num_bits)
+            }
+            ConstantOrWitness::Witness(witness) => {
+                write!(f, "(_{}, num_bits: {})", witness.witness_index(), self.num_bits)
+            }
+        }
+    }
+}
+
+/// A call to a black box function.
+///
+/// Each variant has the arguments of the function laid out by name.
+/// When serialized, the call is flattened into a list of inputs and a list of outputs,
+/// see [`BlackBoxFuncCall::get_inputs_vec`] and [`BlackBoxFuncCall::from_parts`].
+///
+/// Functions which are not part of [`BlackBoxFunc`] can be called through [`BlackBoxFuncCall::Custom`].
+/// These are identified by name and it is up to the caller to provide their definition and solver.
+///
+/// Every call has an optional predicate, which must evaluate to zero or one. If it evaluates to zero
+/// then the call is skipped and all of its outputs are assigned zero. A missing predicate is treated as always being one.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct BlackBoxFuncCall {
-    pub name: BlackBoxFunc,
-    pub inputs: Vec<FunctionInput>,
-    pub outputs: Vec<Witness>,
+pub enum BlackBoxFuncCall {
+    AES {
+        inputs: Vec<FunctionInput>,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    AND {
+        lhs: FunctionInput,
+        rhs: FunctionInput,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    XOR {
+        lhs: FunctionInput,
+        rhs: FunctionInput,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    RANGE {
+        input: FunctionInput,
+        predicate: Option<Expression>,
+    },
+    BoundedRange {
+        input: FunctionInput,
+        /// The smallest value allowed for the input
+        min: FieldElement,
+        /// The largest value allowed for the input
+        max: FieldElement,
+        predicate: Option<Expression>,
+    },
+    SHA256 {
+        inputs: Vec<FunctionInput>,
+        /// If set, only the first `var_message_size` inputs are hashed.
+        var_message_size: Option<FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    Blake2s {
+        inputs: Vec<FunctionInput>,
+        /// If set, only the first `var_message_size` inputs are hashed.
+        var_message_size: Option<FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    ComputeMerkleRoot {
+        leaf: FunctionInput,
+        index: FunctionInput,
+        hash_path: Vec<FunctionInput>,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    SchnorrVerify {
+        public_key_x: FunctionInput,
+        public_key_y: FunctionInput,
+        signature: Vec<FunctionInput>,
+        message: Vec<FunctionInput>,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    Pedersen {
+        inputs: Vec<FunctionInput>,
+        outputs: (Witness, Witness),
+        predicate: Option<Expression>,
+    },
+    HashToField128Security {
+        inputs: Vec<FunctionInput>,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    EcdsaSecp256k1 {
+        public_key_x: Vec<FunctionInput>,
+        public_key_y: Vec<FunctionInput>,
+        signature: Vec<FunctionInput>,
+        hashed_message: Vec<FunctionInput>,
+        /// Whether signatures with a high `s` value are rejected, to prevent their malleability
+        require_low_s: bool,
+        output: Witness,
+        predicate: Option<Expression>,
+    },
+    EcdsaSecp256k1Recover {
+        signature: Vec<FunctionInput>,
+        recovery_id: FunctionInput,
+        hashed_message: Vec<FunctionInput>,
+        /// Whether signatures with a high `s` value are rejected, to prevent their malleability
+        require_low_s: bool,
+        output_mode: RecoverOutputMode,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    FixedBaseScalarMul {
+        input: FunctionInput,
+        outputs: (Witness, Witness),
+        predicate: Option<Expression>,
+    },
+    EmbeddedCurveAdd {
+        input1_x: FunctionInput,
+        input1_y: FunctionInput,
+        input2_x: FunctionInput,
+        input2_y: FunctionInput,
+        outputs: (Witness, Witness),
+        predicate: Option<Expression>,
+    },
+    EmbeddedCurveDouble {
+        input_x: FunctionInput,
+        input_y: FunctionInput,
+        outputs: (Witness, Witness),
+        predicate: Option<Expression>,
+    },
+    VariableBaseScalarMul {
+        point_x: FunctionInput,
+        point_y: FunctionInput,
+        scalar: FunctionInput,
+        outputs: (Witness, Witness),
+        predicate: Option<Expression>,
+    },
+    Keccak256 {
+        inputs: Vec<FunctionInput>,
+        /// If set, only the first `var_message_size` inputs are hashed.
+        var_message_size: Option<FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    Sha256Compression {
+        inputs: Vec<FunctionInput>,
+        hash_values: Vec<FunctionInput>,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    Keccakf1600 {
+        inputs: Vec<FunctionInput>,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
+    /// A call to a black box function which is not known to ACIR.
+    Custom {
+        name: String,
+        inputs: Vec<FunctionInput>,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    },
 }
 
+/// Checks that the bounds of a `BoundedRange` call can be constrained, i.e. that `min <= max`
+/// and that the bounds are less than `2^(max_num_bits - 2)` apart, so that the range cannot wrap around the field.
+///
+/// Returns the reason why they cannot be constrained otherwise.
+pub fn check_range_bounds(min: FieldElement, max: FieldElement) -> Result<(), String> {
+    if min > max {
+        return Err(format!("the lower bound {min} exceeds the upper bound {max}"));
+    }
+    let max_bits = FieldElement::max_num_bits() - 2;
+    if (max - min).num_bits() > max_bits {
+        return Err(format!("the bounds must be less than 2^{max_bits} apart"));
+    }
+    Ok(())
+}
+
+// Number of inputs which precede the hash path of a merkle root computation, the leaf and its index
+const MERKLE_ROOT_LEAF_INPUTS: usize = 2;
+// Number of bytes in each of the fixed size inputs of the signature verification functions
+const SCHNORR_SIGNATURE_BYTES: usize = 64;
+const ECDSA_PUBLIC_KEY_COORDINATE_BYTES: usize = 32;
+const ECDSA_SIGNATURE_BYTES: usize = 64;
+// Number of bytes of the hashed message from which a public key is recovered
+const ECDSA_HASHED_MESSAGE_BYTES: usize = 32;
+// Number of bytes in each of the outputs of public key recovery
+const ECDSA_PUBLIC_KEY_BYTES: usize = 64;
+const ETHEREUM_ADDRESS_BYTES: usize = 20;
+
+// Number of words in the block and the state of the SHA256 compression function
+const SHA256_BLOCK_WORDS: usize = 16;
+const SHA256_STATE_WORDS: usize = 8;
+// Number of lanes in the state of the Keccak-f[1600] permutation
+const KECCAKF1600_LANES: usize = 25;
+
+// Function index which is used to serialize custom black box functions.
+// It is followed by the name of the function.
+const CUSTOM_BLACK_BOX_FUNC_INDEX: u16 = u16::MAX;
+
 impl BlackBoxFuncCall {
+    /// Returns the function which is being called, or `None` if this is a call to a custom function.
+    pub fn get_black_box_func(&self) -> Option<BlackBoxFunc> {
+        let func = match self {
+            BlackBoxFuncCall::AES { .. } => BlackBoxFunc::AES,
+            BlackBoxFuncCall::AND { .. } => BlackBoxFunc::AND,
+            BlackBoxFuncCall::XOR { .. } => BlackBoxFunc::XOR,
+            BlackBoxFuncCall::RANGE { .. } => BlackBoxFunc::RANGE,
+            BlackBoxFuncCall::BoundedRange { .. } => BlackBoxFunc::BoundedRange,
+            BlackBoxFuncCall::SHA256 { .. } => BlackBoxFunc::SHA256,
+            BlackBoxFuncCall::Blake2s { .. } => BlackBoxFunc::Blake2s,
+            BlackBoxFuncCall::ComputeMerkleRoot { .. } => BlackBoxFunc::ComputeMerkleRoot,
+            BlackBoxFuncCall::SchnorrVerify { .. } => BlackBoxFunc::SchnorrVerify,
+            BlackBoxFuncCall::Pedersen { .. } => BlackBoxFunc::Pedersen,
+            BlackBoxFuncCall::HashToField128Security { .. } => BlackBoxFunc::HashToField128Security,
+            BlackBoxFuncCall::EcdsaSecp256k1 { .. } => BlackBoxFunc::EcdsaSecp256k1,
+            BlackBoxFuncCall::EcdsaSecp256k1Recover { .. } => BlackBoxFunc::EcdsaSecp256k1Recover,
+            BlackBoxFuncCall::FixedBaseScalarMul { .. } => BlackBoxFunc::FixedBaseScalarMul,
+            BlackBoxFuncCall::EmbeddedCurveAdd { .. } => BlackBoxFunc::EmbeddedCurveAdd,
+            BlackBoxFuncCall::EmbeddedCurveDouble { .. } => BlackBoxFunc::EmbeddedCurveDouble,
+            BlackBoxFuncCall::VariableBaseScalarMul { .. } => BlackBoxFunc::VariableBaseScalarMul,
+            BlackBoxFuncCall::Keccak256 { .. } => BlackBoxFunc::Keccak256,
+            BlackBoxFuncCall::Sha256Compression { .. } => BlackBoxFunc::Sha256Compression,
+            BlackBoxFuncCall::Keccakf1600 { .. } => BlackBoxFunc::Keccakf1600,
+            BlackBoxFuncCall::Custom { .. } => return None,
+        };
+        Some(func)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            BlackBoxFuncCall::Custom { name, .. } => name,
+            _ => self
+                .get_black_box_func()
+                .expect("only custom functions have no BlackBoxFunc")
+                .name(),
+        }
+    }
+
+    pub fn predicate(&self) -> Option<&Expression> {
+        match self {
+            BlackBoxFuncCall::AES { predicate, .. }
+            | BlackBoxFuncCall::AND { predicate, .. }
+            | BlackBoxFuncCall::XOR { predicate, .. }
+            | BlackBoxFuncCall::RANGE { predicate, .. }
+            | BlackBoxFuncCall::BoundedRange { predicate, .. }
+            | BlackBoxFuncCall::SHA256 { predicate, .. }
+            | BlackBoxFuncCall::Blake2s { predicate, .. }
+            | BlackBoxFuncCall::ComputeMerkleRoot { predicate, .. }
+            | BlackBoxFuncCall::SchnorrVerify { predicate, .. }
+            | BlackBoxFuncCall::Pedersen { predicate, .. }
+            | BlackBoxFuncCall::HashToField128Security { predicate, .. }
+            | BlackBoxFuncCall::EcdsaSecp256k1 { predicate, .. }
+            | BlackBoxFuncCall::EcdsaSecp256k1Recover { predicate, .. }
+            | BlackBoxFuncCall::FixedBaseScalarMul { predicate, .. }
+            | BlackBoxFuncCall::EmbeddedCurveAdd { predicate, .. }
+            | BlackBoxFuncCall::EmbeddedCurveDouble { predicate, .. }
+            | BlackBoxFuncCall::VariableBaseScalarMul { predicate, .. }
+            | BlackBoxFuncCall::Keccak256 { predicate, .. }
+            | BlackBoxFuncCall::Sha256Compression { predicate, .. }
+            | BlackBoxFuncCall::Keccakf1600 { predicate, .. }
+            | BlackBoxFuncCall::Custom { predicate, .. } => predicate.as_ref(),
+        }
+    }
+
+    /// Returns the number of inputs which should be hashed, if the call is to a hash function with a variable message size.
+    pub fn var_message_size(&self) -> Option<&FunctionInput> {
+        match self {
+            BlackBoxFuncCall::SHA256 { var_message_size, .. }
+            | BlackBoxFuncCall::Blake2s { var_message_size, .. }
+            | BlackBoxFuncCall::Keccak256 { var_message_size, .. } => var_message_size.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns how the inputs are packed into the message, if the call is to a hash function which supports packing.
+    pub fn hash_input_packing(&self) -> Option<HashInputPacking> {
+        match self {
+            BlackBoxFuncCall::SHA256 { input_packing, .. }
+            | BlackBoxFuncCall::Blake2s { input_packing, .. }
+            | BlackBoxFuncCall::Keccak256 { input_packing, .. } => Some(*input_packing),
+            _ => None,
+        }
+    }
+
+    /// Returns the shape of the digest, if the call is to a hash function which outputs a 32 byte digest.
+    pub fn hash_output_mode(&self) -> Option<HashOutputMode> {
+        match self {
+            BlackBoxFuncCall::SHA256 { output_mode, .. }
+            | BlackBoxFuncCall::Blake2s { output_mode, .. }
+            | BlackBoxFuncCall::Keccak256 { output_mode, .. } => Some(*output_mode),
+            _ => None,
+        }
+    }
+
+    /// Returns the inputs of the call flattened into a single list,
+    /// in the order in which they are serialized.
+    pub fn get_inputs_vec(&self) -> Vec<FunctionInput> {
+        match self {
+            BlackBoxFuncCall::AES { inputs, .. }
+            | BlackBoxFuncCall::SHA256 { inputs, .. }
+            | BlackBoxFuncCall::Blake2s { inputs, .. }
+            | BlackBoxFuncCall::Pedersen { inputs, .. }
+            | BlackBoxFuncCall::HashToField128Security { inputs, .. }
+            | BlackBoxFuncCall::Keccak256 { inputs, .. }
+            | BlackBoxFuncCall::Keccakf1600 { inputs, .. }
+            | BlackBoxFuncCall::Custom { inputs, .. } => inputs.clone(),
+            BlackBoxFuncCall::Sha256Compression { inputs, hash_values, .. } => {
+                let mut all_inputs = inputs.clone();
+                all_inputs.extend(hash_values.iter().cloned());
+                all_inputs
+            }
+            BlackBoxFuncCall::AND { lhs, rhs, .. } | BlackBoxFuncCall::XOR { lhs, rhs, .. } => {
+                vec![lhs.clone(), rhs.clone()]
+            }
+            BlackBoxFuncCall::RANGE { input, .. }
+            | BlackBoxFuncCall::BoundedRange { input, .. }
+            | BlackBoxFuncCall::FixedBaseScalarMul { input, .. } => vec![input.clone()],
+            BlackBoxFuncCall::EmbeddedCurveAdd {
+                input1_x, input1_y, input2_x, input2_y, ..
+            } => {
+                vec![input1_x.clone(), input1_y.clone(), input2_x.clone(), input2_y.clone()]
+            }
+            BlackBoxFuncCall::EmbeddedCurveDouble { input_x, input_y, .. } => {
+                vec![input_x.clone(), input_y.clone()]
+            }
+            BlackBoxFuncCall::VariableBaseScalarMul { point_x, point_y, scalar, .. } => {
+                vec![point_x.clone(), point_y.clone(), scalar.clone()]
+            }
+            BlackBoxFuncCall::ComputeMerkleRoot { leaf, index, hash_path, .. } => {
+                let mut inputs = vec![leaf.clone(), index.clone()];
+                inputs.extend(hash_path.iter().cloned());
+                inputs
+            }
+            BlackBoxFuncCall::SchnorrVerify {
+                public_key_x,
+                public_key_y,
+                signature,
+                message,
+                ..
+            } => {
+                let mut inputs = vec![public_key_x.clone(), public_key_y.clone()];
+                inputs.extend(signature.iter().cloned());
+                inputs.extend(message.iter().cloned());
+                inputs
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1 {
+                public_key_x,
+                public_key_y,
+                signature,
+                hashed_message,
+                ..
+            } => {
+                let mut inputs = public_key_x.clone();
+                inputs.extend(public_key_y.iter().cloned());
+                inputs.extend(signature.iter().cloned());
+                inputs.extend(hashed_message.iter().cloned());
+                inputs
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1Recover {
+                signature,
+                recovery_id,
+                hashed_message,
+                ..
+            } => {
+                let mut inputs = signature.clone();
+                inputs.push(recovery_id.clone());
+                inputs.extend(hashed_message.iter().cloned());
+                inputs
+            }
+        }
+    }
+
+    /// Returns the outputs of the call flattened into a single list,
+    /// in the order in which they are serialized.
+    pub fn get_outputs_vec(&self) -> Vec<Witness> {
+        match self {
+            BlackBoxFuncCall::AES { outputs, .. }
+            | BlackBoxFuncCall::SHA256 { outputs, .. }
+            | BlackBoxFuncCall::Blake2s { outputs, .. }
+            | BlackBoxFuncCall::Keccak256 { outputs, .. }
+            | BlackBoxFuncCall::Sha256Compression { outputs, .. }
+            | BlackBoxFuncCall::Keccakf1600 { outputs, .. }
+            | BlackBoxFuncCall::EcdsaSecp256k1Recover { outputs, .. }
+            | BlackBoxFuncCall::Custom { outputs, .. } => outputs.clone(),
+            BlackBoxFuncCall::AND { output, .. }
+            | BlackBoxFuncCall::XOR { output, .. }
+            | BlackBoxFuncCall::ComputeMerkleRoot { output, .. }
+            | BlackBoxFuncCall::SchnorrVerify { output, .. }
+            | BlackBoxFuncCall::HashToField128Security { output, .. }
+            | BlackBoxFuncCall::EcdsaSecp256k1 { output, .. } => vec![*output],
+            BlackBoxFuncCall::Pedersen { outputs, .. }
+            | BlackBoxFuncCall::FixedBaseScalarMul { outputs, .. }
+            | BlackBoxFuncCall::EmbeddedCurveAdd { outputs, .. }
+            | BlackBoxFuncCall::EmbeddedCurveDouble { outputs, .. }
+            | BlackBoxFuncCall::VariableBaseScalarMul { outputs, .. } => vec![outputs.0, outputs.1],
+            BlackBoxFuncCall::RANGE { .. } | BlackBoxFuncCall::BoundedRange { .. } => vec![],
+        }
+    }
+
+    /// Builds a call to `func` from its flattened inputs and outputs.
+    ///
+    /// This is the inverse of [`BlackBoxFuncCall::get_inputs_vec`] and [`BlackBoxFuncCall::get_outputs_vec`].
+    /// Returns `None` if the number of inputs or outputs does not match the layout of `func`.
+    ///
+    /// Options which are specific to `func`, such as the message size of the hash functions, are left unset.
+    pub fn from_parts(
+        func: BlackBoxFunc,
+        mut inputs: Vec<FunctionInput>,
+        outputs: Vec<Witness>,
+        predicate: Option<Expression>,
+    ) -> Option<Self> {
+        let call = match (func, inputs.len(), &outputs[..]) {
+            (BlackBoxFunc::AES, ..) => BlackBoxFuncCall::AES { inputs, outputs, predicate },
+            (BlackBoxFunc::AND, 2, [output]) => {
+                let rhs = inputs.pop()?;
+                let lhs = inputs.pop()?;
+                BlackBoxFuncCall::AND { lhs, rhs, output: *output, predicate }
+            }
+            (BlackBoxFunc::XOR, 2, [output]) => {
+                let rhs = inputs.pop()?;
+                let lhs = inputs.pop()?;
+                BlackBoxFuncCall::XOR { lhs, rhs, output: *output, predicate }
+            }
+            (BlackBoxFunc::RANGE, 1, []) => {
+                BlackBoxFuncCall::RANGE { input: inputs.pop()?, predicate }
+            }
+            (BlackBoxFunc::BoundedRange, 1, []) => BlackBoxFuncCall::BoundedRange {
+                input: inputs.pop()?,
+                min: FieldElement::zero(),
+                max: FieldElement::zero(),
+                predicate,
+            },
+            (BlackBoxFunc::SHA256, ..) => BlackBoxFuncCall::SHA256 {
+                inputs,
+                var_message_size: None,
+                input_packing: HashInputPacking::default(),
+                output_mode: HashOutputMode::default(),
+                outputs,
+                predicate,
+            },
+            (BlackBoxFunc::Blake2s, ..) => BlackBoxFuncCall::Blake2s {
+                inputs,
+                var_message_size: None,
+                input_packing: HashInputPacking::default(),
+                output_mode: HashOutputMode::default(),
+                outputs,
+                predicate,
+            },
+            (BlackBoxFunc::ComputeMerkleRoot, num_inputs, [output])
+                if num_inputs >= MERKLE_ROOT_LEAF_INPUTS =>
+            {
+                let hash_path = inputs.split_off(MERKLE_ROOT_LEAF_INPUTS);
+                let index = inputs.pop()?;
+                let leaf = inputs.pop()?;
+                BlackBoxFuncCall::ComputeMerkleRoot {
+                    leaf,
+                    index,
+                    hash_path,
+                    output: *output,
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::SchnorrVerify, num_inputs, [output])
+                if num_inputs >= 2 + SCHNORR_SIGNATURE_BYTES =>
+            {
+                let message = inputs.split_off(2 + SCHNORR_SIGNATURE_BYTES);
+                let signature = inputs.split_off(2);
+                let public_key_y = inputs.pop()?;
+                let public_key_x = inputs.pop()?;
+                BlackBoxFuncCall::SchnorrVerify {
+                    public_key_x,
+                    public_key_y,
+                    signature,
+                    message,
+                    output: *output,
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::Pedersen, _, [output_x, output_y]) => {
+                BlackBoxFuncCall::Pedersen { inputs, outputs: (*output_x, *output_y), predicate }
+            }
+            (BlackBoxFunc::HashToField128Security, _, [output]) => {
+                BlackBoxFuncCall::HashToField128Security { inputs, output: *output, predicate }
+            }
+            (BlackBoxFunc::EcdsaSecp256k1, num_inputs, [output])
+                if num_inputs >= 2 * ECDSA_PUBLIC_KEY_COORDINATE_BYTES + ECDSA_SIGNATURE_BYTES =>
+            {
+                let hashed_message =
+                    inputs.split_off(2 * ECDSA_PUBLIC_KEY_COORDINATE_BYTES + ECDSA_SIGNATURE_BYTES);
+                let signature = inputs.split_off(2 * ECDSA_PUBLIC_KEY_COORDINATE_BYTES);
+                let public_key_y = inputs.split_off(ECDSA_PUBLIC_KEY_COORDINATE_BYTES);
+                BlackBoxFuncCall::EcdsaSecp256k1 {
+                    public_key_x: inputs,
+                    public_key_y,
+                    signature,
+                    hashed_message,
+                    require_low_s: true,
+                    output: *output,
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::EcdsaSecp256k1Recover, num_inputs, _)
+                if num_inputs == ECDSA_SIGNATURE_BYTES + 1 + ECDSA_HASHED_MESSAGE_BYTES
+                    && (outputs.len() == ECDSA_PUBLIC_KEY_BYTES
+                        || outputs.len() == ETHEREUM_ADDRESS_BYTES) =>
+            {
+                let hashed_message = inputs.split_off(ECDSA_SIGNATURE_BYTES + 1);
+                let recovery_id = inputs.pop()?;
+                let output_mode = match outputs.len() {
+                    ETHEREUM_ADDRESS_BYTES => RecoverOutputMode::Address,
+                    _ => RecoverOutputMode::PublicKey,
+                };
+                BlackBoxFuncCall::EcdsaSecp256k1Recover {
+                    signature: inputs,
+                    recovery_id,
+                    hashed_message,
+                    require_low_s: true,
+                    output_mode,
+                    outputs,
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::FixedBaseScalarMul, 1, [output_x, output_y]) => {
+                BlackBoxFuncCall::FixedBaseScalarMul {
+                    input: inputs.pop()?,
+                    outputs: (*output_x, *output_y),
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::EmbeddedCurveAdd, 4, [output_x, output_y]) => {
+                let mut inputs = inputs.into_iter();
+                BlackBoxFuncCall::EmbeddedCurveAdd {
+                    input1_x: inputs.next()?,
+                    input1_y: inputs.next()?,
+                    input2_x: inputs.next()?,
+                    input2_y: inputs.next()?,
+                    outputs: (*output_x, *output_y),
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::EmbeddedCurveDouble, 2, [output_x, output_y]) => {
+                let mut inputs = inputs.into_iter();
+                BlackBoxFuncCall::EmbeddedCurveDouble {
+                    input_x: inputs.next()?,
+                    input_y: inputs.next()?,
+                    outputs: (*output_x, *output_y),
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::VariableBaseScalarMul, 3, [output_x, output_y]) => {
+                let mut inputs = inputs.into_iter();
+                BlackBoxFuncCall::VariableBaseScalarMul {
+                    point_x: inputs.next()?,
+                    point_y: inputs.next()?,
+                    scalar: inputs.next()?,
+                    outputs: (*output_x, *output_y),
+                    predicate,
+                }
+            }
+            (BlackBoxFunc::Keccak256, ..) => BlackBoxFuncCall::Keccak256 {
+                inputs,
+                var_message_size: None,
+                input_packing: HashInputPacking::default(),
+                output_mode: HashOutputMode::default(),
+                outputs,
+                predicate,
+            },
+            (BlackBoxFunc::Sha256Compression, num_inputs, _)
+                if num_inputs == SHA256_BLOCK_WORDS + SHA256_STATE_WORDS
+                    && outputs.len() == SHA256_STATE_WORDS =>
+            {
+                let hash_values = inputs.split_off(SHA256_BLOCK_WORDS);
+                BlackBoxFuncCall::Sha256Compression { inputs, hash_values, outputs, predicate }
+            }
+            (BlackBoxFunc::Keccakf1600, KECCAKF1600_LANES, _)
+                if outputs.len() == KECCAKF1600_LANES =>
+            {
+                BlackBoxFuncCall::Keccakf1600 { inputs, outputs, predicate }
+            }
+            _ => return None,
+        };
+        Some(call)
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
-        write_u16(&mut writer, self.name.to_u16())?;
+        match self {
+            BlackBoxFuncCall::Custom { name, .. } => {
+                write_u16(&mut writer, CUSTOM_BLACK_BOX_FUNC_INDEX)?;
+
+                let name_as_bytes = name.as_bytes();
+                write_u32(&mut writer, name_as_bytes.len() as u32)?;
+                write_bytes(&mut writer, name_as_bytes)?;
+            }
+            _ => {
+                let func =
+                    self.get_black_box_func().expect("only custom functions have no BlackBoxFunc");
+                write_u16(&mut writer, func.to_u16())?;
+            }
+        }
 
-        let num_inputs = self.inputs.len() as u32;
+        let inputs = self.get_inputs_vec();
+        let num_inputs = inputs.len() as u32;
         write_u32(&mut writer, num_inputs)?;
 
-        for input in &self.inputs {
-            write_u32(&mut writer, input.witness.witness_index())?;
-            write_u32(&mut writer, input.num_bits)?;
+        for input in &inputs {
+            input.write(&mut writer)?;
         }
 
-        let num_outputs = self.outputs.len() as u32;
+        let outputs = self.get_outputs_vec();
+        let num_outputs = outputs.len() as u32;
         write_u32(&mut writer, num_outputs)?;
 
-        for output in &self.outputs {
+        for output in &outputs {
             write_u32(&mut writer, output.witness_index())?;
         }
 
-        Ok(())
+        let predicate = self.predicate();
+        let predicate_is_some = vec![predicate.is_some() as u8];
+        write_bytes(&mut writer, &predicate_is_some)?;
+
+        if let Some(pred) = predicate {
+            pred.write(&mut writer)?;
+        }
+
+        self.write_options(&mut writer)
     }
     pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
         let func_index = read_u16(&mut reader)?;
-        let name = BlackBoxFunc::from_u16(func_index).ok_or(std::io::ErrorKind::InvalidData)?;
+        // Custom functions are identified by their name rather than by an index
+        let custom_name = match func_index {
+            CUSTOM_BLACK_BOX_FUNC_INDEX => {
+                let name_len = read_u32(&mut reader)?;
+                let name_as_bytes = read_bytes(&mut reader, name_len as usize)?;
+                let name: String = String::from_utf8(name_as_bytes)
+                    .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+                Some(name)
+            }
+            _ => None,
+        };
+
+        let num_inputs = read_u32(&mut reader)?;
+        let mut inputs = Vec::with_capacity(num_inputs as usize);
+        for _ in 0..num_inputs {
+            inputs.push(FunctionInput::read(&mut reader)?)
+        }
+
+        let num_outputs = read_u32(&mut reader)?;
+        let mut outputs = Vec::with_capacity(num_outputs as usize);
+        for _ in 0..num_outputs {
+            let witness = Witness(read_u32(&mut reader)?);
+            outputs.push(witness)
+        }
+
+        // Read byte to figure out if there is a predicate
+        let predicate_is_some = read_n::<1, _>(&mut reader)?[0] != 0;
+        let predicate = match predicate_is_some {
+            true => Some(Expression::read(&mut reader)?),
+            false => None,
+        };
+
+        match custom_name {
+            Some(name) => Ok(BlackBoxFuncCall::Custom { name, inputs, outputs, predicate }),
+            None => {
+                let func =
+                    BlackBoxFunc::from_u16(func_index).ok_or(std::io::ErrorKind::InvalidData)?;
+                let mut call = BlackBoxFuncCall::from_parts(func, inputs, outputs, predicate)
+                    .ok_or(std::io::ErrorKind::InvalidData)?;
+                call.read_options(&mut reader)?;
+                Ok(call)
+            }
+        }
+    }
+
+    /// Reads a call serialized before version 1 of the circuit format, where every input is a witness
+    /// and calls have neither a predicate nor options.
+    pub(crate) fn read_legacy<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let func_index = read_u16(&mut reader)?;
+        // The legacy format only knows about the functions up to Keccak256
+        if func_index > BlackBoxFunc::Keccak256.to_u16() {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        let func = BlackBoxFunc::from_u16(func_index).ok_or(std::io::ErrorKind::InvalidData)?;
 
         let num_inputs = read_u32(&mut reader)?;
         let mut inputs = Vec::with_capacity(num_inputs as usize);
         for _ in 0..num_inputs {
             let witness = Witness(read_u32(&mut reader)?);
             let num_bits = read_u32(&mut reader)?;
-            let input = FunctionInput { witness, num_bits };
-            inputs.push(input)
+            inputs.push(FunctionInput::witness(witness, num_bits))
         }
 
         let num_outputs = read_u32(&mut reader)?;
@@ -61,13 +819,91 @@ impl BlackBoxFuncCall {
             outputs.push(witness)
         }
 
-        Ok(BlackBoxFuncCall { name, inputs, outputs })
+        BlackBoxFuncCall::from_parts(func, inputs, outputs, None)
+            .ok_or_else(|| std::io::ErrorKind::InvalidData.into())
+    }
+
+    // The options which are specific to a function are serialized after the predicate,
+    // so that the flattened inputs and outputs have the same layout for every function.
+    fn write_options<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        match self {
+            BlackBoxFuncCall::SHA256 { var_message_size, input_packing, output_mode, .. }
+            | BlackBoxFuncCall::Blake2s { var_message_size, input_packing, output_mode, .. }
+            | BlackBoxFuncCall::Keccak256 {
+                var_message_size, input_packing, output_mode, ..
+            } => {
+                let var_message_size_is_some = vec![var_message_size.is_some() as u8];
+                write_bytes(&mut writer, &var_message_size_is_some)?;
+
+                if let Some(message_size) = var_message_size {
+                    message_size.write(&mut writer)?;
+                }
+
+                write_bytes(&mut writer, &[input_packing.to_u8(), output_mode.to_u8()])?;
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1 { require_low_s, .. } => {
+                write_bytes(&mut writer, &[*require_low_s as u8])?;
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1Recover { require_low_s, output_mode, .. } => {
+                write_bytes(&mut writer, &[*require_low_s as u8, output_mode.to_u8()])?;
+            }
+            BlackBoxFuncCall::BoundedRange { min, max, .. } => {
+                write_bytes(&mut writer, &min.to_be_bytes())?;
+                write_bytes(&mut writer, &max.to_be_bytes())?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn read_options<R: Read>(&mut self, mut reader: R) -> std::io::Result<()> {
+        match self {
+            BlackBoxFuncCall::SHA256 { var_message_size, input_packing, output_mode, .. }
+            | BlackBoxFuncCall::Blake2s { var_message_size, input_packing, output_mode, .. }
+            | BlackBoxFuncCall::Keccak256 {
+                var_message_size, input_packing, output_mode, ..
+            } => {
+                let var_message_size_is_some = read_n::<1, _>(&mut reader)?[0] != 0;
+                if var_message_size_is_some {
+                    *var_message_size = Some(FunctionInput::read(&mut reader)?);
+                }
+
+                let [input_packing_index, output_mode_index] = read_n::<2, _>(&mut reader)?;
+                *input_packing = HashInputPacking::from_u8(input_packing_index)
+                    .ok_or(std::io::ErrorKind::InvalidData)?;
+                *output_mode = HashOutputMode::from_u8(output_mode_index)
+                    .ok_or(std::io::ErrorKind::InvalidData)?;
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1 { require_low_s, .. } => {
+                *require_low_s = read_n::<1, _>(&mut reader)?[0] != 0;
+            }
+            BlackBoxFuncCall::EcdsaSecp256k1Recover {
+                require_low_s, output_mode, outputs, ..
+            } => {
+                let [require_low_s_byte, output_mode_index] = read_n::<2, _>(&mut reader)?;
+                *require_low_s = require_low_s_byte != 0;
+                *output_mode = RecoverOutputMode::from_u8(output_mode_index)
+                    .ok_or(std::io::ErrorKind::InvalidData)?;
+                // The number of outputs must match the explicit output mode
+                if outputs.len() as u128 != output_mode.output_size().0 {
+                    return Err(std::io::ErrorKind::InvalidData.into());
+                }
+            }
+            BlackBoxFuncCall::BoundedRange { min, max, .. } => {
+                const FIELD_ELEMENT_NUM_BYTES: usize = FieldElement::max_num_bytes() as usize;
+                *min = read_field_element::<FIELD_ELEMENT_NUM_BYTES, _>(&mut reader)?;
+                *max = read_field_element::<FIELD_ELEMENT_NUM_BYTES, _>(&mut reader)?;
+                check_range_bounds(*min, *max).map_err(|_| std::io::ErrorKind::InvalidData)?;
+            }
+            _ => (),
+        }
+        Ok(())
     }
 }
 
 impl std::fmt::Display for BlackBoxFuncCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let uppercase_name: String = self.name.name().into();
+        let uppercase_name: String = self.name().into();
         let uppercase_name = uppercase_name.to_uppercase();
         write!(f, "BLACKBOX::{uppercase_name} ")?;
         write!(f, "[")?;
@@ -77,35 +913,31 @@ impl std::fmt::Display for BlackBoxFuncCall {
         // t abbreviate them
         const ABBREVIATION_LIMIT: usize = 5;
 
-        let should_abbreviate_inputs = self.inputs.len() <= ABBREVIATION_LIMIT;
-        let should_abbreviate_outputs = self.outputs.len() <= ABBREVIATION_LIMIT;
+        let inputs = self.get_inputs_vec();
+        let outputs = self.get_outputs_vec();
+
+        let should_abbreviate_inputs = inputs.len() <= ABBREVIATION_LIMIT;
+        let should_abbreviate_outputs = outputs.len() <= ABBREVIATION_LIMIT;
 
         // INPUTS
         //
         let inputs_str = if should_abbreviate_inputs {
             let mut result = String::new();
-            for (index, inp) in self.inputs.iter().enumerate() {
-                result +=
-                    &format!("(_{}, num_bits: {})", inp.witness.witness_index(), inp.num_bits);
+            for (index, inp) in inputs.iter().enumerate() {
+                result += &format!("{inp}");
                 // Add a comma, unless it is the last entry
-                if index != self.inputs.len() - 1 {
+                if index != inputs.len() - 1 {
                     result += ", "
                 }
             }
             result
         } else {
-            let first = self.inputs.first().unwrap();
-            let last = self.inputs.last().unwrap();
+            let first = inputs.first().unwrap();
+            let last = inputs.last().unwrap();
 
             let mut result = String::new();
 
-            result += &format!(
-                "(_{}, num_bits: {})...(_{}, num_bits: {})",
-                first.witness.witness_index(),
-                first.num_bits,
-                last.witness.witness_index(),
-                last.num_bits,
-            );
+            result += &format!("{first}...{last}");
 
             result
         };
@@ -115,31 +947,67 @@ impl std::fmt::Display for BlackBoxFuncCall {
         // OUTPUTS
         // TODO: Avoid duplication of INPUTS and OUTPUTS code
 
-        if self.outputs.is_empty() {
-            return Ok(());
+        if !outputs.is_empty() {
+            write!(f, "[ ")?;
+            let outputs_str = if should_abbreviate_outputs {
+                let mut result = String::new();
+                for (index, output) in outputs.iter().enumerate() {
+                    result += &format!("_{}", output.witness_index());
+                    // Add a comma, unless it is the last entry
+                    if index != outputs.len() - 1 {
+                        result += ", "
+                    }
+                }
+                result
+            } else {
+                let first = outputs.first().unwrap();
+                let last = outputs.last().unwrap();
+
+                let mut result = String::new();
+                result += &format!("(_{},...,_{})", first.witness_index(), last.witness_index());
+                result
+            };
+            write!(f, "{outputs_str}")?;
+            write!(f, "]")?;
         }
 
-        write!(f, "[ ")?;
-        let outputs_str = if should_abbreviate_outputs {
-            let mut result = String::new();
-            for (index, output) in self.outputs.iter().enumerate() {
-                result += &format!("_{}", output.witness_index());
-                // Add a comma, unless it is the last entry
-                if index != self.outputs.len() - 1 {
-                    result += ", "
-                }
-            }
-            result
-        } else {
-            let first = self.outputs.first().unwrap();
-            let last = self.outputs.last().unwrap();
+        if let Some(message_size) = self.var_message_size() {
+            write!(f, " MESSAGE_SIZE = {message_size}")?;
+        }
 
-            let mut result = String::new();
-            result += &format!("(_{},...,_{})", first.witness_index(), last.witness_index());
-            result
-        };
-        write!(f, "{outputs_str}")?;
-        write!(f, "]")
+        if let BlackBoxFuncCall::EcdsaSecp256k1 { require_low_s: false, .. }
+        | BlackBoxFuncCall::EcdsaSecp256k1Recover { require_low_s: false, .. } = self
+        {
+            write!(f, " ALLOW_HIGH_S")?;
+        }
+
+        if let BlackBoxFuncCall::BoundedRange { min, max, .. } = self {
+            write!(f, " BOUNDS = [{min}, {max}]")?;
+        }
+
+        if let Some(HashInputPacking::Bits) = self.hash_input_packing() {
+            write!(f, " INPUT = BITS")?;
+        }
+
+        match self.hash_output_mode() {
+            Some(HashOutputMode::Limbs128) => write!(f, " OUTPUT = LIMBS128")?,
+            Some(HashOutputMode::Field) => write!(f, " OUTPUT = FIELD")?,
+            Some(HashOutputMode::Bytes) | None => (),
+        }
+
+        if let BlackBoxFuncCall::EcdsaSecp256k1Recover {
+            output_mode: RecoverOutputMode::Address,
+            ..
+        } = self
+        {
+            write!(f, " OUTPUT = ADDRESS")?;
+        }
+
+        if let Some(pred) = self.predicate() {
+            write!(f, " PREDICATE = {pred}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -1,11 +1,12 @@
 use std::io::{Read, Write};
 
+use crate::circuit::Opcode;
 use crate::native_types::Expression;
 use crate::serialization::{read_u32, write_u32};
 use acir_field::FieldElement;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Hash, Copy, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, Copy, Default)]
 pub struct BlockId(pub u32);
 
 /// Operation on a block
@@ -18,8 +19,79 @@ pub struct MemOp {
     pub value: Expression,
 }
 
+impl MemOp {
+    pub(crate) fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.operation.write(&mut writer)?;
+        self.index.write(&mut writer)?;
+        self.value.write(&mut writer)
+    }
+
+    pub(crate) fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let operation = Expression::read(&mut reader)?;
+        let index = Expression::read(&mut reader)?;
+        let value = Expression::read(&mut reader)?;
+        Ok(MemOp { operation, index, value })
+    }
+}
+
+/// The ways in which an operation on a memory block can be malformed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MemoryOpError {
+    InvalidOperation(FieldElement),
+    NonConstantOperation,
+    WriteAfterInitialization,
+    InvalidInitialization,
+    InitializationOutOfBounds(FieldElement),
+    NonWitnessInitialization,
+    DuplicateInitialization(FieldElement),
+    IncompleteInitialization(u32),
+    IncorrectRead(FieldElement),
+    DuplicateMemory,
+    UninitializedMemory,
+    EmptyMemoryAccess,
+}
+
+impl std::fmt::Display for MemoryOpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryOpError::InvalidOperation(operation) => {
+                write!(f, "operation {operation} is neither a read (0) nor a write (1)")
+            }
+            MemoryOpError::NonConstantOperation => write!(f, "the operation must be a constant"),
+            MemoryOpError::WriteAfterInitialization => {
+                write!(f, "a ROM cannot be written after its initialization")
+            }
+            MemoryOpError::InvalidInitialization => {
+                write!(f, "the initialization must write at a constant index")
+            }
+            MemoryOpError::InitializationOutOfBounds(index) => {
+                write!(f, "the initialization writes at index {index} which is out of bounds")
+            }
+            MemoryOpError::NonWitnessInitialization => {
+                write!(f, "the initialization must write a witness")
+            }
+            MemoryOpError::DuplicateInitialization(index) => {
+                write!(f, "index {index} is initialized more than once")
+            }
+            MemoryOpError::IncompleteInitialization(len) => {
+                write!(f, "the trace does not initialize all {len} elements")
+            }
+            MemoryOpError::IncorrectRead(index) => {
+                write!(f, "the value read at index {index} does not match the memory")
+            }
+            MemoryOpError::DuplicateMemory => write!(f, "the memory is initialized more than once"),
+            MemoryOpError::UninitializedMemory => {
+                write!(f, "the memory is accessed before its initialization")
+            }
+            MemoryOpError::EmptyMemoryAccess => write!(f, "an empty memory cannot be accessed"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryOpError {}
+
 /// Represents operations on a block of length len of data
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MemoryBlock {
     /// Id of the block
     pub id: BlockId,
@@ -36,10 +108,7 @@ impl MemoryBlock {
         let trace_len = read_u32(&mut reader)?;
         let mut trace = Vec::with_capacity(len as usize);
         for _i in 0..trace_len {
-            let operation = Expression::read(&mut reader)?;
-            let index = Expression::read(&mut reader)?;
-            let value = Expression::read(&mut reader)?;
-            trace.push(MemOp { operation, index, value });
+            trace.push(MemOp::read(&mut reader)?);
         }
         Ok(MemoryBlock { id: BlockId(id), len, trace })
     }
@@ -50,35 +119,174 @@ impl MemoryBlock {
         write_u32(&mut writer, self.trace.len() as u32)?;
 
         for op in &self.trace {
-            op.operation.write(&mut writer)?;
-            op.index.write(&mut writer)?;
-            op.value.write(&mut writer)?;
+            op.write(&mut writer)?;
         }
         Ok(())
     }
 
     /// Returns the initialization vector of the MemoryBlock
-    pub fn init_phase(&self) -> Vec<Expression> {
-        let mut init = Vec::new();
-        for i in 0..self.len as usize {
-            assert_eq!(
-                self.trace[i].operation,
-                Expression::one(),
-                "Block initialization require a write"
-            );
-            let index = self.trace[i]
-                .index
-                .to_const()
-                .expect("Non-const index during Block initialization");
-            if index != FieldElement::from(i as i128) {
-                todo!(
-                    "invalid index when initializing a block, we could try to sort the init phase"
-                );
+    ///
+    /// The first `len` operations of the trace initialize the block, they must write a witness
+    /// to each element of the block at a constant index but can do so in any order.
+    /// Otherwise, returns the position in the trace of the first malformed operation.
+    pub fn init_phase(&self) -> Result<Vec<Expression>, (usize, MemoryOpError)> {
+        self.sorted_init_phase()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                if value.is_degree_one_univariate() {
+                    Ok(value.clone())
+                } else {
+                    Err((self.init_position(index), MemoryOpError::NonWitnessInitialization))
+                }
+            })
+            .collect()
+    }
+
+    /// Lowers the block to a [MemoryInit][Opcode::MemoryInit] opcode followed by a [MemoryOp][Opcode::MemoryOp]
+    /// opcode for each operation after the initialization phase, in the order of the trace.
+    /// Memory opcodes do not forbid writes after the initialization, so they have the semantics of a RAM.
+    /// Returns the position in the trace of the first malformed operation of the initialization phase if any.
+    pub fn to_memory_opcodes(&self) -> Result<Vec<Opcode>, (usize, MemoryOpError)> {
+        let init = self.sorted_init_phase()?.into_iter().cloned().collect();
+        let mut opcodes = vec![Opcode::MemoryInit { id: self.id, init }];
+        opcodes.extend(
+            self.trace[self.len as usize..]
+                .iter()
+                .map(|op| Opcode::MemoryOp { id: self.id, op: op.clone() }),
+        );
+        Ok(opcodes)
+    }
+
+    /// Returns the values written by the initialization phase, sorted by index.
+    ///
+    /// Unlike [init_phase][MemoryBlock::init_phase], the values can be any expression.
+    /// Otherwise, returns the position in the trace of the first malformed operation.
+    pub fn sorted_init_phase(&self) -> Result<Vec<&Expression>, (usize, MemoryOpError)> {
+        let len = self.len as usize;
+        if self.trace.len() < len {
+            return Err((self.trace.len(), MemoryOpError::IncompleteInitialization(self.len)));
+        }
+
+        let mut init = vec![None; len];
+        for (position, op) in self.trace[..len].iter().enumerate() {
+            let index = match (op.operation.to_const(), op.index.to_const()) {
+                (Some(operation), Some(index)) if operation.is_one() => index,
+                _ => return Err((position, MemoryOpError::InvalidInitialization)),
+            };
+            let element = index
+                .try_to_u64()
+                .filter(|element| *element < len as u64)
+                .ok_or((position, MemoryOpError::InitializationOutOfBounds(index)))?;
+            match &mut init[element as usize] {
+                value @ None => *value = Some(&op.value),
+                Some(_) => return Err((position, MemoryOpError::DuplicateInitialization(index))),
             }
-            let value = self.trace[i].value.clone();
-            assert!(value.is_degree_one_univariate(), "Block initialization requires a witness");
-            init.push(value);
         }
-        init
+        Ok(init
+            .into_iter()
+            .map(|value| value.expect("each element has been written once"))
+            .collect())
+    }
+
+    // Returns the position in the trace of the initialization of the element `index`
+    fn init_position(&self, index: usize) -> usize {
+        let index = FieldElement::from(index as u128);
+        self.trace[..self.len as usize]
+            .iter()
+            .position(|op| op.index.to_const() == Some(index))
+            .expect("each element is initialized by the trace")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockId, MemOp, MemoryBlock, MemoryOpError};
+    use crate::{
+        circuit::Opcode,
+        native_types::{Expression, Witness},
+    };
+    use acir_field::FieldElement;
+
+    #[test]
+    fn init_phase_sorts_initialization() {
+        let trace = [2_u128, 0, 1]
+            .into_iter()
+            .map(|index| MemOp {
+                operation: Expression::one(),
+                index: Expression::from_field(FieldElement::from(index)),
+                value: Expression::from(Witness(index as u32 + 1)),
+            })
+            .collect();
+        let block = MemoryBlock { id: BlockId(0), len: 3, trace };
+        let expected: Vec<_> = (1..=3).map(|i| Expression::from(Witness(i))).collect();
+        assert_eq!(block.init_phase(), Ok(expected));
+    }
+
+    #[test]
+    fn reports_malformed_initialization() {
+        let write = |index: u128, value: Expression| MemOp {
+            operation: Expression::one(),
+            index: Expression::from_field(FieldElement::from(index)),
+            value,
+        };
+        let block = |trace: Vec<MemOp>| MemoryBlock { id: BlockId(0), len: 2, trace };
+        let witness = |i| Expression::from(Witness(i));
+
+        assert_eq!(
+            block(vec![write(0, witness(1))]).init_phase(),
+            Err((1, MemoryOpError::IncompleteInitialization(2)))
+        );
+        let read = MemOp { operation: Expression::zero(), ..write(1, witness(2)) };
+        assert_eq!(
+            block(vec![write(0, witness(1)), read]).init_phase(),
+            Err((1, MemoryOpError::InvalidInitialization))
+        );
+        let non_constant_index = MemOp { index: witness(3), ..write(1, witness(2)) };
+        assert_eq!(
+            block(vec![write(0, witness(1)), non_constant_index]).init_phase(),
+            Err((1, MemoryOpError::InvalidInitialization))
+        );
+        assert_eq!(
+            block(vec![write(2, witness(1)), write(0, witness(2))]).init_phase(),
+            Err((0, MemoryOpError::InitializationOutOfBounds(FieldElement::from(2_u128))))
+        );
+        assert_eq!(
+            block(vec![write(1, witness(1)), write(1, witness(2))]).init_phase(),
+            Err((1, MemoryOpError::DuplicateInitialization(FieldElement::one())))
+        );
+        let constant = Expression::from_field(FieldElement::from(5_u128));
+        assert_eq!(
+            block(vec![write(1, witness(1)), write(0, constant)]).init_phase(),
+            Err((1, MemoryOpError::NonWitnessInitialization))
+        );
+    }
+
+    #[test]
+    fn lowers_to_memory_opcodes() {
+        let init = (0..2_u128).rev().map(|index| MemOp {
+            operation: Expression::one(),
+            index: Expression::from_field(FieldElement::from(index)),
+            value: Expression::from_field(FieldElement::from(index + 10)),
+        });
+        let read = MemOp {
+            operation: Expression::zero(),
+            index: Expression::from(Witness(1)),
+            value: Expression::from(Witness(2)),
+        };
+        let block =
+            MemoryBlock { id: BlockId(4), len: 2, trace: init.chain([read.clone()]).collect() };
+
+        let init = vec![
+            Expression::from_field(FieldElement::from(10_u128)),
+            Expression::from_field(FieldElement::from(11_u128)),
+        ];
+        assert_eq!(
+            block.to_memory_opcodes(),
+            Ok(vec![
+                Opcode::MemoryInit { id: BlockId(4), init },
+                Opcode::MemoryOp { id: BlockId(4), op: read }
+            ])
+        );
     }
 }
@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+
+use crate::native_types::Expression;
+use crate::serialization::{read_bytes, read_field_element, read_u32, write_bytes, write_u32};
+use acir_field::FieldElement;
+use serde::{Deserialize, Serialize};
+
+/// A named table of constant rows, declared once in the circuit and referenced by [`Lookup`] opcodes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupTable {
+    pub name: String,
+    /// Each row has one value per column of the table
+    pub rows: Vec<Vec<FieldElement>>,
+}
+
+impl LookupTable {
+    pub(crate) fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_string(&mut writer, &self.name)?;
+
+        write_u32(&mut writer, self.rows.len() as u32)?;
+        for row in &self.rows {
+            write_u32(&mut writer, row.len() as u32)?;
+            for value in row {
+                write_bytes(&mut writer, &value.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        const FIELD_ELEMENT_NUM_BYTES: usize = FieldElement::max_num_bytes() as usize;
+
+        let name = read_string(&mut reader)?;
+
+        let num_rows = read_u32(&mut reader)?;
+        let mut rows = Vec::new();
+        for _ in 0..num_rows {
+            let row_len = read_u32(&mut reader)?;
+            let mut row = Vec::new();
+            for _ in 0..row_len {
+                row.push(read_field_element::<FIELD_ELEMENT_NUM_BYTES, _>(&mut reader)?);
+            }
+            rows.push(row);
+        }
+        Ok(LookupTable { name, rows })
+    }
+}
+
+/// Asserts that the tuple `(keys..., values...)` is a row of the lookup table named `table`.
+///
+/// The keys are matched against the first columns of the table and the values against the remaining ones,
+/// so that the values can be solved for once the keys are known.
+/// The keys should therefore identify a single row of the table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lookup {
+    pub table: String,
+    pub keys: Vec<Expression>,
+    pub values: Vec<Expression>,
+}
+
+impl Lookup {
+    /// Returns the expressions matched against each column of the table
+    pub fn entries(&self) -> impl Iterator<Item = &Expression> {
+        self.keys.iter().chain(&self.values)
+    }
+
+    /// Returns the number of columns which identify a row of the table.
+    /// A lookup without keys matches all of its entries instead.
+    pub fn num_keys(&self) -> usize {
+        match self.keys.is_empty() {
+            true => self.values.len(),
+            false => self.keys.len(),
+        }
+    }
+
+    /// Checks that `table` can be looked up, i.e. that each of its rows has one column per entry of the lookup
+    /// and that no two distinct rows have the same keys.
+    ///
+    /// Returns the reason why the table cannot be looked up otherwise.
+    pub fn check_table(&self, table: &LookupTable) -> Result<(), String> {
+        let num_columns = self.keys.len() + self.values.len();
+        if num_columns == 0 {
+            return Err("lookups must have at least one column".to_owned());
+        }
+        if table.rows.iter().any(|row| row.len() != num_columns) {
+            return Err(format!("the rows of the table do not have {num_columns} columns"));
+        }
+
+        let num_keys = self.num_keys();
+        let distinct_rows: BTreeSet<&[FieldElement]> =
+            table.rows.iter().map(Vec::as_slice).collect();
+        let mut distinct_keys = BTreeSet::new();
+        if !distinct_rows.into_iter().all(|row| distinct_keys.insert(&row[..num_keys])) {
+            return Err("the keys do not identify a single row of the table".to_owned());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_string(&mut writer, &self.table)?;
+
+        for expressions in [&self.keys, &self.values] {
+            write_u32(&mut writer, expressions.len() as u32)?;
+            for expr in expressions {
+                expr.write(&mut writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let table = read_string(&mut reader)?;
+
+        let mut read_expressions = || -> std::io::Result<Vec<Expression>> {
+            let len = read_u32(&mut reader)?;
+            let mut expressions = Vec::new();
+            for _ in 0..len {
+                expressions.push(Expression::read(&mut reader)?);
+            }
+            Ok(expressions)
+        };
+        let keys = read_expressions()?;
+        let values = read_expressions()?;
+        Ok(Lookup { table, keys, values })
+    }
+}
+
+impl std::fmt::Display for Lookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LOOKUP {} ", self.table)?;
+        write!(f, "(keys: {}, values: {})", self.keys.len(), self.values.len())
+    }
+}
+
+fn write_string<W: Write>(mut writer: W, string: &str) -> std::io::Result<()> {
+    let bytes = string.as_bytes();
+    write_u32(&mut writer, bytes.len() as u32)?;
+    write_bytes(&mut writer, bytes)?;
+    Ok(())
+}
+
+fn read_string<R: Read>(mut reader: R) -> std::io::Result<String> {
+    let len = read_u32(&mut reader)?;
+    let bytes = read_bytes(&mut reader, len as usize)?;
+    String::from_utf8(bytes).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+}
@@ -0,0 +1,75 @@
+use std::io::{Read, Write};
+
+use crate::native_types::Witness;
+use crate::serialization::{read_witnesses, write_witnesses};
+use serde::{Deserialize, Serialize};
+
+/// Verifies an inner proof against its verification key, as part of recursive proof aggregation.
+///
+/// Rather than verifying the inner proof directly, the backend accumulates it into an aggregation object,
+/// whose final verification is deferred to the verifier of the outer proof.
+/// The layout of each of the witness lists is specific to the backend.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecursiveAggregation {
+    pub verification_key: Vec<Witness>,
+    pub proof: Vec<Witness>,
+    pub public_inputs: Vec<Witness>,
+    /// The aggregation object output by a previous recursive aggregation,
+    /// empty if this is the first proof to be aggregated.
+    pub input_aggregation_object: Vec<Witness>,
+    pub output_aggregation_object: Vec<Witness>,
+}
+
+impl RecursiveAggregation {
+    /// Returns the witnesses which must be assigned before the output aggregation object can be computed
+    pub fn get_inputs_vec(&self) -> Vec<Witness> {
+        let mut inputs = self.verification_key.clone();
+        inputs.extend(&self.proof);
+        inputs.extend(&self.public_inputs);
+        inputs.extend(&self.input_aggregation_object);
+        inputs
+    }
+
+    pub(crate) fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write_witnesses(&mut writer, &self.verification_key)?;
+        write_witnesses(&mut writer, &self.proof)?;
+        write_witnesses(&mut writer, &self.public_inputs)?;
+        write_witnesses(&mut writer, &self.input_aggregation_object)?;
+        write_witnesses(&mut writer, &self.output_aggregation_object)?;
+        Ok(())
+    }
+
+    pub(crate) fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        Ok(RecursiveAggregation {
+            verification_key: read_witnesses(&mut reader)?,
+            proof: read_witnesses(&mut reader)?,
+            public_inputs: read_witnesses(&mut reader)?,
+            input_aggregation_object: read_witnesses(&mut reader)?,
+            output_aggregation_object: read_witnesses(&mut reader)?,
+        })
+    }
+}
+
+impl std::fmt::Display for RecursiveAggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn witnesses(witnesses: &[Witness]) -> String {
+            match (witnesses.first(), witnesses.last()) {
+                (Some(first), Some(last)) => {
+                    format!("[_{}..._{}]", first.witness_index(), last.witness_index())
+                }
+                _ => "[]".to_owned(),
+            }
+        }
+
+        write!(f, "RECURSIVE_AGGREGATION ")?;
+        write!(
+            f,
+            "(vk: {}, proof: {}, public inputs: {}, input aggregation object: {}) -> {}",
+            witnesses(&self.verification_key),
+            witnesses(&self.proof),
+            witnesses(&self.public_inputs),
+            witnesses(&self.input_aggregation_object),
+            witnesses(&self.output_aggregation_object)
+        )
+    }
+}
@@ -2,6 +2,8 @@ use std::io::{Read, Write};
 
 use acir_field::FieldElement;
 
+use crate::native_types::Witness;
+
 pub(crate) fn read_n<const NUM_BYTES: usize, R: Read>(
     mut r: R,
 ) -> std::io::Result<[u8; NUM_BYTES]> {
@@ -48,6 +50,24 @@ pub(crate) fn read_u32<R: Read>(r: R) -> std::io::Result<u32> {
     Ok(u32::from_le_bytes(bytes))
 }
 
+// Writes the number of witnesses followed by their indices
+pub(crate) fn write_witnesses<W: Write>(mut w: W, witnesses: &[Witness]) -> std::io::Result<()> {
+    write_u32(&mut w, witnesses.len() as u32)?;
+    for witness in witnesses {
+        write_u32(&mut w, witness.witness_index())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_witnesses<R: Read>(mut r: R) -> std::io::Result<Vec<Witness>> {
+    let len = read_u32(&mut r)?;
+    let mut witnesses = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        witnesses.push(Witness(read_u32(&mut r)?));
+    }
+    Ok(witnesses)
+}
+
 pub(crate) fn read_field_element<const NUM_BYTES: usize, R: Read>(
     mut r: R,
 ) -> std::io::Result<FieldElement> {
@@ -2,7 +2,7 @@
 pub mod optimizers;
 pub mod transformers;
 
-use crate::Language;
+use crate::{custom_black_box::CustomBlackBoxRegistry, Language, MemoryOpError};
 use acir::{
     circuit::{Circuit, Opcode},
     native_types::{Expression, Witness},
@@ -19,19 +19,42 @@ use self::optimizers::RangeOptimizer;
 pub enum CompileError {
     #[error("The blackbox function {0} is not supported by the backend and acvm does not have a fallback implementation")]
     UnsupportedBlackBox(BlackBoxFunc),
+    #[error("The blackbox function {0} is not supported by the backend for messages of variable length and acvm does not have a fallback implementation")]
+    UnsupportedVariableLengthHash(BlackBoxFunc),
+    #[error("The custom blackbox function {0} is not supported by the backend and has no registered fallback")]
+    UnsupportedCustomBlackBox(String),
+    #[error("Invalid call to the custom blackbox function {0}: {1}")]
+    InvalidCustomBlackBoxCall(String, String),
+    #[error("Invalid call to the blackbox function {0}: {1}")]
+    InvalidBlackBoxCall(BlackBoxFunc, String),
+    #[error("Invalid {0} opcode: {1}")]
+    InvalidBigIntCall(String, String),
+    #[error("The lookup table {0} is not defined in the circuit")]
+    UnknownLookupTable(String),
+    #[error("The lookup table {0} cannot be looked up: {1}")]
+    InvalidLookupTable(String, String),
+    #[error("The memory block {0} must be initialized once, before its operations")]
+    InvalidMemoryInit(u32),
+    #[error(
+        "The backend supports the initialization of the memory block {0} but not its operations"
+    )]
+    UnsupportedMemoryOp(u32),
+    #[error("The operation {position} of the memory block {block_id} is malformed: {reason}")]
+    InvalidMemoryBlock { block_id: u32, position: usize, reason: MemoryOpError },
 }
 
 pub fn compile(
     acir: Circuit,
     np_language: Language,
     is_opcode_supported: IsOpcodeSupported,
+    custom_black_boxes: &CustomBlackBoxRegistry,
 ) -> Result<Circuit, CompileError> {
     // Instantiate the optimizer.
     // Currently the optimizer and reducer are one in the same
     // for CSAT
 
     // Fallback transformer pass
-    let acir = FallbackTransformer::transform(acir, is_opcode_supported)?;
+    let acir = FallbackTransformer::transform(acir, is_opcode_supported, custom_black_boxes)?;
 
     // General optimizer pass
     let mut opcodes: Vec<Opcode> = Vec::new();
@@ -102,5 +125,6 @@ pub fn compile(
         // The optimizer does not add new public inputs
         public_parameters: acir.public_parameters,
         return_values: acir.return_values,
+        tables: acir.tables,
     })
 }
@@ -1,10 +1,15 @@
 use acir::{
-    circuit::{Circuit, Opcode},
+    circuit::{opcodes::BlackBoxFuncCall, Circuit, Opcode},
     native_types::Witness,
-    BlackBoxFunc,
+    FieldElement,
 };
+use num_bigint::BigUint;
+use num_traits::One;
 use std::collections::{BTreeMap, HashSet};
 
+/// The lower and upper bounds, both inclusive, between which a range constraint restricts a witness.
+type RangeBounds = (BigUint, BigUint);
+
 /// RangeOptimizer will remove redundant range constraints.
 ///
 /// Suppose we had the following pseudo-code:
@@ -21,9 +26,13 @@ use std::collections::{BTreeMap, HashSet};
 ///
 /// This optimization pass will keep the 16-bit range constraint
 /// and remove the 32-bit range constraint opcode.
+///
+/// Bounded range constraints are handled in the same way: a range constraint is removed
+/// if the bounds of another range constraint on the same witness lie within its own bounds.
 pub(crate) struct RangeOptimizer {
-    /// Maps witnesses to their lowest known bit sizes.
-    lists: BTreeMap<Witness, u32>,
+    /// Maps witnesses to the tightest bounds they are known to be constrained to,
+    /// none of which contains another.
+    lists: BTreeMap<Witness, Vec<RangeBounds>>,
     circuit: Circuit,
 }
 
@@ -35,45 +44,44 @@ impl RangeOptimizer {
         Self { circuit, lists: range_list }
     }
 
-    /// Stores the lowest bit range, that a witness
-    /// has been constrained to be.
+    /// Stores the tightest bounds that a witness
+    /// has been constrained to.
     /// For example, if we constrain a witness `x` to be
     /// both 32 bits and 16 bits. This function will
     /// only store the fact that we have constrained it to
     /// be 16 bits.
-    fn collect_ranges(circuit: &Circuit) -> BTreeMap<Witness, u32> {
-        let mut witness_to_bit_sizes = BTreeMap::new();
+    fn collect_ranges(circuit: &Circuit) -> BTreeMap<Witness, Vec<RangeBounds>> {
+        let mut witness_to_bounds: BTreeMap<Witness, Vec<RangeBounds>> = BTreeMap::new();
 
         for opcode in &circuit.opcodes {
-            // Extract the witness index and number of bits,
+            // Extract the witness index and bounds,
             // if it is a range constraint
-            let (witness, num_bits) = match extract_range_opcode(opcode) {
+            let (witness, bounds) = match extract_range_opcode(opcode) {
                 Some(func_inputs) => func_inputs,
                 None => continue,
             };
 
-            // Check if the witness has already been recorded and if the witness
-            // size is more than the current one, we replace it
-            let should_replace = match witness_to_bit_sizes.get(&witness).copied() {
-                Some(old_range_bits) => old_range_bits > num_bits,
-                None => true,
-            };
-            if should_replace {
-                witness_to_bit_sizes.insert(witness, num_bits);
+            // If the witness is already constrained to bounds within these ones then they are redundant,
+            // otherwise they replace the recorded bounds which contain them
+            let known_bounds = witness_to_bounds.entry(witness).or_default();
+            if known_bounds.iter().any(|known| contains(&bounds, known)) {
+                continue;
             }
+            known_bounds.retain(|known| !contains(known, &bounds));
+            known_bounds.push(bounds);
         }
-        witness_to_bit_sizes
+        witness_to_bounds
     }
 
     /// Returns a `Circuit` where each Witness is only range constrained
-    /// once to the lowest number `bit size` possible.
+    /// once to each of its tightest bounds.
     pub(crate) fn replace_redundant_ranges(self) -> Circuit {
-        let mut already_seen_witness = HashSet::new();
+        let mut already_seen_bounds = HashSet::new();
 
         let mut optimized_opcodes = Vec::with_capacity(self.circuit.opcodes.len());
 
         for opcode in self.circuit.opcodes {
-            let (witness, num_bits) = match extract_range_opcode(&opcode) {
+            let (witness, bounds) = match extract_range_opcode(&opcode) {
                 Some(range_opcode) => range_opcode,
                 None => {
                     // If its not the range opcode, add it to the opcode
@@ -82,21 +90,21 @@ impl RangeOptimizer {
                     continue;
                 }
             };
-            // If we've already applied the range constraint for this witness then skip this opcode.
-            let already_added = already_seen_witness.contains(&witness);
+            // If we've already applied these bounds to this witness then skip this opcode.
+            let already_added = already_seen_bounds.contains(&(witness, bounds.clone()));
             if already_added {
                 continue;
             }
 
-            // Check if this is the lowest number of bits in the circuit
-            let stored_num_bits = self.lists.get(&witness).expect("Could not find witness. This should never be the case if `collect_ranges` is called");
-            let is_lowest_bit_size = num_bits <= *stored_num_bits;
+            // Check if these are among the tightest bounds in the circuit
+            let stored_bounds = self.lists.get(&witness).expect("Could not find witness. This should never be the case if `collect_ranges` is called");
+            let is_tightest_bounds = stored_bounds.contains(&bounds);
 
-            // If the opcode is associated with the lowest bit size
+            // If the opcode is associated with the tightest bounds
             // and we have not added a duplicate of this opcode yet,
             // then we should add retain this range opcode.
-            if is_lowest_bit_size {
-                already_seen_witness.insert(witness);
+            if is_tightest_bounds {
+                already_seen_bounds.insert((witness, bounds));
                 optimized_opcodes.push(opcode);
             }
         }
@@ -106,13 +114,19 @@ impl RangeOptimizer {
             opcodes: optimized_opcodes,
             public_parameters: self.circuit.public_parameters,
             return_values: self.circuit.return_values,
+            tables: self.circuit.tables,
         }
     }
 }
 
+/// Returns true if the `inner` bounds lie within the `outer` bounds
+fn contains(outer: &RangeBounds, inner: &RangeBounds) -> bool {
+    outer.0 <= inner.0 && inner.1 <= outer.1
+}
+
 /// Extract the range opcode from the `Opcode` enum
 /// Returns None, if `Opcode` is not the range opcode.
-fn extract_range_opcode(opcode: &Opcode) -> Option<(Witness, u32)> {
+fn extract_range_opcode(opcode: &Opcode) -> Option<(Witness, RangeBounds)> {
     // Range constraints are blackbox function calls
     // so we first extract the function call
     let func_call = match opcode {
@@ -120,34 +134,58 @@ fn extract_range_opcode(opcode: &Opcode) -> Option<(Witness, u32)> {
         _ => return None,
     };
 
-    // Skip if it is not a range constraint
-    if func_call.name != BlackBoxFunc::RANGE {
-        return None;
-    }
+    // Skip if it is not a range constraint.
+    // A range constraint which is only conditionally applied
+    // cannot be used to remove other range constraints.
+    let to_integer = |value: &FieldElement| BigUint::from_bytes_be(&value.to_be_bytes());
+    let (func_input, bounds) = match func_call {
+        BlackBoxFuncCall::RANGE { input, predicate: None } => {
+            (input, (BigUint::default(), (BigUint::one() << input.num_bits) - 1_u32))
+        }
+        BlackBoxFuncCall::BoundedRange { input, min, max, predicate: None } => {
+            (input, (to_integer(min), to_integer(max)))
+        }
+        _ => return None,
+    };
 
-    let func_input =
-        func_call.inputs.first().expect("we expect there to be one input for a range call");
-    Some((func_input.witness, func_input.num_bits))
+    // Range constraints on constants are left untouched
+    let witness = func_input.to_witness()?;
+    Some((witness, bounds))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::compiler::optimizers::redundant_range::{extract_range_opcode, RangeOptimizer};
+    use crate::compiler::optimizers::redundant_range::{
+        extract_range_opcode, RangeBounds, RangeOptimizer,
+    };
     use acir::{
         circuit::{
             opcodes::{BlackBoxFuncCall, FunctionInput},
             Circuit, Opcode, PublicInputs,
         },
         native_types::{Expression, Witness},
-        BlackBoxFunc,
+        FieldElement,
     };
+    use num_bigint::BigUint;
+
+    fn bits_bounds(num_bits: u32) -> RangeBounds {
+        (BigUint::default(), (BigUint::from(1_u32) << num_bits) - 1_u32)
+    }
+
+    fn bounded_range_constraint(witness: Witness, min: u128, max: u128) -> Opcode {
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BoundedRange {
+            input: FunctionInput::witness(witness, FieldElement::max_num_bits()),
+            min: FieldElement::from(min),
+            max: FieldElement::from(max),
+            predicate: None,
+        })
+    }
 
     fn test_circuit(ranges: Vec<(Witness, u32)>) -> Circuit {
         fn test_range_constraint(witness: Witness, num_bits: u32) -> Opcode {
-            Opcode::BlackBoxFuncCall(BlackBoxFuncCall {
-                name: BlackBoxFunc::RANGE,
-                inputs: vec![FunctionInput { witness, num_bits }],
-                outputs: vec![],
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput::witness(witness, num_bits),
+                predicate: None,
             })
         }
 
@@ -161,6 +199,7 @@ mod tests {
             opcodes,
             public_parameters: PublicInputs::default(),
             return_values: PublicInputs::default(),
+            tables: Vec::new(),
         }
     }
 
@@ -171,23 +210,24 @@ mod tests {
 
         let optimizer = RangeOptimizer::new(circuit);
 
-        let range_size = *optimizer
+        let range_bounds = optimizer
             .lists
             .get(&Witness(1))
             .expect("Witness(1) was inserted, but it is missing from the map");
         assert_eq!(
-            range_size, 16,
+            range_bounds,
+            &vec![bits_bounds(16)],
             "expected a range size of 16 since that was the lowest bit size provided"
         );
 
         let optimized_circuit = optimizer.replace_redundant_ranges();
         assert_eq!(optimized_circuit.opcodes.len(), 1);
 
-        let (witness, num_bits) =
+        let (witness, bounds) =
             extract_range_opcode(&optimized_circuit.opcodes[0]).expect("expected one range opcode");
 
         assert_eq!(witness, Witness(1));
-        assert_eq!(num_bits, 16);
+        assert_eq!(bounds, bits_bounds(16));
     }
 
     #[test]
@@ -205,15 +245,15 @@ mod tests {
         let optimized_circuit = optimizer.replace_redundant_ranges();
         assert_eq!(optimized_circuit.opcodes.len(), 2);
 
-        let (witness_a, num_bits_a) =
+        let (witness_a, bounds_a) =
             extract_range_opcode(&optimized_circuit.opcodes[0]).expect("expected two range opcode");
-        let (witness_b, num_bits_b) =
+        let (witness_b, bounds_b) =
             extract_range_opcode(&optimized_circuit.opcodes[1]).expect("expected two range opcode");
 
         assert_eq!(witness_a, Witness(1));
         assert_eq!(witness_b, Witness(2));
-        assert_eq!(num_bits_a, 16);
-        assert_eq!(num_bits_b, 23);
+        assert_eq!(bounds_a, bits_bounds(16));
+        assert_eq!(bounds_b, bits_bounds(23));
     }
 
     #[test]
@@ -231,4 +271,23 @@ mod tests {
         let optimized_circuit = optimizer.replace_redundant_ranges();
         assert_eq!(optimized_circuit.opcodes.len(), 5)
     }
+
+    #[test]
+    fn retain_tightest_bounded_ranges() {
+        // [10, 1000] lies within 16 bits and within [0, 2000], so only it is kept
+        let mut circuit = test_circuit(vec![(Witness(1), 16)]);
+        circuit.opcodes.push(bounded_range_constraint(Witness(1), 0, 2000));
+        circuit.opcodes.push(bounded_range_constraint(Witness(1), 10, 1000));
+        circuit.opcodes.push(bounded_range_constraint(Witness(1), 10, 1000));
+
+        let optimized_circuit = RangeOptimizer::new(circuit).replace_redundant_ranges();
+        assert_eq!(optimized_circuit.opcodes, vec![bounded_range_constraint(Witness(1), 10, 1000)]);
+
+        // Neither [0, 255] nor [10, 1000] lies within the other, so both are kept
+        let mut circuit = test_circuit(vec![(Witness(1), 8)]);
+        circuit.opcodes.push(bounded_range_constraint(Witness(1), 10, 1000));
+
+        let optimized_circuit = RangeOptimizer::new(circuit.clone()).replace_redundant_ranges();
+        assert_eq!(optimized_circuit.opcodes, circuit.opcodes);
+    }
 }
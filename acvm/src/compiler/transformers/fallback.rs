@@ -1,9 +1,16 @@
 use super::super::CompileError;
+use crate::custom_black_box::CustomBlackBoxRegistry;
 use acir::{
-    circuit::{opcodes::BlackBoxFuncCall, Circuit, Opcode},
-    native_types::Expression,
-    BlackBoxFunc,
+    circuit::{
+        black_box_functions::HashOutputMode,
+        opcodes::{BlackBoxFuncCall, BlockId, MemOp, MemoryBlock, MemoryOpError},
+        Circuit, Opcode,
+    },
+    native_types::{Expression, Witness},
+    BlackBoxFunc, FieldElement,
 };
+use std::collections::{BTreeMap, BTreeSet};
+use stdlib::helpers::VariableStore;
 
 // A predicate that returns true if the black box function is supported
 pub type IsOpcodeSupported = fn(&Opcode) -> bool;
@@ -12,27 +19,133 @@ pub struct FallbackTransformer;
 
 impl FallbackTransformer {
     //ACIR pass which replace unsupported opcodes using arithmetic fallback
+    //Unsupported custom black box functions are replaced using the fallback of the registered function
     pub fn transform(
         acir: Circuit,
         is_supported: IsOpcodeSupported,
+        custom_black_boxes: &CustomBlackBoxRegistry,
     ) -> Result<Circuit, CompileError> {
         let mut acir_supported_opcodes = Vec::with_capacity(acir.opcodes.len());
 
         let mut witness_idx = acir.current_witness_index + 1;
 
+        // Unsupported memory opcodes are gathered into a block per memory,
+        // which are replaced by arithmetic at the end of the circuit
+        let mut memories: BTreeMap<u32, MemoryBlock> = BTreeMap::new();
+        // The memories whose initialization is supported by the backend
+        let mut native_memories: BTreeSet<u32> = BTreeSet::new();
+
         for opcode in acir.opcodes {
             match &opcode {
                 Opcode::Arithmetic(_)
                 | Opcode::Directive(_)
-                | Opcode::Block(_)
-                | Opcode::ROM(_)
-                | Opcode::RAM(_)
-                | Opcode::Oracle { .. } => {
-                    // directive, arithmetic expression or  block are handled by acvm
+                | Opcode::Oracle { .. }
+                | Opcode::RecursiveAggregation(_) => {
+                    // directive and arithmetic expression are handled by acvm
                     // The oracle opcode is assumed to be supported.
+                    // Recursive aggregation cannot be expressed with arithmetic, so it is left to the backend.
                     acir_supported_opcodes.push(opcode);
                     continue;
                 }
+                Opcode::Block(block) | Opcode::ROM(block) | Opcode::RAM(block) => {
+                    if is_supported(&opcode) {
+                        acir_supported_opcodes.push(opcode);
+                        continue;
+                    }
+                    // The backend may support memory opcodes instead of whole blocks.
+                    // Memory opcodes can write after the initialization, so ROMs are not lowered to them.
+                    if !matches!(opcode, Opcode::ROM(_)) {
+                        let memory_opcodes = block
+                            .to_memory_opcodes()
+                            .map_err(Self::invalid_memory_block(block.id))?;
+                        if memory_opcodes.iter().all(is_supported) {
+                            acir_supported_opcodes.extend(memory_opcodes);
+                            continue;
+                        }
+                    }
+                    // Unsupported memory blocks are replaced by a sorted copy of their trace,
+                    // whose consistency is checked using arithmetic.
+                    // Blocks are lowered as RAM, so they must also start with an initialization phase.
+                    let (updated_witness_index, opcodes_fallback) = match opcode {
+                        Opcode::ROM(_) => stdlib::fallback::rom(block, witness_idx),
+                        _ => stdlib::fallback::ram(block, witness_idx),
+                    }
+                    .map_err(Self::invalid_memory_block(block.id))?;
+                    witness_idx = updated_witness_index;
+                    acir_supported_opcodes.extend(opcodes_fallback);
+                    continue;
+                }
+                Opcode::MemoryInit { id, init } => {
+                    if memories.contains_key(&id.0) || native_memories.contains(&id.0) {
+                        return Err(CompileError::InvalidMemoryInit(id.0));
+                    }
+                    if is_supported(&opcode) {
+                        native_memories.insert(id.0);
+                        acir_supported_opcodes.push(opcode);
+                    } else {
+                        let trace = (0_u128..)
+                            .zip(init)
+                            .map(|(index, value)| MemOp {
+                                operation: Expression::one(),
+                                index: Expression::from_field(FieldElement::from(index)),
+                                value: value.clone(),
+                            })
+                            .collect();
+                        let block = MemoryBlock { id: *id, len: init.len() as u32, trace };
+                        memories.insert(id.0, block);
+                    }
+                    continue;
+                }
+                Opcode::MemoryOp { id, op } => {
+                    // The operations on a memory are solved the same way as its initialization
+                    if let Some(block) = memories.get_mut(&id.0) {
+                        block.trace.push(op.clone());
+                    } else if !native_memories.contains(&id.0) {
+                        return Err(CompileError::InvalidMemoryInit(id.0));
+                    } else if is_supported(&opcode) {
+                        acir_supported_opcodes.push(opcode);
+                    } else {
+                        return Err(CompileError::UnsupportedMemoryOp(id.0));
+                    }
+                    continue;
+                }
+                Opcode::BigInt(call) => {
+                    if is_supported(&opcode) {
+                        acir_supported_opcodes.push(opcode);
+                    } else {
+                        // Unsupported bigint opcodes are replaced by arithmetic over their limbs
+                        let (updated_witness_index, opcodes_fallback) =
+                            stdlib::fallback::bigint(call, witness_idx).map_err(|reason| {
+                                CompileError::InvalidBigIntCall(call.name().to_owned(), reason)
+                            })?;
+                        witness_idx = updated_witness_index;
+                        acir_supported_opcodes.extend(opcodes_fallback);
+                    }
+                    continue;
+                }
+                Opcode::Lookup(lookup) => {
+                    if is_supported(&opcode) {
+                        acir_supported_opcodes.push(opcode);
+                    } else {
+                        // Unsupported lookups select the matching row of the table using arithmetic
+                        let table = acir
+                            .tables
+                            .iter()
+                            .find(|table| table.name == lookup.table)
+                            .ok_or_else(|| {
+                                CompileError::UnknownLookupTable(lookup.table.clone())
+                            })?;
+                        let (updated_witness_index, opcodes_fallback) =
+                            stdlib::fallback::lookup(lookup, table, witness_idx).map_err(
+                                |reason| {
+                                    CompileError::InvalidLookupTable(table.name.clone(), reason)
+                                },
+                            )?;
+                        witness_idx = updated_witness_index;
+                        acir_supported_opcodes.extend(opcodes_fallback);
+                    }
+                    continue;
+                }
                 Opcode::BlackBoxFuncCall(bb_func_call) => {
                     // We know it is an black box function. Now check if it is
                     // supported by the backend. If it is supported, then we can simply
@@ -40,12 +153,24 @@ impl FallbackTransformer {
                     if is_supported(&opcode) {
                         acir_supported_opcodes.push(opcode);
                         continue;
+                    } else if let Some((updated_witness_index, bytes_call, pack_opcodes)) =
+                        Self::hash_output_fallback(bb_func_call, witness_idx)?
+                    {
+                        // The backend may only support hash functions which output their digest as bytes,
+                        // in which case the bytes are packed into the requested shape using arithmetic expressions
+                        if !is_supported(&bytes_call) {
+                            return Err(Self::unsupported_black_box(bb_func_call));
+                        }
+                        witness_idx = updated_witness_index;
+
+                        acir_supported_opcodes.push(bytes_call);
+                        acir_supported_opcodes.extend(pack_opcodes);
                     } else {
                         // If we get here then we know that this black box function is not supported
                         // so we need to replace it with a version of the opcode which only uses arithmetic
                         // expressions
                         let (updated_witness_index, opcodes_fallback) =
-                            Self::opcode_fallback(bb_func_call, witness_idx)?;
+                            Self::opcode_fallback(bb_func_call, witness_idx, custom_black_boxes)?;
                         witness_idx = updated_witness_index;
 
                         acir_supported_opcodes.extend(opcodes_fallback);
@@ -54,55 +179,224 @@ impl FallbackTransformer {
             }
         }
 
+        for block in memories.values() {
+            let (updated_witness_index, opcodes_fallback) =
+                stdlib::fallback::ram(block, witness_idx)
+                    .map_err(Self::invalid_memory_block(block.id))?;
+            witness_idx = updated_witness_index;
+            acir_supported_opcodes.extend(opcodes_fallback);
+        }
+
+        // Only the tables which are still looked up need to be given to the backend
+        let tables = acir
+            .tables
+            .into_iter()
+            .filter(|table| {
+                acir_supported_opcodes.iter().any(
+                    |opcode| matches!(opcode, Opcode::Lookup(lookup) if lookup.table == table.name),
+                )
+            })
+            .collect();
+
         Ok(Circuit {
             current_witness_index: witness_idx,
             opcodes: acir_supported_opcodes,
             public_parameters: acir.public_parameters,
             return_values: acir.return_values,
+            tables,
         })
     }
 
     fn opcode_fallback(
         gc: &BlackBoxFuncCall,
         current_witness_idx: u32,
+        custom_black_boxes: &CustomBlackBoxRegistry,
     ) -> Result<(u32, Vec<Opcode>), CompileError> {
-        let (updated_witness_index, opcodes_fallback) = match gc.name {
-            BlackBoxFunc::AND => {
-                let (lhs, rhs, result, num_bits) = crate::pwg::logic::extract_input_output(gc);
+        // The fallbacks constrain the inputs multiplied by the predicate, so that when the predicate
+        // is zero they constrain zero inputs to produce the zero outputs assigned by the solver.
+        // Any other value than zero or one would scale the inputs, so the predicate is constrained to be boolean.
+        let (current_witness_idx, predicate_opcodes, predicate) = match gc.predicate() {
+            Some(predicate) => {
+                let (current_witness_idx, mut predicate_opcodes, predicate) =
+                    Self::linear_predicate(predicate, current_witness_idx);
+                // predicate * (predicate - 1) = 0
+                let boolean = &predicate * &(predicate.clone() - FieldElement::one());
+                predicate_opcodes.push(Opcode::Arithmetic(boolean));
+                (current_witness_idx, predicate_opcodes, predicate)
+            }
+            None => (current_witness_idx, Vec::new(), Expression::one()),
+        };
+
+        let (updated_witness_index, opcodes_fallback) = match gc {
+            BlackBoxFuncCall::AND { lhs, rhs, output, .. } => {
+                assert_eq!(
+                    lhs.num_bits, rhs.num_bits,
+                    "number of bits specified for each input must be the same"
+                );
                 stdlib::fallback::and(
-                    Expression::from(lhs),
-                    Expression::from(rhs),
-                    result,
-                    num_bits,
+                    &predicate * &Expression::from(lhs),
+                    &predicate * &Expression::from(rhs),
+                    *output,
+                    lhs.num_bits,
                     current_witness_idx,
                 )
             }
-            BlackBoxFunc::XOR => {
-                let (lhs, rhs, result, num_bits) = crate::pwg::logic::extract_input_output(gc);
+            BlackBoxFuncCall::XOR { lhs, rhs, output, .. } => {
+                assert_eq!(
+                    lhs.num_bits, rhs.num_bits,
+                    "number of bits specified for each input must be the same"
+                );
                 stdlib::fallback::xor(
-                    Expression::from(lhs),
-                    Expression::from(rhs),
-                    result,
-                    num_bits,
+                    &predicate * &Expression::from(lhs),
+                    &predicate * &Expression::from(rhs),
+                    *output,
+                    lhs.num_bits,
                     current_witness_idx,
                 )
             }
-            BlackBoxFunc::RANGE => {
-                // TODO: add consistency checks in one place
-                // TODO: we aren't checking that range gate should have one input
-                let input = &gc.inputs[0];
+            BlackBoxFuncCall::RANGE { input, .. } => {
                 // Note there are no outputs because range produces no outputs
                 stdlib::fallback::range(
-                    Expression::from(input.witness),
+                    &predicate * &Expression::from(input),
                     input.num_bits,
                     current_witness_idx,
                 )
             }
-            _ => {
-                return Err(CompileError::UnsupportedBlackBox(gc.name));
+            BlackBoxFuncCall::BoundedRange { input, min, max, .. } => {
+                // When the predicate is zero the constrained value is `min`, which is within the bounds
+                let gate = (&predicate * &(Expression::from(input) - *min)) + *min;
+                stdlib::fallback::bounded_range(gate, *min, *max, current_witness_idx).map_err(
+                    |reason| CompileError::InvalidBlackBoxCall(BlackBoxFunc::BoundedRange, reason),
+                )?
+            }
+            BlackBoxFuncCall::Custom { name, inputs, outputs, .. } => {
+                let custom_black_box = custom_black_boxes
+                    .get(name)
+                    .ok_or_else(|| CompileError::UnsupportedCustomBlackBox(name.clone()))?;
+                let fallback = custom_black_box
+                    .fallback
+                    .as_ref()
+                    .ok_or_else(|| CompileError::UnsupportedCustomBlackBox(name.clone()))?;
+                custom_black_box.check_arity(inputs.len(), outputs.len()).map_err(|reason| {
+                    CompileError::InvalidCustomBlackBoxCall(name.clone(), reason)
+                })?;
+                let inputs =
+                    inputs.iter().map(|input| &predicate * &Expression::from(input)).collect();
+                fallback(inputs, outputs, current_witness_idx)
             }
+            _ => return Err(Self::unsupported_black_box(gc)),
+        };
+
+        let mut opcodes = predicate_opcodes;
+        opcodes.extend(opcodes_fallback);
+        Ok((updated_witness_index, opcodes))
+    }
+
+    /// Returns the error for a call to a black box function which has no fallback.
+    ///
+    /// Hash functions have no fallback, but calls with a variable message size are reported separately
+    /// since the backend may support the function for messages of a fixed size.
+    fn unsupported_black_box(gc: &BlackBoxFuncCall) -> CompileError {
+        let func =
+            gc.get_black_box_func().expect("custom black box functions are handled separately");
+        match gc.var_message_size() {
+            Some(_) => CompileError::UnsupportedVariableLengthHash(func),
+            None => CompileError::UnsupportedBlackBox(func),
+        }
+    }
+
+    /// Returns the error reported for the malformed operation at `position` in the trace of the block `id`.
+    fn invalid_memory_block(id: BlockId) -> impl Fn((usize, MemoryOpError)) -> CompileError {
+        move |(position, reason)| CompileError::InvalidMemoryBlock {
+            block_id: id.0,
+            position,
+            reason,
+        }
+    }
+
+    /// Replaces a call to a hash function which outputs its digest in a packed shape
+    /// with a call which outputs the digest as bytes, followed by the arithmetic expressions
+    /// which pack these bytes into the original outputs.
+    ///
+    /// Returns `None` if the call does not output a packed digest.
+    fn hash_output_fallback(
+        gc: &BlackBoxFuncCall,
+        mut current_witness_idx: u32,
+    ) -> Result<Option<(u32, Opcode, Vec<Opcode>)>, CompileError> {
+        let limb_size = match gc.hash_output_mode() {
+            None | Some(HashOutputMode::Bytes) => return Ok(None),
+            Some(HashOutputMode::Limbs128) => 16,
+            Some(HashOutputMode::Field) => 32,
         };
 
-        Ok((updated_witness_index, opcodes_fallback))
+        let packed_outputs = gc.get_outputs_vec();
+        if packed_outputs.len() * limb_size != 32 {
+            let func =
+                gc.get_black_box_func().expect("hash functions are not custom black box functions");
+            return Err(CompileError::InvalidBlackBoxCall(
+                func,
+                format!(
+                    "expected {} outputs for the output mode of the hash function, got {}",
+                    32 / limb_size,
+                    packed_outputs.len()
+                ),
+            ));
+        }
+
+        let mut variables = VariableStore::new(&mut current_witness_idx);
+        let digest_bytes: Vec<Witness> = (0..32).map(|_| variables.new_variable()).collect();
+
+        let mut bytes_call = gc.clone();
+        match &mut bytes_call {
+            BlackBoxFuncCall::SHA256 { output_mode, outputs, .. }
+            | BlackBoxFuncCall::Blake2s { output_mode, outputs, .. }
+            | BlackBoxFuncCall::Keccak256 { output_mode, outputs, .. } => {
+                *output_mode = HashOutputMode::Bytes;
+                *outputs = digest_bytes.clone();
+            }
+            _ => unreachable!("only hash functions have an output mode"),
+        }
+
+        // Each output is the big endian composition of its bytes.
+        // The composition of all 32 bytes is reduced by the field arithmetic.
+        let byte_base = FieldElement::from(256_u128);
+        let pack_opcodes = packed_outputs
+            .iter()
+            .zip(digest_bytes.chunks(limb_size))
+            .map(|(output, bytes)| {
+                let mut limb = Expression::default();
+                for byte in bytes {
+                    limb = &limb * byte_base;
+                    limb.push_addition_term(FieldElement::one(), *byte);
+                }
+                limb.push_addition_term(-FieldElement::one(), *output);
+                Opcode::Arithmetic(limb)
+            })
+            .collect();
+
+        Ok(Some((current_witness_idx, Opcode::BlackBoxFuncCall(bytes_call), pack_opcodes)))
+    }
+
+    /// Returns a degree one expression equal to `predicate`, so that it can be multiplied with
+    /// the inputs of a black box function call.
+    ///
+    /// If `predicate` has degree two then it is assigned to a new witness.
+    fn linear_predicate(
+        predicate: &Expression,
+        mut current_witness_idx: u32,
+    ) -> (u32, Vec<Opcode>, Expression) {
+        if predicate.is_linear() {
+            return (current_witness_idx, Vec::new(), predicate.clone());
+        }
+
+        let predicate_witness = VariableStore::new(&mut current_witness_idx).new_variable();
+        let mut predicate_constraint = predicate.clone();
+        predicate_constraint.push_addition_term(-FieldElement::one(), predicate_witness);
+
+        (
+            current_witness_idx,
+            vec![Opcode::Arithmetic(predicate_constraint)],
+            Expression::from(predicate_witness),
+        )
     }
 }
@@ -0,0 +1,105 @@
+// Black box functions which are not part of ACIR can be registered at runtime,
+// so that adding a new primitive does not require changes to `BlackBoxFunc`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use acir::{
+    circuit::{black_box_functions::FuncDefinition, opcodes::FunctionInput, Opcode},
+    native_types::{Expression, Witness},
+    BlackBoxFunc, FieldElement,
+};
+
+use thiserror::Error;
+
+use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+/// Solves a call to a custom black box function.
+///
+/// The solver is only called once all of the inputs have been assigned and the predicate of the call is non-zero.
+/// The solver can capture state of the backend, such as keys.
+pub type CustomBlackBoxSolver = Box<
+    dyn Fn(
+            &mut BTreeMap<Witness, FieldElement>,
+            &[FunctionInput],
+            &[Witness],
+        ) -> Result<OpcodeResolution, OpcodeResolutionError>
+        + Send
+        + Sync,
+>;
+
+/// Replaces a call to a custom black box function with arithmetic opcodes.
+///
+/// The inputs have already been multiplied by the predicate of the call.
+/// Like the fallbacks in `stdlib`, this takes the current witness index
+/// and returns the updated witness index together with the new opcodes.
+pub type CustomBlackBoxFallback =
+    Box<dyn Fn(Vec<Expression>, &[Witness], u32) -> (u32, Vec<Opcode>) + Send + Sync>;
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum CustomBlackBoxError {
+    #[error("{0} is already a black box function")]
+    ReservedName(&'static str),
+}
+
+pub struct CustomBlackBox {
+    pub definition: FuncDefinition,
+    pub solver: CustomBlackBoxSolver,
+    pub fallback: Option<CustomBlackBoxFallback>,
+}
+
+impl CustomBlackBox {
+    /// Checks that a call with `num_inputs` inputs and `num_outputs` outputs matches the definition of the function.
+    ///
+    /// Returns the reason for the mismatch otherwise.
+    pub(crate) fn check_arity(&self, num_inputs: usize, num_outputs: usize) -> Result<(), String> {
+        let input_size = &self.definition.input_size;
+        if let Some(expected_inputs) = input_size.fixed_size() {
+            if expected_inputs != num_inputs as u128 {
+                return Err(format!("expected {expected_inputs} inputs, but got {num_inputs}"));
+            }
+        } else if input_size.min_size() > num_inputs as u128 {
+            return Err(format!(
+                "expected at least {} inputs, but got {num_inputs}",
+                input_size.min_size()
+            ));
+        }
+        let expected_outputs = self.definition.output_size.0;
+        if expected_outputs != num_outputs as u128 {
+            return Err(format!("expected {expected_outputs} outputs, but got {num_outputs}"));
+        }
+        Ok(())
+    }
+}
+
+/// The set of custom black box functions which are known to the compiler and the solver,
+/// keyed by the name that is used in [`acir::circuit::opcodes::BlackBoxFuncCall::Custom`].
+#[derive(Default)]
+pub struct CustomBlackBoxRegistry {
+    functions: HashMap<&'static str, CustomBlackBox>,
+}
+
+impl CustomBlackBoxRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a custom black box function under the name of its definition.
+    ///
+    /// Returns the function which was previously registered under that name, if any,
+    /// or an error if the name is already used by a [`BlackBoxFunc`], in which case the registry is unchanged.
+    pub fn register(
+        &mut self,
+        definition: FuncDefinition,
+        solver: CustomBlackBoxSolver,
+        fallback: Option<CustomBlackBoxFallback>,
+    ) -> Result<Option<CustomBlackBox>, CustomBlackBoxError> {
+        if BlackBoxFunc::is_valid_black_box_func_name(definition.name) {
+            return Err(CustomBlackBoxError::ReservedName(definition.name));
+        }
+        Ok(self.functions.insert(definition.name, CustomBlackBox { definition, solver, fallback }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CustomBlackBox> {
+        self.functions.get(name)
+    }
+}
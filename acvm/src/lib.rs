@@ -5,10 +5,12 @@
 // Org name is needed because more than one implementation of the same proof system may arise
 
 pub mod compiler;
+pub mod custom_black_box;
 pub mod pwg;
 
 use acir::{
     circuit::{
+        black_box_functions::{HashInputPacking, HashOutputMode, RecoverOutputMode},
         opcodes::{BlackBoxFuncCall, FunctionInput},
         Circuit, Opcode,
     },
@@ -20,6 +22,7 @@ use thiserror::Error;
 
 // re-export acir
 pub use acir;
+pub use acir::circuit::opcodes::MemoryOpError;
 pub use acir::FieldElement;
 
 // This enum represents the different cases in which an
@@ -36,6 +39,8 @@ pub enum OpcodeNotSolvable {
     MissingAssignment(u32),
     #[error("expression has too many unknowns {0}")]
     ExpressionHasTooManyUnknowns(Expression),
+    #[error("waiting for an earlier operation on memory block {0}")]
+    PendingMemoryOperation(u32),
 }
 
 #[derive(PartialEq, Eq, Debug, Error)]
@@ -52,6 +57,30 @@ pub enum OpcodeResolutionError {
     IncorrectNumFunctionArguments(usize, BlackBoxFunc, usize),
     #[error("failed to solve blackbox function: {0}, reason: {1}")]
     BlackBoxFunctionFailed(BlackBoxFunc, String),
+    #[error("custom blackbox function {0} has not been registered")]
+    UnsupportedCustomBlackBoxFunc(String),
+    #[error("failed to solve custom blackbox function: {0}, reason: {1}")]
+    CustomBlackBoxFunctionFailed(String, String),
+    #[error("failed to solve {0}, reason: {1}")]
+    BigIntFailed(String, String),
+    #[error("the predicate of {0} is {1}, but must be 0 or 1")]
+    InvalidPredicate(String, FieldElement),
+    #[error("index {index} is out of bounds at position {position} of memory block {block_id}")]
+    IndexOutOfBounds { block_id: u32, position: usize, index: FieldElement },
+    #[error("malformed operation at position {position} of memory block {block_id}: {reason}")]
+    MalformedMemoryOperation { block_id: u32, position: usize, reason: MemoryOpError },
+    #[error("failed to look up table {0}, reason: {1}")]
+    LookupFailed(String, String),
+    #[error("failed to resolve oracle {0}, reason: {1}")]
+    OracleFailed(String, String),
+    #[error("failed to log, reason: {0}")]
+    LogFailed(String),
+    #[error("attempted to divide by zero")]
+    DivisionByZero,
+    #[error("{value} is not a signed integer of {bit_size} bits")]
+    InvalidSignedInteger { value: FieldElement, bit_size: u32 },
+    #[error("cannot decompose in base {0}, the radix must be between 2 and 256")]
+    InvalidRadix(u32),
 }
 
 pub trait Backend: SmartContract + ProofSystemCompiler + PartialWitnessGenerator + Default {}
@@ -69,75 +98,160 @@ pub trait PartialWitnessGenerator {
     fn and(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
-        outputs: &[Witness],
+        lhs: &FunctionInput,
+        rhs: &FunctionInput,
+        output: &Witness,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn xor(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
-        outputs: &[Witness],
+        lhs: &FunctionInput,
+        rhs: &FunctionInput,
+        output: &Witness,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn range(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
-        outputs: &[Witness],
+        input: &FunctionInput,
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn bounded_range(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        input: &FunctionInput,
+        min: &FieldElement,
+        max: &FieldElement,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn sha256(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
+        var_message_size: Option<&FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
         outputs: &[Witness],
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn blake2s(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
+        var_message_size: Option<&FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
         outputs: &[Witness],
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn compute_merkle_root(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
-        outputs: &[Witness],
+        leaf: &FunctionInput,
+        index: &FunctionInput,
+        hash_path: &[FunctionInput],
+        output: &Witness,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn schnorr_verify(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
-        outputs: &[Witness],
+        public_key_x: &FunctionInput,
+        public_key_y: &FunctionInput,
+        signature: &[FunctionInput],
+        message: &[FunctionInput],
+        output: &Witness,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn pedersen(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
-        outputs: &[Witness],
+        outputs: &(Witness, Witness),
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn hash_to_field128_security(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
-        outputs: &[Witness],
+        output: &Witness,
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    #[allow(clippy::too_many_arguments)]
     fn ecdsa_secp256k1(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        inputs: &[FunctionInput],
+        public_key_x: &[FunctionInput],
+        public_key_y: &[FunctionInput],
+        signature: &[FunctionInput],
+        hashed_message: &[FunctionInput],
+        require_low_s: bool,
+        output: &Witness,
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    #[allow(clippy::too_many_arguments)]
+    fn ecdsa_secp256k1_recover(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        signature: &[FunctionInput],
+        recovery_id: &FunctionInput,
+        hashed_message: &[FunctionInput],
+        require_low_s: bool,
+        output_mode: RecoverOutputMode,
         outputs: &[Witness],
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
     fn fixed_base_scalar_mul(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        input: &FunctionInput,
+        outputs: &(Witness, Witness),
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn keccak256(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
+        var_message_size: Option<&FunctionInput>,
+        input_packing: HashInputPacking,
+        output_mode: HashOutputMode,
         outputs: &[Witness],
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
-    fn keccak256(
+    fn sha256_compression(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        inputs: &[FunctionInput],
+        hash_values: &[FunctionInput],
+        outputs: &[Witness],
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn keccakf1600(
         &self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         inputs: &[FunctionInput],
         outputs: &[Witness],
     ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    /// Computes the output aggregation object of a [recursive aggregation][acir::circuit::opcodes::RecursiveAggregation]
+    fn recursive_aggregation(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        verification_key: &[Witness],
+        proof: &[Witness],
+        public_inputs: &[Witness],
+        input_aggregation_object: &[Witness],
+        output_aggregation_object: &[Witness],
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn embedded_curve_add(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        input1_x: &FunctionInput,
+        input1_y: &FunctionInput,
+        input2_x: &FunctionInput,
+        input2_y: &FunctionInput,
+        outputs: &(Witness, Witness),
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn embedded_curve_double(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        input_x: &FunctionInput,
+        input_y: &FunctionInput,
+        outputs: &(Witness, Witness),
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
+    fn variable_base_scalar_mul(
+        &self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        point_x: &FunctionInput,
+        point_y: &FunctionInput,
+        scalar: &FunctionInput,
+        outputs: &(Witness, Witness),
+    ) -> Result<pwg::OpcodeResolution, OpcodeResolutionError>;
 }
 
 pub trait SmartContract {
@@ -242,11 +356,7 @@ pub fn default_is_opcode_supported(
     // attempt to transform into supported gates. If these are also not available
     // then a compiler error will be emitted.
     fn plonk_is_supported(opcode: &Opcode) -> bool {
-        !matches!(
-            opcode,
-            Opcode::BlackBoxFuncCall(BlackBoxFuncCall { name: BlackBoxFunc::AES, .. })
-                | Opcode::Block(_)
-        )
+        !matches!(opcode, Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES { .. }) | Opcode::Block(_))
     }
 
     match language {
@@ -261,17 +371,36 @@ mod test {
 
     use acir::{
         circuit::{
-            directives::Directive,
-            opcodes::{FunctionInput, OracleData},
-            Opcode,
+            black_box_functions::{
+                FuncDefinition, HashInputPacking, HashOutputMode, InputSize, OutputSize,
+                RecoverOutputMode,
+            },
+            directives::{Directive, LogInfo},
+            opcodes::{
+                BigIntCall, BigIntOp, BlackBoxFuncCall, BlockId, FunctionInput, Lookup,
+                LookupTable, MemOp, MemoryBlock, OracleData, RecursiveAggregation,
+            },
+            Circuit, Opcode,
         },
         native_types::{Expression, Witness},
-        FieldElement,
+        BlackBoxFunc, FieldElement,
     };
 
+    use num_bigint::BigUint;
+
     use crate::{
-        pwg::{self, block::Blocks, OpcodeResolution, PartialWitnessGeneratorStatus},
-        OpcodeResolutionError, PartialWitnessGenerator,
+        compiler::{
+            transformers::{FallbackTransformer, IsOpcodeSupported},
+            CompileError,
+        },
+        custom_black_box::{CustomBlackBoxError, CustomBlackBoxRegistry},
+        pwg::{
+            self,
+            block::Blocks,
+            log::{LogEvent, LogOutput},
+            OpcodeResolution, PartialWitnessGeneratorStatus, SolverOptions,
+        },
+        MemoryOpError, OpcodeResolutionError, PartialWitnessGenerator,
     };
 
     struct StubbedPwg;
@@ -283,129 +412,215 @@ mod test {
             _inputs: &[FunctionInput],
             _outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn and(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _lhs: &FunctionInput,
+            _rhs: &FunctionInput,
+            _output: &Witness,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn xor(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _lhs: &FunctionInput,
+            _rhs: &FunctionInput,
+            _output: &Witness,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn range(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _input: &FunctionInput,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
+        }
+        fn bounded_range(
+            &self,
+            initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            input: &FunctionInput,
+            min: &FieldElement,
+            max: &FieldElement,
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            pwg::range::solve_bounded_range_opcode(initial_witness, input, min, max)
         }
         fn sha256(
             &self,
-            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            inputs: &[FunctionInput],
+            var_message_size: Option<&FunctionInput>,
+            input_packing: HashInputPacking,
+            output_mode: HashOutputMode,
+            outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            pwg::hash::sha256(
+                initial_witness,
+                inputs,
+                var_message_size,
+                input_packing,
+                output_mode,
+                outputs,
+            )
         }
         fn blake2s(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
             _inputs: &[FunctionInput],
+            _var_message_size: Option<&FunctionInput>,
+            _input_packing: HashInputPacking,
+            _output_mode: HashOutputMode,
             _outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn compute_merkle_root(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _leaf: &FunctionInput,
+            _index: &FunctionInput,
+            _hash_path: &[FunctionInput],
+            _output: &Witness,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn schnorr_verify(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _public_key_x: &FunctionInput,
+            _public_key_y: &FunctionInput,
+            _signature: &[FunctionInput],
+            _message: &[FunctionInput],
+            _output: &Witness,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn pedersen(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
             _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _outputs: &(Witness, Witness),
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn hash_to_field128_security(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
             _inputs: &[FunctionInput],
-            _outputs: &[Witness],
+            _output: &Witness,
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn ecdsa_secp256k1(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
-            _inputs: &[FunctionInput],
+            _public_key_x: &[FunctionInput],
+            _public_key_y: &[FunctionInput],
+            _signature: &[FunctionInput],
+            _hashed_message: &[FunctionInput],
+            _require_low_s: bool,
+            _output: &Witness,
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
+        }
+        fn ecdsa_secp256k1_recover(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _signature: &[FunctionInput],
+            _recovery_id: &FunctionInput,
+            _hashed_message: &[FunctionInput],
+            _require_low_s: bool,
+            _output_mode: RecoverOutputMode,
             _outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
         fn fixed_base_scalar_mul(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _input: &FunctionInput,
+            _outputs: &(Witness, Witness),
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
+        }
+        fn keccak256(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
             _inputs: &[FunctionInput],
+            _var_message_size: Option<&FunctionInput>,
+            _input_packing: HashInputPacking,
+            _output_mode: HashOutputMode,
             _outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
-            }
+            panic!("Path not trodden by this test")
         }
-        fn keccak256(
+        fn sha256_compression(
             &self,
             _initial_witness: &mut BTreeMap<Witness, FieldElement>,
             _inputs: &[FunctionInput],
+            _hash_values: &[FunctionInput],
             _outputs: &[Witness],
         ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-            {
-                panic!("Path not trodden by this test")
+            panic!("Path not trodden by this test")
+        }
+        fn keccakf1600(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _inputs: &[FunctionInput],
+            _outputs: &[Witness],
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
+        }
+        fn recursive_aggregation(
+            &self,
+            initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _verification_key: &[Witness],
+            proof: &[Witness],
+            _public_inputs: &[Witness],
+            _input_aggregation_object: &[Witness],
+            output_aggregation_object: &[Witness],
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            // Stands in for the aggregation of a real backend by summing the proof
+            let proof_sum = proof
+                .iter()
+                .fold(FieldElement::zero(), |sum, witness| sum + initial_witness[witness]);
+            for output in output_aggregation_object {
+                initial_witness.insert(*output, proof_sum);
             }
+            Ok(OpcodeResolution::Solved)
+        }
+        fn embedded_curve_add(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _input1_x: &FunctionInput,
+            _input1_y: &FunctionInput,
+            _input2_x: &FunctionInput,
+            _input2_y: &FunctionInput,
+            _outputs: &(Witness, Witness),
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
+        }
+        fn embedded_curve_double(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _input_x: &FunctionInput,
+            _input_y: &FunctionInput,
+            _outputs: &(Witness, Witness),
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
+        }
+        fn variable_base_scalar_mul(
+            &self,
+            _initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            _point_x: &FunctionInput,
+            _point_y: &FunctionInput,
+            _scalar: &FunctionInput,
+            _outputs: &(Witness, Witness),
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            panic!("Path not trodden by this test")
         }
     }
 
@@ -460,8 +675,14 @@ mod test {
             (Witness(2), FieldElement::from(3u128)),
         ]);
         let mut blocks = Blocks::default();
-        let solver_status = pwg::solve(&backend, &mut witness_assignments, &mut blocks, opcodes)
-            .expect("should stall on oracle");
+        let solver_status = pwg::solve(
+            &backend,
+            &mut witness_assignments,
+            &mut blocks,
+            opcodes,
+            SolverOptions::default(),
+        )
+        .expect("should stall on oracle");
         let PartialWitnessGeneratorStatus::RequiresOracleData { mut required_oracle_data, unsolved_opcodes } = solver_status else {
             panic!("Should require oracle data")
         };
@@ -475,9 +696,1180 @@ mod test {
         oracle_data.output_values = vec![oracle_data.input_values.last().unwrap().inverse()];
         let mut next_opcodes_for_solving = vec![Opcode::Oracle(oracle_data)];
         next_opcodes_for_solving.extend_from_slice(&unsolved_opcodes[..]);
-        let solver_status =
-            pwg::solve(&backend, &mut witness_assignments, &mut blocks, next_opcodes_for_solving)
-                .expect("should be solvable");
+        let solver_status = pwg::solve(
+            &backend,
+            &mut witness_assignments,
+            &mut blocks,
+            next_opcodes_for_solving,
+            SolverOptions::default(),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+    }
+
+    #[test]
+    fn resolves_known_oracles_in_process() {
+        let oracle = |name: &str, input: Witness, output: Witness| {
+            Opcode::Oracle(OracleData {
+                name: name.into(),
+                inputs: vec![Expression::from(input)],
+                input_values: vec![],
+                outputs: vec![output],
+                output_values: vec![],
+            })
+        };
+        // The second oracle depends on the output of the first one
+        let opcodes = vec![
+            oracle("invert", Witness(2), Witness(3)),
+            oracle("invert", Witness(1), Witness(2)),
+        ];
+        let mut resolver = |name: &str, inputs: &[FieldElement]| match name {
+            "invert" => Ok(Some(vec![inputs[0].inverse()])),
+            _ => Ok(None),
+        };
+
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(2u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            opcodes,
+            SolverOptions::default().with_oracle_resolver(&mut resolver),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved);
+        assert_eq!(witness_assignments[&Witness(3)], FieldElement::from(2u128));
+
+        // Unknown oracles are returned to the caller
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(2u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            vec![
+                oracle("invert", Witness(1), Witness(2)),
+                oracle("square", Witness(2), Witness(3)),
+            ],
+            SolverOptions::default().with_oracle_resolver(&mut resolver),
+        )
+        .expect("should stall on the unknown oracle");
+        let PartialWitnessGeneratorStatus::RequiresOracleData { required_oracle_data, .. } =
+            solver_status
+        else {
+            panic!("Should require oracle data")
+        };
+        assert_eq!(required_oracle_data.len(), 1);
+        assert_eq!(required_oracle_data[0].name, "square");
+        assert_eq!(required_oracle_data[0].input_values, vec![FieldElement::from(2u128).inverse()]);
+
+        // The resolver must return a value for each output
+        let result = pwg::solve(
+            &StubbedPwg,
+            &mut BTreeMap::from([(Witness(1), FieldElement::one())]),
+            &mut Blocks::default(),
+            vec![oracle("invert", Witness(1), Witness(2))],
+            SolverOptions::default()
+                .with_oracle_resolver(&mut |_: &str, _: &[FieldElement]| Ok(Some(vec![]))),
+        );
+        assert!(matches!(result, Err(OpcodeResolutionError::OracleFailed(..))));
+    }
+
+    #[test]
+    fn skips_black_box_with_zero_predicate() {
+        // The stubbed backend panics if it is asked to solve the XOR call,
+        // so this test only passes if the call is skipped.
+        let opcodes = vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::XOR {
+            lhs: FunctionInput::witness(Witness(1), 8),
+            rhs: FunctionInput::witness(Witness(1), 8),
+            output: Witness(2),
+            predicate: Some(Expression::from(Witness(3))),
+        })];
+
+        let mut witness_assignments =
+            BTreeMap::from([(Witness(1), FieldElement::one()), (Witness(3), FieldElement::zero())]);
+        let mut blocks = Blocks::default();
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut blocks,
+            opcodes,
+            SolverOptions::default(),
+        )
+        .expect("should be solvable");
+
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        assert_eq!(witness_assignments[&Witness(2)], FieldElement::zero());
+    }
+
+    #[test]
+    fn rejects_black_box_with_non_boolean_predicate() {
+        let opcodes = vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::XOR {
+            lhs: FunctionInput::witness(Witness(1), 8),
+            rhs: FunctionInput::witness(Witness(1), 8),
+            output: Witness(2),
+            predicate: Some(Expression::from(Witness(3))),
+        })];
+        let mut witness_assignments = BTreeMap::from([
+            (Witness(1), FieldElement::one()),
+            (Witness(3), FieldElement::from(2_u128)),
+        ]);
+        let result = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            opcodes,
+            SolverOptions::default(),
+        );
+        assert_eq!(
+            result,
+            Err(OpcodeResolutionError::InvalidPredicate(
+                "xor".to_owned(),
+                FieldElement::from(2_u128)
+            ))
+        );
+    }
+
+    #[test]
+    fn and_fallback_with_constant_input() {
+        let circuit = Circuit {
+            current_witness_index: 2,
+            opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+                lhs: FunctionInput::witness(Witness(1), 4),
+                rhs: FunctionInput::constant(FieldElement::from(0b1100_u128), 4),
+                output: Witness(2),
+                predicate: None,
+            })],
+            ..Circuit::default()
+        };
+        let circuit = FallbackTransformer::transform(
+            circuit,
+            |opcode| !matches!(opcode, Opcode::BlackBoxFuncCall(_)),
+            &CustomBlackBoxRegistry::default(),
+        )
+        .expect("AND has a fallback");
+
+        let mut witness_assignments =
+            BTreeMap::from([(Witness(1), FieldElement::from(0b1010_u128))]);
+        let mut blocks = Blocks::default();
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut blocks,
+            circuit.opcodes,
+            SolverOptions::default().with_tables(&circuit.tables),
+        )
+        .expect("should be solvable");
+
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        assert_eq!(witness_assignments[&Witness(2)], FieldElement::from(0b1000_u128));
+    }
+
+    // Replaces the opcodes of `circuit` which are not supported with their fallbacks
+    fn with_fallbacks(circuit: &Circuit, is_supported: IsOpcodeSupported) -> Circuit {
+        FallbackTransformer::transform(
+            circuit.clone(),
+            is_supported,
+            &CustomBlackBoxRegistry::default(),
+        )
+        .expect("unsupported opcodes should have a fallback")
+    }
+
+    // Solves `circuit` from `initial_witness`, returning the witness assignments if it is fully solved
+    fn solve_circuit(
+        circuit: &Circuit,
+        initial_witness: &BTreeMap<Witness, FieldElement>,
+    ) -> Result<BTreeMap<Witness, FieldElement>, OpcodeResolutionError> {
+        let mut witness_assignments = initial_witness.clone();
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes.clone(),
+            SolverOptions::default().with_tables(&circuit.tables),
+        )?;
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        Ok(witness_assignments)
+    }
+
+    #[test]
+    fn bounded_range_fallback_matches_native_solver() {
+        let bounded_range = |min: u128, max: u128| {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BoundedRange {
+                input: FunctionInput::witness(Witness(1), FieldElement::max_num_bits()),
+                min: FieldElement::from(min),
+                max: FieldElement::from(max),
+                predicate: Some(Expression::from(Witness(2))),
+            })
+        };
+        // The second range is one less than a power of two apart, so it needs a single range constraint
+        for (min, max) in [(10, 1000), (16, 271)] {
+            let circuit = Circuit {
+                current_witness_index: 2,
+                opcodes: vec![bounded_range(min, max)],
+                ..Circuit::default()
+            };
+            let fallback_circuit =
+                with_fallbacks(&circuit, |opcode| !matches!(opcode, Opcode::BlackBoxFuncCall(_)));
+
+            // Predicates must be 0 or 1
+            for (value, predicate) in
+                [(min, 1_u128), (max, 1), (min - 1, 1), (max + 1, 1), (0, 0), (min, 2)]
+            {
+                let initial_witness = BTreeMap::from([
+                    (Witness(1), FieldElement::from(value)),
+                    (Witness(2), FieldElement::from(predicate)),
+                ]);
+                let results: Vec<_> = [&circuit, &fallback_circuit]
+                    .into_iter()
+                    .map(|circuit| solve_circuit(circuit, &initial_witness).is_ok())
+                    .collect();
+                let in_range = predicate == 0 || (predicate == 1 && (min..=max).contains(&value));
+                assert_eq!(results, vec![in_range, in_range], "value {value} in [{min}, {max}]");
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_range_fallback_rejects_invalid_bounds() {
+        let max_distance = FieldElement::from(2_u128)
+            .pow(&FieldElement::from((FieldElement::max_num_bits() - 2) as u128));
+        for (min, max) in [
+            // The lower bound exceeds the upper bound
+            (FieldElement::from(5_u128), FieldElement::from(4_u128)),
+            // The bounds are too far apart
+            (FieldElement::zero(), max_distance),
+        ] {
+            let circuit = Circuit {
+                current_witness_index: 1,
+                opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BoundedRange {
+                    input: FunctionInput::witness(Witness(1), FieldElement::max_num_bits()),
+                    min,
+                    max,
+                    predicate: None,
+                })],
+                ..Circuit::default()
+            };
+            let result = FallbackTransformer::transform(
+                circuit,
+                |opcode| !matches!(opcode, Opcode::BlackBoxFuncCall(_)),
+                &CustomBlackBoxRegistry::default(),
+            );
+            assert!(matches!(
+                result,
+                Err(CompileError::InvalidBlackBoxCall(BlackBoxFunc::BoundedRange, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn radix_decomposition_constrains_digits() {
+        let (opcodes, digits, _) = stdlib::fallback::radix_decomposition(
+            Expression::from(Witness(1)),
+            10,
+            3,
+            stdlib::fallback::Endianness::Big,
+            2,
+        );
+        let solve = |opcodes: &[Opcode], witness_assignments: &mut BTreeMap<_, _>| {
+            pwg::solve(
+                &StubbedPwg,
+                witness_assignments,
+                &mut Blocks::default(),
+                opcodes.to_vec(),
+                SolverOptions::default(),
+            )
+        };
+
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(123u128))]);
+        assert!(solve(&opcodes, &mut witness_assignments).is_ok());
+        let values: Vec<_> =
+            digits.iter().map(|digit| witness_assignments[digit].to_u128()).collect();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(1234u128))]);
+        assert!(solve(&opcodes, &mut witness_assignments).is_err());
+
+        // 0 * 100 + 12 * 10 + 3 = 123, but 12 is not a digit in base 10
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(123u128))]);
+        for (digit, value) in digits.iter().zip([0u128, 12, 3]) {
+            witness_assignments.insert(*digit, FieldElement::from(value));
+        }
+        assert!(solve(&opcodes[1..], &mut witness_assignments).is_err());
+    }
+
+    #[test]
+    fn packs_hash_output_with_fallback() {
+        let circuit = Circuit {
+            current_witness_index: 2,
+            opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SHA256 {
+                inputs: vec![FunctionInput::witness(Witness(1), 8)],
+                var_message_size: None,
+                input_packing: HashInputPacking::Bytes,
+                output_mode: HashOutputMode::Field,
+                outputs: vec![Witness(2)],
+                predicate: None,
+            })],
+            ..Circuit::default()
+        };
+        // The backend only supports hash functions which output bytes
+        let circuit = FallbackTransformer::transform(
+            circuit,
+            |opcode| match opcode {
+                Opcode::BlackBoxFuncCall(call) => {
+                    matches!(call.hash_output_mode(), Some(HashOutputMode::Bytes))
+                }
+                _ => true,
+            },
+            &CustomBlackBoxRegistry::default(),
+        )
+        .expect("packed digests have a fallback");
+
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(97u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes,
+            SolverOptions::default().with_tables(&circuit.tables),
+        )
+        .expect("should be solvable");
+
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        let mut expected = BTreeMap::new();
+        pwg::hash::sha256(
+            &mut expected,
+            &[FunctionInput::constant(FieldElement::from(97u128), 8)],
+            None,
+            HashInputPacking::Bytes,
+            HashOutputMode::Field,
+            &[Witness(2)],
+        )
+        .unwrap();
+        assert_eq!(witness_assignments[&Witness(2)], expected[&Witness(2)]);
+    }
+
+    #[test]
+    fn rejects_variable_length_hash_without_backend_support() {
+        let circuit = Circuit {
+            current_witness_index: 3,
+            opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SHA256 {
+                inputs: vec![FunctionInput::witness(Witness(1), 8)],
+                var_message_size: Some(FunctionInput::witness(Witness(2), 32)),
+                input_packing: HashInputPacking::Bytes,
+                output_mode: HashOutputMode::Field,
+                outputs: vec![Witness(3)],
+                predicate: None,
+            })],
+            ..Circuit::default()
+        };
+        // The backend only supports hash functions over messages of a fixed size
+        let result = FallbackTransformer::transform(
+            circuit,
+            |opcode| match opcode {
+                Opcode::BlackBoxFuncCall(call) => call.var_message_size().is_none(),
+                _ => true,
+            },
+            &CustomBlackBoxRegistry::default(),
+        );
+
+        assert_eq!(result, Err(CompileError::UnsupportedVariableLengthHash(BlackBoxFunc::SHA256)));
+    }
+
+    #[test]
+    fn rejects_packed_hash_output_of_wrong_size() {
+        let circuit = Circuit {
+            current_witness_index: 4,
+            opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SHA256 {
+                inputs: vec![FunctionInput::witness(Witness(1), 8)],
+                var_message_size: None,
+                input_packing: HashInputPacking::Bytes,
+                output_mode: HashOutputMode::Limbs128,
+                outputs: vec![Witness(2), Witness(3), Witness(4)],
+                predicate: None,
+            })],
+            ..Circuit::default()
+        };
+        let result = FallbackTransformer::transform(
+            circuit,
+            |opcode| match opcode {
+                Opcode::BlackBoxFuncCall(call) => {
+                    matches!(call.hash_output_mode(), Some(HashOutputMode::Bytes))
+                }
+                _ => true,
+            },
+            &CustomBlackBoxRegistry::default(),
+        );
+
+        assert!(matches!(result, Err(CompileError::InvalidBlackBoxCall(BlackBoxFunc::SHA256, _))));
+    }
+
+    // A custom black box function which squares its input
+    fn square_registry() -> CustomBlackBoxRegistry {
+        fn solve_square(
+            initial_witness: &mut BTreeMap<Witness, FieldElement>,
+            inputs: &[FunctionInput],
+            outputs: &[Witness],
+        ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+            let input = pwg::input_to_value(initial_witness, &inputs[0])?;
+            initial_witness.insert(outputs[0], input * input);
+            Ok(OpcodeResolution::Solved)
+        }
+
+        fn square_fallback(
+            inputs: Vec<Expression>,
+            outputs: &[Witness],
+            num_witness: u32,
+        ) -> (u32, Vec<Opcode>) {
+            let mut square = &inputs[0] * &inputs[0];
+            square.push_addition_term(-FieldElement::one(), outputs[0]);
+            (num_witness, vec![Opcode::Arithmetic(square)])
+        }
+
+        let mut registry = CustomBlackBoxRegistry::new();
+        registry
+            .register(
+                FuncDefinition {
+                    name: "square",
+                    input_size: InputSize::Fixed(1),
+                    output_size: OutputSize(1),
+                },
+                Box::new(solve_square),
+                Some(Box::new(square_fallback)),
+            )
+            .expect("square is not a black box function");
+        registry
+    }
+
+    #[test]
+    fn registers_custom_black_boxes_with_state() {
+        let definition = |name| FuncDefinition {
+            name,
+            input_size: InputSize::Fixed(1),
+            output_size: OutputSize(1),
+        };
+        // The solver captures the factor to multiply its input with
+        let factor = FieldElement::from(5_u128);
+        let solve_scale = move |initial_witness: &mut BTreeMap<Witness, FieldElement>,
+                                inputs: &[FunctionInput],
+                                outputs: &[Witness]| {
+            let input = pwg::input_to_value(initial_witness, &inputs[0])?;
+            initial_witness.insert(outputs[0], input * factor);
+            Ok(OpcodeResolution::Solved)
+        };
+
+        let mut registry = CustomBlackBoxRegistry::new();
+        assert!(matches!(
+            registry.register(definition("scale"), Box::new(solve_scale), None),
+            Ok(None)
+        ));
+        assert!(matches!(
+            registry.register(definition("sha256"), Box::new(solve_scale), None),
+            Err(CustomBlackBoxError::ReservedName("sha256"))
+        ));
+        assert!(registry.get("sha256").is_none());
+
+        let opcodes = vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Custom {
+            name: "scale".into(),
+            inputs: vec![FunctionInput::witness(Witness(1), 8)],
+            outputs: vec![Witness(2)],
+            predicate: None,
+        })];
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(3u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            opcodes,
+            SolverOptions::default().with_custom_black_boxes(&registry),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved);
+        assert_eq!(witness_assignments[&Witness(2)], FieldElement::from(15u128));
+    }
+
+    #[test]
+    fn solves_custom_black_box() {
+        let circuit = Circuit {
+            current_witness_index: 2,
+            opcodes: vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Custom {
+                name: "square".into(),
+                inputs: vec![FunctionInput::witness(Witness(1), 8)],
+                outputs: vec![Witness(2)],
+                predicate: None,
+            })],
+            ..Circuit::default()
+        };
+        let registry = square_registry();
+
+        // Natively, using the registered solver
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(3u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes.clone(),
+            SolverOptions::default()
+                .with_custom_black_boxes(&registry)
+                .with_tables(&circuit.tables),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        assert_eq!(witness_assignments[&Witness(2)], FieldElement::from(9u128));
+
+        // Unregistered custom functions cannot be solved
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(3u128))]);
+        let err = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes.clone(),
+            SolverOptions::default().with_tables(&circuit.tables),
+        )
+        .unwrap_err();
+        assert_eq!(err, OpcodeResolutionError::UnsupportedCustomBlackBoxFunc("square".into()));
+
+        // Using the registered fallback
+        let circuit = FallbackTransformer::transform(
+            circuit,
+            |opcode| !matches!(opcode, Opcode::BlackBoxFuncCall(_)),
+            &registry,
+        )
+        .expect("square has a fallback");
+        assert!(circuit.opcodes.iter().all(Opcode::is_arithmetic));
+
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::from(3u128))]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes,
+            SolverOptions::default().with_tables(&circuit.tables),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        assert_eq!(witness_assignments[&Witness(2)], FieldElement::from(9u128));
+    }
+
+    #[test]
+    fn bigint_fallback_matches_native_solver() {
+        // The base field of secp256k1, as 4 limbs of 64 bits
+        let modulus = BigUint::parse_bytes(
+            b"fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            16,
+        )
+        .unwrap();
+        let limbs = |first_witness: u32| (first_witness..first_witness + 4).map(Witness);
+        let lhs: Vec<_> = limbs(1).collect();
+        let rhs: Vec<_> = limbs(5).collect();
+        let output: Vec<_> = limbs(9).collect();
+
+        let lhs_value = &modulus - 5_u32;
+        let rhs_value =
+            BigUint::parse_bytes(b"123456789abcdef0fedcba9876543210deadbeef", 16).unwrap();
+        let mut initial_witness = BTreeMap::new();
+        for (value, witnesses) in [(&lhs_value, &lhs), (&rhs_value, &rhs)] {
+            let mut digits = value.to_u64_digits();
+            digits.resize(witnesses.len(), 0);
+            for (limb, witness) in digits.into_iter().zip(witnesses) {
+                initial_witness.insert(*witness, FieldElement::from(limb as u128));
+            }
+        }
+
+        let ops = [
+            BigIntOp::Add { lhs: lhs.clone(), rhs: rhs.clone(), output: output.clone() },
+            BigIntOp::Mul { lhs: lhs.clone(), rhs: rhs.clone(), output: output.clone() },
+            BigIntOp::ModReduce {
+                input: lhs.iter().chain(&rhs).copied().collect(),
+                output: output.clone(),
+            },
+            BigIntOp::Compare { lhs: rhs.clone(), rhs: lhs.clone(), output: Witness(9) },
+        ];
+        for op in ops {
+            let circuit = Circuit {
+                current_witness_index: 12,
+                opcodes: vec![Opcode::BigInt(BigIntCall {
+                    modulus: modulus.to_bytes_be(),
+                    limb_bits: 64,
+                    op,
+                })],
+                ..Circuit::default()
+            };
+            let fallback_circuit =
+                with_fallbacks(&circuit, |opcode| !matches!(opcode, Opcode::BigInt(_)));
+            assert!(fallback_circuit
+                .opcodes
+                .iter()
+                .all(|opcode| !matches!(opcode, Opcode::BigInt(_))));
+
+            let outputs: Vec<_> = [&circuit, &fallback_circuit]
+                .into_iter()
+                .map(|circuit| {
+                    let witness_assignments =
+                        solve_circuit(circuit, &initial_witness).expect("should be solvable");
+                    output
+                        .iter()
+                        .filter_map(|w| witness_assignments.get(w).copied())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            assert_eq!(outputs[0], outputs[1]);
+        }
+    }
+
+    #[test]
+    fn bigint_fallback_range_constrains_operands() {
+        // 2^16 + 1 as limbs of 8 bits, where the limb 0x100 of `lhs` does not fit
+        let op = BigIntOp::Add {
+            lhs: vec![Witness(1)],
+            rhs: vec![Witness(2)],
+            output: vec![Witness(3), Witness(4), Witness(5)],
+        };
+        let circuit = Circuit {
+            current_witness_index: 5,
+            opcodes: vec![Opcode::BigInt(BigIntCall { modulus: vec![1, 0, 1], limb_bits: 8, op })],
+            ..Circuit::default()
+        };
+        let fallback_circuit =
+            with_fallbacks(&circuit, |opcode| !matches!(opcode, Opcode::BigInt(_)));
+
+        let initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(0x100_u128)),
+            (Witness(2), FieldElement::one()),
+        ]);
+        for circuit in [&circuit, &fallback_circuit] {
+            assert!(
+                solve_circuit(circuit, &initial_witness).is_err(),
+                "limbs which do not fit must not be accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn bigint_fallback_rejects_invalid_calls() {
+        let transform = |modulus: Vec<u8>, limb_bits: u32, output: Vec<Witness>| {
+            let op = BigIntOp::ModReduce { input: vec![Witness(1)], output };
+            let circuit = Circuit {
+                current_witness_index: 3,
+                opcodes: vec![Opcode::BigInt(BigIntCall { modulus, limb_bits, op })],
+                ..Circuit::default()
+            };
+            FallbackTransformer::transform(
+                circuit,
+                |opcode| !matches!(opcode, Opcode::BigInt(_)),
+                &CustomBlackBoxRegistry::default(),
+            )
+        };
+
+        for (modulus, limb_bits, output) in [
+            // The modulus is zero
+            (vec![0], 8, vec![Witness(2)]),
+            // The limbs have no bits
+            (vec![7], 0, vec![Witness(2)]),
+            // A single limb of 8 bits cannot hold a modulus of 9 bits
+            (vec![1, 1], 8, vec![Witness(2)]),
+        ] {
+            assert!(matches!(
+                transform(modulus, limb_bits, output),
+                Err(CompileError::InvalidBigIntCall(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn lookup_fallback_matches_native_solver() {
+        // XOR of two 2-bit integers
+        let xor_table = LookupTable {
+            name: "xor_2".to_owned(),
+            rows: (0..16_u128)
+                .map(|i| {
+                    let (a, b) = (i / 4, i % 4);
+                    vec![FieldElement::from(a), FieldElement::from(b), FieldElement::from(a ^ b)]
+                })
+                .collect(),
+        };
+        let circuit = Circuit {
+            current_witness_index: 3,
+            opcodes: vec![Opcode::Lookup(Lookup {
+                table: xor_table.name.clone(),
+                keys: vec![Expression::from(Witness(1)), Expression::from(Witness(2))],
+                values: vec![Expression::from(Witness(3))],
+            })],
+            tables: vec![xor_table],
+            ..Circuit::default()
+        };
+        let fallback_circuit =
+            with_fallbacks(&circuit, |opcode| !matches!(opcode, Opcode::Lookup(_)));
+        assert!(fallback_circuit.opcodes.iter().all(|opcode| !matches!(opcode, Opcode::Lookup(_))));
+        assert!(fallback_circuit.tables.is_empty(), "tables which are not looked up are removed");
+
+        for (a, b) in [(0_u128, 0_u128), (1, 2), (3, 1), (3, 3)] {
+            let initial_witness = BTreeMap::from([
+                (Witness(1), FieldElement::from(a)),
+                (Witness(2), FieldElement::from(b)),
+            ]);
+            for circuit in [&circuit, &fallback_circuit] {
+                let witness_assignments =
+                    solve_circuit(circuit, &initial_witness).expect("should be solvable");
+                assert_eq!(witness_assignments[&Witness(3)], FieldElement::from(a ^ b));
+            }
+        }
+
+        // Keys outside of the table cannot be solved by either circuit
+        let initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(4_u128)),
+            (Witness(2), FieldElement::zero()),
+        ]);
+        for circuit in [&circuit, &fallback_circuit] {
+            assert!(solve_circuit(circuit, &initial_witness).is_err());
+        }
+    }
+
+    #[test]
+    fn lookup_fallback_rejects_invalid_tables() {
+        let lookup = |keys: Vec<Expression>, values: Vec<Expression>| Lookup {
+            table: "square".to_owned(),
+            keys,
+            values,
+        };
+        let square_table = |rows: &[(u128, u128)]| LookupTable {
+            name: "square".to_owned(),
+            rows: rows
+                .iter()
+                .map(|(x, y)| vec![FieldElement::from(*x), FieldElement::from(*y)])
+                .collect(),
+        };
+        let (x, y) = (Expression::from(Witness(1)), Expression::from(Witness(2)));
+
+        for (lookup, table) in [
+            // No columns
+            (lookup(vec![], vec![]), square_table(&[(2, 4)])),
+            // Rows of the wrong width
+            (lookup(vec![x.clone()], vec![y.clone(), y.clone()]), square_table(&[(2, 4)])),
+            // Two rows with the same key
+            (lookup(vec![x], vec![y]), square_table(&[(2, 4), (2, 5)])),
+        ] {
+            let circuit = Circuit {
+                current_witness_index: 2,
+                opcodes: vec![Opcode::Lookup(lookup)],
+                tables: vec![table],
+                ..Circuit::default()
+            };
+            let result = FallbackTransformer::transform(
+                circuit,
+                |opcode| !matches!(opcode, Opcode::Lookup(_)),
+                &CustomBlackBoxRegistry::default(),
+            );
+            assert!(matches!(result, Err(CompileError::InvalidLookupTable(..))));
+        }
+    }
+
+    #[test]
+    fn memory_fallback_matches_native_solver() {
+        fn op(operation: u128, index: Expression, value: Witness) -> MemOp {
+            let operation = Expression::from_field(FieldElement::from(operation));
+            MemOp { operation, index, value: Expression::from(value) }
+        }
+        let constant = |value: u128| Expression::from_field(FieldElement::from(value));
+        let init: Vec<_> = (0..3).map(|i| op(1, constant(i), Witness(i as u32 + 1))).collect();
+
+        // The index of the last read is the value of the previous read
+        let ram = MemoryBlock {
+            id: BlockId(0),
+            len: 3,
+            trace: [
+                init.clone(),
+                vec![
+                    op(1, Expression::from(Witness(4)), Witness(5)),
+                    op(0, constant(0), Witness(6)),
+                    op(0, constant(2), Witness(7)),
+                    op(0, Expression::from(Witness(7)), Witness(8)),
+                ],
+            ]
+            .concat(),
+        };
+        let rom = MemoryBlock {
+            id: BlockId(1),
+            len: 3,
+            trace: [
+                init,
+                vec![
+                    op(0, constant(2), Witness(6)),
+                    op(0, Expression::from(Witness(6)), Witness(7)),
+                    op(0, constant(0), Witness(8)),
+                ],
+            ]
+            .concat(),
+        };
+        let initial_witness: BTreeMap<_, _> = [10_u128, 20, 1, 0, 7]
+            .into_iter()
+            .zip(1..)
+            .map(|(value, i)| (Witness(i), value.into()))
+            .collect();
+
+        for (opcode, expected) in [
+            (Opcode::RAM(ram.clone()), [7_u128, 1, 20]),
+            (Opcode::Block(ram), [7, 1, 20]),
+            (Opcode::ROM(rom), [1, 20, 10]),
+        ] {
+            let circuit =
+                Circuit { current_witness_index: 8, opcodes: vec![opcode], ..Circuit::default() };
+            let fallback_circuit =
+                with_fallbacks(&circuit, |opcode| matches!(opcode, Opcode::Arithmetic(_)));
+            assert!(fallback_circuit
+                .opcodes
+                .iter()
+                .all(|opcode| matches!(opcode, Opcode::Arithmetic(_) | Opcode::Directive(_))));
+
+            for circuit in [&circuit, &fallback_circuit] {
+                let witness_assignments =
+                    solve_circuit(circuit, &initial_witness).expect("should be solvable");
+                for (witness, value) in (6..).zip(expected) {
+                    assert_eq!(witness_assignments[&Witness(witness)], FieldElement::from(value));
+                }
+            }
+
+            // An inconsistent read does not satisfy the arithmetic constraints of the fallback
+            let mut witness_assignments =
+                solve_circuit(&fallback_circuit, &initial_witness).expect("should be solvable");
+            witness_assignments.insert(Witness(8), FieldElement::from(30_u128));
+            let constraints = Circuit {
+                opcodes: fallback_circuit
+                    .opcodes
+                    .into_iter()
+                    .filter(|opcode| matches!(opcode, Opcode::Arithmetic(_)))
+                    .collect(),
+                ..Circuit::default()
+            };
+            assert_eq!(
+                solve_circuit(&constraints, &witness_assignments),
+                Err(OpcodeResolutionError::UnsatisfiedConstrain)
+            );
+        }
+    }
+
+    #[test]
+    fn memory_fallback_accepts_non_linear_expressions() {
+        let square = |witness: u32| {
+            let mut expr = Expression::default();
+            expr.push_multiplication_term(FieldElement::one(), Witness(witness), Witness(witness));
+            expr
+        };
+        let write = |index: u128, value: Expression| MemOp {
+            operation: Expression::one(),
+            index: Expression::from_field(FieldElement::from(index)),
+            value,
+        };
+        // Reads w2 * w2 at index w3 * w3 = 1
+        let read = MemOp { operation: Expression::zero(), index: square(3), value: square(4) };
+        let block = MemoryBlock {
+            id: BlockId(0),
+            len: 2,
+            trace: vec![write(1, square(2)), write(0, square(1)), read],
+        };
+        let witness = |values: [u128; 4]| -> BTreeMap<_, _> {
+            (1..).zip(values).map(|(i, value)| (Witness(i), FieldElement::from(value))).collect()
+        };
+
+        for opcode in [Opcode::ROM(block.clone()), Opcode::RAM(block)] {
+            let circuit =
+                Circuit { current_witness_index: 4, opcodes: vec![opcode], ..Circuit::default() };
+            let fallback_circuit =
+                with_fallbacks(&circuit, |opcode| matches!(opcode, Opcode::Arithmetic(_)));
+            for circuit in [&circuit, &fallback_circuit] {
+                assert!(solve_circuit(circuit, &witness([2, 3, 1, 3])).is_ok());
+                assert!(solve_circuit(circuit, &witness([2, 3, 1, 2])).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_memory_blocks() {
+        let write = |index: u128| MemOp {
+            operation: Expression::one(),
+            index: Expression::from_field(FieldElement::from(index)),
+            value: Expression::from(Witness(1)),
+        };
+        let block = MemoryBlock { id: BlockId(2), len: 2, trace: vec![write(0), write(0)] };
+        let circuit = Circuit {
+            current_witness_index: 1,
+            opcodes: vec![Opcode::RAM(block)],
+            ..Circuit::default()
+        };
+        let result = FallbackTransformer::transform(
+            circuit,
+            |opcode| !matches!(opcode, Opcode::RAM(_)),
+            &CustomBlackBoxRegistry::default(),
+        );
+        assert_eq!(
+            result,
+            Err(CompileError::InvalidMemoryBlock {
+                block_id: 2,
+                position: 1,
+                reason: MemoryOpError::DuplicateInitialization(FieldElement::zero())
+            })
+        );
+    }
+
+    #[test]
+    fn memory_fallback_rejects_malformed_blocks() {
+        let op = |operation: u128, index: u128| MemOp {
+            operation: Expression::from_field(FieldElement::from(operation)),
+            index: Expression::from_field(FieldElement::from(index)),
+            value: Expression::from(Witness(1)),
+        };
+        let write_after_init =
+            MemoryBlock { id: BlockId(0), len: 1, trace: vec![op(1, 0), op(1, 0)] };
+        let empty = MemoryBlock { id: BlockId(1), len: 0, trace: vec![op(0, 0)] };
+        let duplicate_init =
+            MemoryBlock { id: BlockId(2), len: 2, trace: vec![op(1, 1), op(1, 1)] };
+
+        for (opcode, position, reason) in [
+            (Opcode::ROM(write_after_init), 1, MemoryOpError::WriteAfterInitialization),
+            (Opcode::RAM(empty), 0, MemoryOpError::EmptyMemoryAccess),
+            (
+                Opcode::ROM(duplicate_init),
+                1,
+                MemoryOpError::DuplicateInitialization(FieldElement::one()),
+            ),
+        ] {
+            let block_id = match &opcode {
+                Opcode::ROM(block) | Opcode::RAM(block) => block.id.0,
+                _ => unreachable!(),
+            };
+            let circuit =
+                Circuit { current_witness_index: 1, opcodes: vec![opcode], ..Circuit::default() };
+            let result = FallbackTransformer::transform(
+                circuit,
+                |opcode| matches!(opcode, Opcode::Arithmetic(_)),
+                &CustomBlackBoxRegistry::default(),
+            );
+            assert_eq!(
+                result,
+                Err(CompileError::InvalidMemoryBlock { block_id, position, reason })
+            );
+        }
+    }
+
+    #[test]
+    fn lowers_blocks_to_supported_memory_opcodes() {
+        let op = |operation: u128, index: u128, value: u32| MemOp {
+            operation: Expression::from_field(FieldElement::from(operation)),
+            index: Expression::from_field(FieldElement::from(index)),
+            value: Expression::from(Witness(value)),
+        };
+        let block = MemoryBlock { id: BlockId(0), len: 1, trace: vec![op(1, 0, 1), op(0, 0, 2)] };
+        let is_supported: IsOpcodeSupported = |opcode| {
+            matches!(
+                opcode,
+                Opcode::Arithmetic(_) | Opcode::MemoryInit { .. } | Opcode::MemoryOp { .. }
+            )
+        };
+
+        // RAM blocks are lowered to memory opcodes, but ROM blocks are replaced by arithmetic
+        // since memory opcodes could write to them after their initialization
+        let ram = Circuit {
+            current_witness_index: 2,
+            opcodes: vec![Opcode::RAM(block.clone())],
+            ..Circuit::default()
+        };
+        assert_eq!(with_fallbacks(&ram, is_supported).opcodes, block.to_memory_opcodes().unwrap());
+        let rom = Circuit { opcodes: vec![Opcode::ROM(block)], ..ram };
+        assert!(with_fallbacks(&rom, is_supported)
+            .opcodes
+            .iter()
+            .all(|opcode| matches!(opcode, Opcode::Arithmetic(_) | Opcode::Directive(_))));
+
+        // The operations on a memory initialized by the backend cannot be replaced by arithmetic
+        let circuit = Circuit {
+            current_witness_index: 2,
+            opcodes: vec![
+                Opcode::MemoryInit { id: BlockId(3), init: vec![Expression::from(Witness(1))] },
+                Opcode::MemoryOp { id: BlockId(3), op: op(0, 0, 2) },
+            ],
+            ..Circuit::default()
+        };
+        let result = FallbackTransformer::transform(
+            circuit.clone(),
+            |opcode| matches!(opcode, Opcode::Arithmetic(_) | Opcode::MemoryInit { .. }),
+            &CustomBlackBoxRegistry::default(),
+        );
+        assert_eq!(result, Err(CompileError::UnsupportedMemoryOp(3)));
+
+        // The operations on a memory replaced by arithmetic are replaced along with it
+        let fallback_circuit = with_fallbacks(&circuit, |opcode| {
+            matches!(opcode, Opcode::Arithmetic(_) | Opcode::MemoryOp { .. })
+        });
+        assert!(fallback_circuit
+            .opcodes
+            .iter()
+            .all(|opcode| matches!(opcode, Opcode::Arithmetic(_) | Opcode::Directive(_))));
+    }
+
+    #[test]
+    fn solves_memory_opcodes_in_order() {
+        let op = |operation: Expression, index: Expression, value: Expression| Opcode::MemoryOp {
+            id: BlockId(0),
+            op: MemOp { operation, index, value },
+        };
+        let mut index_expr = Expression::from(Witness(3));
+        index_expr.push_addition_term(-FieldElement::one(), Witness(5));
+        let circuit = Circuit {
+            current_witness_index: 6,
+            opcodes: vec![
+                Opcode::MemoryInit {
+                    id: BlockId(0),
+                    init: vec![Expression::from(Witness(1)), Expression::from(Witness(2))],
+                },
+                // The index of the write is only known after the last opcode is solved,
+                // so the reads which follow it have to wait
+                op(
+                    Expression::one(),
+                    Expression::from(Witness(3)),
+                    Expression::from_field(FieldElement::from(5_u128)),
+                ),
+                op(Expression::zero(), Expression::zero(), Expression::from(Witness(4))),
+                op(Expression::zero(), Expression::one(), Expression::from(Witness(6))),
+                Opcode::Arithmetic(index_expr),
+            ],
+            ..Circuit::default()
+        };
+        let fallback_circuit = FallbackTransformer::transform(
+            circuit.clone(),
+            |opcode| matches!(opcode, Opcode::Arithmetic(_)),
+            &CustomBlackBoxRegistry::default(),
+        )
+        .expect("memory opcodes have a fallback");
+
+        let initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(10_u128)),
+            (Witness(2), FieldElement::from(20_u128)),
+            (Witness(5), FieldElement::zero()),
+        ]);
+        for circuit in [circuit, fallback_circuit] {
+            let mut witness_assignments = initial_witness.clone();
+            let solver_status = pwg::solve(
+                &StubbedPwg,
+                &mut witness_assignments,
+                &mut Blocks::default(),
+                circuit.opcodes,
+                SolverOptions::default().with_tables(&circuit.tables),
+            )
+            .expect("should be solvable");
+            assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved);
+            assert_eq!(witness_assignments[&Witness(4)], FieldElement::from(5_u128));
+            assert_eq!(witness_assignments[&Witness(6)], FieldElement::from(20_u128));
+        }
+    }
+
+    #[test]
+    fn memory_opcodes_fallback_accepts_non_linear_expressions() {
+        let square = |witness: u32| {
+            let mut expr = Expression::default();
+            expr.push_multiplication_term(FieldElement::one(), Witness(witness), Witness(witness));
+            expr
+        };
+        let op = |operation: Expression, index: Expression, value: Expression| Opcode::MemoryOp {
+            id: BlockId(0),
+            op: MemOp { operation, index, value },
+        };
+        let circuit = Circuit {
+            current_witness_index: 5,
+            opcodes: vec![
+                Opcode::MemoryInit { id: BlockId(0), init: vec![square(1), square(2)] },
+                op(Expression::one(), Expression::zero(), square(3)),
+                op(Expression::zero(), Expression::zero(), Expression::from(Witness(4))),
+                op(Expression::zero(), Expression::one(), Expression::from(Witness(5))),
+            ],
+            ..Circuit::default()
+        };
+        let fallback_circuit =
+            with_fallbacks(&circuit, |opcode| matches!(opcode, Opcode::Arithmetic(_)));
+
+        let initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(2_u128)),
+            (Witness(2), FieldElement::from(3_u128)),
+            (Witness(3), FieldElement::from(4_u128)),
+        ]);
+        for circuit in [&circuit, &fallback_circuit] {
+            let witness_assignments =
+                solve_circuit(circuit, &initial_witness).expect("should be solvable");
+            assert_eq!(witness_assignments[&Witness(4)], FieldElement::from(16_u128));
+            assert_eq!(witness_assignments[&Witness(5)], FieldElement::from(9_u128));
+        }
+    }
+
+    #[test]
+    fn sends_logs_to_the_sink() {
+        // The logged witness is only known after the opcode following the log is solved
+        let mut expr = Expression::from(Witness(2));
+        expr.push_addition_term(-FieldElement::one(), Witness(1));
+        let opcodes = vec![
+            Opcode::Directive(Directive::Log(LogInfo::FinalizedOutput("start".to_owned()))),
+            Opcode::Directive(Directive::Log(LogInfo::WitnessOutput(vec![Witness(2)]))),
+            Opcode::Arithmetic(expr),
+        ];
+
+        let mut events = Vec::new();
+        let mut witness_assignments = BTreeMap::from([(Witness(1), FieldElement::one())]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            opcodes,
+            SolverOptions::default().with_log_sink(&mut |event| events.push(event)),
+        )
+        .expect("should be solvable");
+        assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved);
+        assert_eq!(
+            events,
+            vec![
+                LogEvent { opcode_index: 0, output: LogOutput::String("start".to_owned()) },
+                LogEvent { opcode_index: 1, output: LogOutput::Values(vec![FieldElement::one()]) },
+            ]
+        );
+    }
+
+    #[test]
+    fn solves_recursive_aggregation_with_backend() {
+        let aggregation = Opcode::RecursiveAggregation(RecursiveAggregation {
+            verification_key: vec![Witness(1)],
+            proof: vec![Witness(2), Witness(3)],
+            public_inputs: vec![Witness(1)],
+            input_aggregation_object: Vec::new(),
+            output_aggregation_object: vec![Witness(4), Witness(5)],
+        });
+        // The proof depends on an opcode placed after the aggregation
+        let mut proof_expr = Expression::from(Witness(1));
+        proof_expr.push_addition_term(-FieldElement::one(), Witness(3));
+        let circuit = Circuit {
+            current_witness_index: 5,
+            opcodes: vec![aggregation.clone(), Opcode::Arithmetic(proof_expr)],
+            ..Circuit::default()
+        };
+
+        // Aggregation is left to the backend, even when it is reported as unsupported
+        let circuit = FallbackTransformer::transform(
+            circuit,
+            |opcode| !matches!(opcode, Opcode::RecursiveAggregation(_)),
+            &CustomBlackBoxRegistry::default(),
+        )
+        .expect("recursive aggregation is passed through");
+        assert_eq!(circuit.opcodes[0], aggregation);
+
+        let mut witness_assignments = BTreeMap::from([
+            (Witness(1), FieldElement::from(3_u128)),
+            (Witness(2), FieldElement::from(4_u128)),
+        ]);
+        let solver_status = pwg::solve(
+            &StubbedPwg,
+            &mut witness_assignments,
+            &mut Blocks::default(),
+            circuit.opcodes,
+            SolverOptions::default().with_tables(&circuit.tables),
+        )
+        .expect("should be solvable");
         assert_eq!(solver_status, PartialWitnessGeneratorStatus::Solved, "should be fully solved");
+        assert_eq!(witness_assignments[&Witness(4)], FieldElement::from(7_u128));
+        assert_eq!(witness_assignments[&Witness(5)], FieldElement::from(7_u128));
     }
 }
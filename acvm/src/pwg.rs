@@ -1,28 +1,43 @@
 // Re-usable methods that backends can use to implement their PWG
 
-use crate::{OpcodeNotSolvable, OpcodeResolutionError, PartialWitnessGenerator};
+use crate::{
+    custom_black_box::CustomBlackBoxRegistry, OpcodeNotSolvable, OpcodeResolutionError,
+    PartialWitnessGenerator,
+};
 use acir::{
-    circuit::opcodes::{Opcode, OracleData},
+    circuit::{
+        directives::Directive,
+        opcodes::{ConstantOrWitness, FunctionInput, LookupTable, Opcode, OracleData},
+    },
     native_types::{Expression, Witness},
     FieldElement,
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 
 use self::{
-    arithmetic::ArithmeticSolver, block::Blocks, directives::solve_directives, oracle::OracleSolver,
+    arithmetic::ArithmeticSolver,
+    block::Blocks,
+    directives::solve_directives,
+    log::{LogSink, StdoutLogSink},
+    oracle::{NoOracleResolver, OracleResolver, OracleSolver},
 };
 
 // arithmetic
 pub mod arithmetic;
 // Directives
+pub mod bigint;
 pub mod directives;
 // black box functions
 mod blackbox;
 pub mod block;
+pub mod embedded_curve;
 pub mod hash;
+pub mod log;
 pub mod logic;
+pub mod lookup;
 pub mod oracle;
 pub mod range;
+mod recursion;
 pub mod signature;
 pub mod sorting;
 
@@ -33,8 +48,10 @@ pub enum PartialWitnessGeneratorStatus {
 
     /// The `PartialWitnessGenerator` has encountered a request for [oracle data][Opcode::Oracle].
     ///
+    /// These are the oracles which are unknown to the [`OracleResolver`] given to the solver.
     /// The caller must resolve these opcodes externally and insert the results into the intermediate witness.
     /// Once this is done, the `PartialWitnessGenerator` can be restarted to solve the remaining opcodes.
+    /// The [LogEvent][log::LogEvent]s of the restarted solver index the directives within `unsolved_opcodes`.
     RequiresOracleData { required_oracle_data: Vec<OracleData>, unsolved_opcodes: Vec<Opcode> },
 }
 
@@ -48,32 +65,104 @@ pub enum OpcodeResolution {
     InProgress,
 }
 
+/// The hooks and data used by [solve] besides the backend, each of which has a default.
+///
+/// The options are set with the `with_*` methods, so that new options do not break the callers of [solve].
+#[derive(Default)]
+pub struct SolverOptions<'a> {
+    custom_black_boxes: Option<&'a CustomBlackBoxRegistry>,
+    log_sink: Option<&'a mut dyn LogSink>,
+    oracle_resolver: Option<&'a mut dyn OracleResolver>,
+    tables: &'a [LookupTable],
+}
+
+impl<'a> SolverOptions<'a> {
+    /// Solves the calls to the custom black box functions of `custom_black_boxes`.
+    /// By default, no custom black box function is known to the solver.
+    pub fn with_custom_black_boxes(
+        mut self,
+        custom_black_boxes: &'a CustomBlackBoxRegistry,
+    ) -> Self {
+        self.custom_black_boxes = Some(custom_black_boxes);
+        self
+    }
+
+    /// Sends the logs of the circuit to `log_sink` instead of printing them with [StdoutLogSink].
+    pub fn with_log_sink(mut self, log_sink: &'a mut dyn LogSink) -> Self {
+        self.log_sink = Some(log_sink);
+        self
+    }
+
+    /// Resolves the oracles known to `oracle_resolver` while solving.
+    /// By default, all oracles are returned to the caller with [NoOracleResolver].
+    pub fn with_oracle_resolver(mut self, oracle_resolver: &'a mut dyn OracleResolver) -> Self {
+        self.oracle_resolver = Some(oracle_resolver);
+        self
+    }
+
+    /// Solves the lookups against `tables`, which are usually the tables of the circuit.
+    /// By default, there are no tables to look up.
+    pub fn with_tables(mut self, tables: &'a [LookupTable]) -> Self {
+        self.tables = tables;
+        self
+    }
+}
+
 pub fn solve(
     backend: &impl PartialWitnessGenerator,
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
     blocks: &mut Blocks,
-    mut opcode_to_solve: Vec<Opcode>,
+    opcodes: Vec<Opcode>,
+    options: SolverOptions,
 ) -> Result<PartialWitnessGeneratorStatus, OpcodeResolutionError> {
-    let mut unresolved_opcodes: Vec<Opcode> = Vec::new();
+    let default_black_boxes = CustomBlackBoxRegistry::default();
+    let custom_black_boxes = options.custom_black_boxes.unwrap_or(&default_black_boxes);
+    let (mut stdout_log_sink, mut no_oracle_resolver) = (StdoutLogSink, NoOracleResolver);
+    let log_sink = options.log_sink.unwrap_or(&mut stdout_log_sink);
+    let oracle_resolver = options.oracle_resolver.unwrap_or(&mut no_oracle_resolver);
+    let tables = options.tables;
+
+    // The opcodes keep their index in `opcodes`, which is reported by their logs
+    let mut opcode_to_solve: Vec<(usize, Opcode)> = opcodes.into_iter().enumerate().collect();
+    let mut unresolved_opcodes: Vec<(usize, Opcode)> = Vec::new();
     let mut unresolved_oracles: Vec<OracleData> = Vec::new();
     while !opcode_to_solve.is_empty() || !unresolved_oracles.is_empty() {
         unresolved_opcodes.clear();
         let mut stalled = true;
         let mut opcode_not_solvable = None;
-        for opcode in &opcode_to_solve {
+        // The memory operations on a block must be solved in order,
+        // so they wait for any operation on the same block which is stalled in this pass
+        let mut stalled_memories = HashSet::new();
+        for (opcode_index, opcode) in &opcode_to_solve {
             let mut solved_oracle_data = None;
             let resolution = match opcode {
                 Opcode::Arithmetic(expr) => ArithmeticSolver::solve(initial_witness, expr),
                 Opcode::BlackBoxFuncCall(bb_func) => {
-                    blackbox::solve(backend, initial_witness, bb_func)
+                    blackbox::solve(backend, custom_black_boxes, initial_witness, bb_func)
+                }
+                Opcode::Directive(Directive::Log(info)) => {
+                    log::solve_log(initial_witness, info, *opcode_index, log_sink)
                 }
                 Opcode::Directive(directive) => solve_directives(initial_witness, directive),
-                Opcode::Block(block) | Opcode::ROM(block) | Opcode::RAM(block) => {
-                    blocks.solve(block.id, &block.trace, initial_witness)
+                Opcode::Block(block) => blocks.solve(block, initial_witness),
+                Opcode::ROM(block) => blocks.solve_rom(block, initial_witness),
+                Opcode::RAM(block) => blocks.solve_ram(block, initial_witness),
+                Opcode::BigInt(call) => bigint::solve_bigint(initial_witness, call),
+                Opcode::RecursiveAggregation(aggregation) => {
+                    recursion::solve(backend, initial_witness, aggregation)
                 }
+                Opcode::Lookup(lookup) => lookup::solve_lookup(initial_witness, tables, lookup),
+                Opcode::MemoryInit { id, .. } | Opcode::MemoryOp { id, .. }
+                    if stalled_memories.contains(id) =>
+                {
+                    Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::PendingMemoryOperation(id.0)))
+                }
+                Opcode::MemoryInit { id, init } => blocks.init_memory(*id, init, initial_witness),
+                Opcode::MemoryOp { id, op } => blocks.solve_memory_op(*id, op, initial_witness),
                 Opcode::Oracle(data) => {
                     let mut data_clone = data.clone();
-                    let result = OracleSolver::solve(initial_witness, &mut data_clone)?;
+                    let result =
+                        OracleSolver::solve(initial_witness, &mut data_clone, oracle_resolver)?;
                     solved_oracle_data = Some(data_clone);
                     Ok(result)
                 }
@@ -88,10 +177,13 @@ pub fn solve(
                     if let Some(oracle) = solved_oracle_data {
                         unresolved_oracles.push(oracle);
                     } else {
-                        unresolved_opcodes.push(opcode.clone());
+                        unresolved_opcodes.push((*opcode_index, opcode.clone()));
                     }
                 }
                 Ok(OpcodeResolution::Stalled(not_solvable)) => {
+                    if let Opcode::MemoryInit { id, .. } | Opcode::MemoryOp { id, .. } = opcode {
+                        stalled_memories.insert(*id);
+                    }
                     if opcode_not_solvable.is_none() {
                         // we keep track of the first unsolvable opcode
                         opcode_not_solvable = Some(not_solvable);
@@ -99,10 +191,11 @@ pub fn solve(
                     // We push those opcodes not solvable to the back as
                     // it could be because the opcodes are out of order, i.e. this assignment
                     // relies on a later opcodes' results
-                    unresolved_opcodes.push(match solved_oracle_data {
+                    let opcode = match solved_oracle_data {
                         Some(oracle_data) => Opcode::Oracle(oracle_data),
                         None => opcode.clone(),
-                    });
+                    };
+                    unresolved_opcodes.push((*opcode_index, opcode));
                 }
                 Err(OpcodeResolutionError::OpcodeNotSolvable(_)) => {
                     unreachable!("ICE - Result should have been converted to GateResolution")
@@ -114,7 +207,10 @@ pub fn solve(
         if !unresolved_oracles.is_empty() {
             return Ok(PartialWitnessGeneratorStatus::RequiresOracleData {
                 required_oracle_data: unresolved_oracles,
-                unsolved_opcodes: unresolved_opcodes,
+                unsolved_opcodes: unresolved_opcodes
+                    .into_iter()
+                    .map(|(_, opcode)| opcode)
+                    .collect(),
             });
         }
         // We are stalled because of an opcode being bad
@@ -142,6 +238,18 @@ pub fn witness_to_value(
     }
 }
 
+// Returns the concrete value for a black box function input,
+// which is either a constant or the value assigned to its witness.
+pub fn input_to_value(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    input: &FunctionInput,
+) -> Result<FieldElement, OpcodeResolutionError> {
+    match input.input {
+        ConstantOrWitness::Constant(value) => Ok(value),
+        ConstantOrWitness::Witness(witness) => witness_to_value(initial_witness, witness).copied(),
+    }
+}
+
 // TODO: There is an issue open to decide on whether we need to get values from Expressions
 // TODO versus just getting values from Witness
 pub fn get_value(
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use acir::{
+    circuit::opcodes::{BigIntCall, BigIntOp},
+    native_types::{Expression, Witness},
+    FieldElement,
+};
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{One, Zero};
+
+use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+use super::{get_value, insert_value, witness_to_value};
+
+/// Attempts to solve the [`BigIntCall`] opcode `call`.
+///
+/// Returns `Err(OpcodeResolutionError)` if the modulus is zero, the limbs do not fit in a field element, a limb of the operands does not fit in its bits
+/// or the result does not fit in the output limbs.
+pub fn solve_bigint(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    call: &BigIntCall,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    match solve_bigint_internal(initial_witness, call) {
+        Ok(_) => Ok(OpcodeResolution::Solved),
+        Err(OpcodeResolutionError::OpcodeNotSolvable(unsolved)) => {
+            Ok(OpcodeResolution::Stalled(unsolved))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn solve_bigint_internal(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    call: &BigIntCall,
+) -> Result<(), OpcodeResolutionError> {
+    let BigIntCall { modulus, limb_bits, op } = call;
+    let modulus = BigUint::from_bytes_be(modulus);
+    if modulus.is_zero() || *limb_bits == 0 {
+        return Err(OpcodeResolutionError::BigIntFailed(
+            op.name().to_owned(),
+            "the modulus and the number of bits per limb must be non-zero".to_owned(),
+        ));
+    }
+    if *limb_bits >= FieldElement::max_num_bits() {
+        return Err(OpcodeResolutionError::BigIntFailed(
+            op.name().to_owned(),
+            format!("limbs of {limb_bits} bits are too large for the field"),
+        ));
+    }
+
+    let read = |limbs: &[Witness]| -> Result<BigUint, OpcodeResolutionError> {
+        let values = limbs
+            .iter()
+            .map(|limb| witness_to_value(initial_witness, *limb).copied())
+            .collect::<Result<Vec<_>, _>>()?;
+        from_limbs(&values, *limb_bits).ok_or_else(|| {
+            OpcodeResolutionError::BigIntFailed(
+                op.name().to_owned(),
+                format!("a limb of the operands does not fit in {limb_bits} bits"),
+            )
+        })
+    };
+
+    let (result, output) = match op {
+        BigIntOp::Add { lhs, rhs, output } => ((read(lhs)? + read(rhs)?) % &modulus, output),
+        BigIntOp::Mul { lhs, rhs, output } => ((read(lhs)? * read(rhs)?) % &modulus, output),
+        BigIntOp::ModReduce { input, output } => (read(input)? % &modulus, output),
+        BigIntOp::Compare { lhs, rhs, output } => {
+            let is_less_than = read(lhs)? < read(rhs)?;
+            return insert_value(output, FieldElement::from(is_less_than), initial_witness);
+        }
+    };
+
+    let limbs = to_limbs(&result, *limb_bits, output.len()).ok_or_else(|| {
+        OpcodeResolutionError::BigIntFailed(
+            op.name().to_owned(),
+            format!("the result does not fit in {} limbs of {limb_bits} bits", output.len()),
+        )
+    })?;
+    for (witness, limb) in output.iter().zip(limbs) {
+        insert_value(witness, limb, initial_witness)?;
+    }
+    Ok(())
+}
+
+/// Solves [`acir::circuit::directives::Directive::BigIntQuotient`]
+pub(super) fn solve_bigint_quotient(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    a: &[Expression],
+    limb_bits: u32,
+    modulus: &[u8],
+    q: &[Witness],
+    r: &[Witness],
+) -> Result<(), OpcodeResolutionError> {
+    let failure = |reason: &str| {
+        OpcodeResolutionError::BigIntFailed("bigint_quotient".to_owned(), reason.to_owned())
+    };
+
+    let modulus = BigUint::from_bytes_be(modulus);
+    if modulus.is_zero() || limb_bits == 0 {
+        return Err(failure("the modulus and the number of bits per limb must be non-zero"));
+    }
+    if limb_bits >= FieldElement::max_num_bits() {
+        return Err(failure(&format!("limbs of {limb_bits} bits are too large for the field")));
+    }
+
+    let mut value = BigInt::zero();
+    for column in a.iter().rev() {
+        let column = to_signed(get_value(column, initial_witness)?);
+        value = (value << limb_bits) + column;
+    }
+    let value = value.to_biguint().ok_or_else(|| failure("the dividend is negative"))?;
+
+    let quotient = to_limbs(&(&value / &modulus), limb_bits, q.len())
+        .ok_or_else(|| failure("the quotient does not fit in its limbs"))?;
+    let rest = to_limbs(&(&value % &modulus), limb_bits, r.len())
+        .ok_or_else(|| failure("the rest does not fit in its limbs"))?;
+    for (witness, limb) in q.iter().chain(r).zip(quotient.into_iter().chain(rest)) {
+        insert_value(witness, limb, initial_witness)?;
+    }
+    Ok(())
+}
+
+// Interprets field elements in the upper half of the field as negative integers
+fn to_signed(value: FieldElement) -> BigInt {
+    let field_modulus = FieldElement::modulus();
+    let value = BigUint::from_bytes_be(&value.to_be_bytes());
+    if value > &field_modulus >> 1 {
+        BigInt::from_biguint(Sign::Minus, field_modulus - value)
+    } else {
+        BigInt::from_biguint(Sign::Plus, value)
+    }
+}
+
+// Joins little endian `limbs` into a single value, returning `None` if a limb does not fit in `limb_bits` bits
+fn from_limbs(limbs: &[FieldElement], limb_bits: u32) -> Option<BigUint> {
+    limbs.iter().rev().try_fold(BigUint::zero(), |value, limb| {
+        (limb.num_bits() <= limb_bits)
+            .then(|| (value << limb_bits) + BigUint::from_bytes_be(&limb.to_be_bytes()))
+    })
+}
+
+// Splits `value` into `num_limbs` limbs, returning `None` if it does not fit
+fn to_limbs(value: &BigUint, limb_bits: u32, num_limbs: usize) -> Option<Vec<FieldElement>> {
+    let mask = (BigUint::one() << limb_bits) - 1_u32;
+    let limbs = (0..num_limbs)
+        .map(|i| {
+            let limb = (value >> (i as u32 * limb_bits)) & &mask;
+            FieldElement::from_be_bytes_reduce(&limb.to_bytes_be())
+        })
+        .collect();
+    (value.bits() <= num_limbs as u64 * limb_bits as u64).then_some(limbs)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{
+        circuit::opcodes::{BigIntCall, BigIntOp},
+        native_types::{Expression, Witness},
+        FieldElement,
+    };
+
+    use super::{solve_bigint, solve_bigint_quotient};
+    use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+    fn call(op: BigIntOp) -> BigIntCall {
+        // 2^16 + 1 split into 8 bit limbs
+        BigIntCall { modulus: vec![1, 0, 1], limb_bits: 8, op }
+    }
+
+    fn limbs(first_witness: u32, num_limbs: u32) -> Vec<Witness> {
+        (first_witness..first_witness + num_limbs).map(Witness).collect()
+    }
+
+    fn assign(values: &[(u32, u128)]) -> BTreeMap<Witness, FieldElement> {
+        values
+            .iter()
+            .map(|(witness, value)| (Witness(*witness), FieldElement::from(*value)))
+            .collect()
+    }
+
+    fn read(initial_witness: &BTreeMap<Witness, FieldElement>, limbs: &[Witness]) -> u128 {
+        limbs.iter().rev().fold(0, |value, limb| (value << 8) + initial_witness[limb].to_u128())
+    }
+
+    #[test]
+    fn solves_modular_arithmetic() {
+        // lhs = 0xff00 and rhs = 0x1234
+        let values = [(1, 0x00), (2, 0xff), (3, 0x34), (4, 0x12)];
+
+        let mut initial_witness = assign(&values);
+        let add = call(BigIntOp::Add { lhs: limbs(1, 2), rhs: limbs(3, 2), output: limbs(5, 3) });
+        assert_eq!(solve_bigint(&mut initial_witness, &add), Ok(OpcodeResolution::Solved));
+        assert_eq!(read(&initial_witness, &limbs(5, 3)), (0xff00 + 0x1234) % 0x10001);
+
+        let mut initial_witness = assign(&values);
+        let mul = call(BigIntOp::Mul { lhs: limbs(1, 2), rhs: limbs(3, 2), output: limbs(5, 3) });
+        assert_eq!(solve_bigint(&mut initial_witness, &mul), Ok(OpcodeResolution::Solved));
+        assert_eq!(read(&initial_witness, &limbs(5, 3)), (0xff00 * 0x1234) % 0x10001);
+
+        let mut initial_witness = assign(&values);
+        let reduce = call(BigIntOp::ModReduce { input: limbs(1, 4), output: limbs(5, 3) });
+        assert_eq!(solve_bigint(&mut initial_witness, &reduce), Ok(OpcodeResolution::Solved));
+        assert_eq!(read(&initial_witness, &limbs(5, 3)), 0x1234ff00 % 0x10001);
+
+        let mut initial_witness = assign(&values);
+        let compare =
+            call(BigIntOp::Compare { lhs: limbs(3, 2), rhs: limbs(1, 2), output: Witness(5) });
+        assert_eq!(solve_bigint(&mut initial_witness, &compare), Ok(OpcodeResolution::Solved));
+        assert_eq!(initial_witness[&Witness(5)], FieldElement::one());
+    }
+
+    #[test]
+    fn rejects_results_which_do_not_fit() {
+        let mut initial_witness = assign(&[(1, 0xff), (2, 0xff)]);
+        // The modulus has 17 bits, so the result can need 3 limbs
+        let add = call(BigIntOp::Add { lhs: limbs(1, 1), rhs: limbs(2, 1), output: limbs(3, 1) });
+        assert!(matches!(
+            solve_bigint(&mut initial_witness, &add),
+            Err(OpcodeResolutionError::BigIntFailed(..))
+        ));
+    }
+
+    #[test]
+    fn rejects_limbs_which_do_not_fit() {
+        // The limb 0x100 does not fit in 8 bits
+        let mut initial_witness = assign(&[(1, 0x100), (2, 0x01)]);
+        let add = call(BigIntOp::Add { lhs: limbs(1, 1), rhs: limbs(2, 1), output: limbs(3, 3) });
+        assert!(matches!(
+            solve_bigint(&mut initial_witness, &add),
+            Err(OpcodeResolutionError::BigIntFailed(..))
+        ));
+    }
+
+    #[test]
+    fn rejects_limbs_too_large_for_the_field() {
+        let limb_bits = FieldElement::max_num_bits();
+        let mut initial_witness = assign(&[(1, 1), (2, 1)]);
+        let add = BigIntCall {
+            limb_bits,
+            ..call(BigIntOp::Add { lhs: limbs(1, 1), rhs: limbs(2, 1), output: limbs(3, 1) })
+        };
+        assert!(matches!(
+            solve_bigint(&mut initial_witness, &add),
+            Err(OpcodeResolutionError::BigIntFailed(..))
+        ));
+
+        let a = [Expression::from(Witness(1))];
+        let (q, r) = (limbs(3, 1), limbs(4, 1));
+        assert!(matches!(
+            solve_bigint_quotient(&mut initial_witness, &a, limb_bits, &[1, 0, 1], &q, &r),
+            Err(OpcodeResolutionError::BigIntFailed(..))
+        ));
+    }
+}
@@ -3,12 +3,15 @@ use std::collections::BTreeMap;
 use acir::{
     circuit::opcodes::{BlackBoxFuncCall, FunctionInput},
     native_types::Witness,
-    BlackBoxFunc, FieldElement,
+    FieldElement,
 };
 
-use crate::{OpcodeNotSolvable, OpcodeResolutionError, PartialWitnessGenerator};
+use crate::{
+    custom_black_box::CustomBlackBoxRegistry, OpcodeNotSolvable, OpcodeResolutionError,
+    PartialWitnessGenerator,
+};
 
-use super::OpcodeResolution;
+use super::{get_value, insert_value, OpcodeResolution};
 
 /// Check if all of the inputs to the function have assignments
 ///
@@ -17,78 +20,204 @@ fn first_missing_assignment(
     witness_assignments: &BTreeMap<Witness, FieldElement>,
     inputs: &[FunctionInput],
 ) -> Option<Witness> {
-    inputs.iter().find_map(|input| {
-        if witness_assignments.contains_key(&input.witness) {
-            None
-        } else {
-            Some(input.witness)
-        }
+    inputs.iter().filter_map(FunctionInput::to_witness).find(|witness| {
+        // Constant inputs never need an assignment
+        !witness_assignments.contains_key(witness)
     })
 }
 
-/// Check if all of the inputs to the function have assignments
-fn contains_all_inputs(
-    witness_assignments: &BTreeMap<Witness, FieldElement>,
-    inputs: &[FunctionInput],
-) -> bool {
-    inputs.iter().all(|input| witness_assignments.contains_key(&input.witness))
-}
-
 pub(crate) fn solve(
     backend: &impl PartialWitnessGenerator,
+    custom_black_boxes: &CustomBlackBoxRegistry,
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
     bb_func: &BlackBoxFuncCall,
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    match bb_func {
-        BlackBoxFuncCall { inputs, .. } if !contains_all_inputs(initial_witness, inputs) => {
-            if let Some(unassigned_witness) = first_missing_assignment(initial_witness, inputs) {
-                Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(
-                    unassigned_witness.0,
-                )))
-            } else {
-                // This only exists because Rust won't let us bind in a pattern guard.
-                // See https://github.com/rust-lang/rust/issues/51114
-                unreachable!("Only reachable if the blackbox is stalled")
+    if let Some(predicate) = bb_func.predicate() {
+        let predicate_value = match get_value(predicate, initial_witness) {
+            Ok(value) => value,
+            Err(OpcodeResolutionError::OpcodeNotSolvable(unsolved)) => {
+                return Ok(OpcodeResolution::Stalled(unsolved))
+            }
+            Err(err) => return Err(err),
+        };
+        // If the predicate is zero then the call is skipped and its outputs default to zero
+        if predicate_value.is_zero() {
+            for output in &bb_func.get_outputs_vec() {
+                insert_value(output, FieldElement::zero(), initial_witness)?;
             }
+            return Ok(OpcodeResolution::Solved);
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::AES, inputs, outputs } => {
+        if !predicate_value.is_one() {
+            return Err(OpcodeResolutionError::InvalidPredicate(
+                bb_func.name().to_owned(),
+                predicate_value,
+            ));
+        }
+    }
+
+    let mut inputs = bb_func.get_inputs_vec();
+    inputs.extend(bb_func.var_message_size().cloned());
+    if let Some(unassigned_witness) = first_missing_assignment(initial_witness, &inputs) {
+        return Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(
+            unassigned_witness.0,
+        )));
+    }
+
+    match bb_func {
+        BlackBoxFuncCall::AES { inputs, outputs, .. } => {
             backend.aes(initial_witness, inputs, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::AND, inputs, outputs } => {
-            backend.and(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::AND { lhs, rhs, output, .. } => {
+            backend.and(initial_witness, lhs, rhs, output)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::XOR, inputs, outputs } => {
-            backend.xor(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::XOR { lhs, rhs, output, .. } => {
+            backend.xor(initial_witness, lhs, rhs, output)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::RANGE, inputs, outputs } => {
-            backend.range(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::RANGE { input, .. } => backend.range(initial_witness, input),
+        BlackBoxFuncCall::BoundedRange { input, min, max, .. } => {
+            backend.bounded_range(initial_witness, input, min, max)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::SHA256, inputs, outputs } => {
-            backend.sha256(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::SHA256 {
+            inputs,
+            var_message_size,
+            input_packing,
+            output_mode,
+            outputs,
+            ..
+        } => backend.sha256(
+            initial_witness,
+            inputs,
+            var_message_size.as_ref(),
+            *input_packing,
+            *output_mode,
+            outputs,
+        ),
+        BlackBoxFuncCall::Blake2s {
+            inputs,
+            var_message_size,
+            input_packing,
+            output_mode,
+            outputs,
+            ..
+        } => backend.blake2s(
+            initial_witness,
+            inputs,
+            var_message_size.as_ref(),
+            *input_packing,
+            *output_mode,
+            outputs,
+        ),
+        BlackBoxFuncCall::ComputeMerkleRoot { leaf, index, hash_path, output, .. } => {
+            backend.compute_merkle_root(initial_witness, leaf, index, hash_path, output)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::Blake2s, inputs, outputs } => {
-            backend.blake2s(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::SchnorrVerify {
+            public_key_x,
+            public_key_y,
+            signature,
+            message,
+            output,
+            ..
+        } => backend.schnorr_verify(
+            initial_witness,
+            public_key_x,
+            public_key_y,
+            signature,
+            message,
+            output,
+        ),
+        BlackBoxFuncCall::Pedersen { inputs, outputs, .. } => {
+            backend.pedersen(initial_witness, inputs, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::ComputeMerkleRoot, inputs, outputs } => {
-            backend.compute_merkle_root(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::HashToField128Security { inputs, output, .. } => {
+            backend.hash_to_field128_security(initial_witness, inputs, output)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::SchnorrVerify, inputs, outputs } => {
-            backend.schnorr_verify(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::EcdsaSecp256k1 {
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            require_low_s,
+            output,
+            ..
+        } => backend.ecdsa_secp256k1(
+            initial_witness,
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            *require_low_s,
+            output,
+        ),
+        BlackBoxFuncCall::EcdsaSecp256k1Recover {
+            signature,
+            recovery_id,
+            hashed_message,
+            require_low_s,
+            output_mode,
+            outputs,
+            ..
+        } => backend.ecdsa_secp256k1_recover(
+            initial_witness,
+            signature,
+            recovery_id,
+            hashed_message,
+            *require_low_s,
+            *output_mode,
+            outputs,
+        ),
+        BlackBoxFuncCall::FixedBaseScalarMul { input, outputs, .. } => {
+            backend.fixed_base_scalar_mul(initial_witness, input, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::Pedersen, inputs, outputs } => {
-            backend.pedersen(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::EmbeddedCurveAdd {
+            input1_x,
+            input1_y,
+            input2_x,
+            input2_y,
+            outputs,
+            ..
+        } => backend.embedded_curve_add(
+            initial_witness,
+            input1_x,
+            input1_y,
+            input2_x,
+            input2_y,
+            outputs,
+        ),
+        BlackBoxFuncCall::EmbeddedCurveDouble { input_x, input_y, outputs, .. } => {
+            backend.embedded_curve_double(initial_witness, input_x, input_y, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::HashToField128Security, inputs, outputs } => {
-            backend.hash_to_field128_security(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::VariableBaseScalarMul { point_x, point_y, scalar, outputs, .. } => {
+            backend.variable_base_scalar_mul(initial_witness, point_x, point_y, scalar, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::EcdsaSecp256k1, inputs, outputs } => {
-            backend.ecdsa_secp256k1(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::Keccak256 {
+            inputs,
+            var_message_size,
+            input_packing,
+            output_mode,
+            outputs,
+            ..
+        } => backend.keccak256(
+            initial_witness,
+            inputs,
+            var_message_size.as_ref(),
+            *input_packing,
+            *output_mode,
+            outputs,
+        ),
+        BlackBoxFuncCall::Sha256Compression { inputs, hash_values, outputs, .. } => {
+            backend.sha256_compression(initial_witness, inputs, hash_values, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::FixedBaseScalarMul, inputs, outputs } => {
-            backend.fixed_base_scalar_mul(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::Keccakf1600 { inputs, outputs, .. } => {
+            backend.keccakf1600(initial_witness, inputs, outputs)
         }
-        BlackBoxFuncCall { name: BlackBoxFunc::Keccak256, inputs, outputs } => {
-            backend.keccak256(initial_witness, inputs, outputs)
+        BlackBoxFuncCall::Custom { name, inputs, outputs, .. } => {
+            let custom_black_box = custom_black_boxes.get(name).ok_or_else(|| {
+                OpcodeResolutionError::UnsupportedCustomBlackBoxFunc(name.clone())
+            })?;
+            custom_black_box.check_arity(inputs.len(), outputs.len()).map_err(|reason| {
+                OpcodeResolutionError::CustomBlackBoxFunctionFailed(name.clone(), reason)
+            })?;
+            (custom_black_box.solver)(initial_witness, inputs, outputs)
         }
     }
 }
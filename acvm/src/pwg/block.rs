@@ -1,36 +1,242 @@
 use std::collections::{BTreeMap, HashMap};
 
 use acir::{
-    circuit::opcodes::{BlockId, MemOp},
-    native_types::Witness,
+    circuit::opcodes::{BlockId, MemOp, MemoryBlock},
+    native_types::{Expression, Witness},
     FieldElement,
 };
 
-use crate::{pwg::OpcodeResolution, OpcodeNotSolvable, OpcodeResolutionError};
+use crate::{pwg::OpcodeResolution, MemoryOpError, OpcodeNotSolvable, OpcodeResolutionError};
 
-use super::{
-    arithmetic::{ArithmeticSolver, GateStatus},
-    insert_value,
-};
+use super::{arithmetic::ArithmeticSolver, get_value};
 
 /// Maps a block to its emulated state
 #[derive(Default)]
 pub struct Blocks {
     blocks: HashMap<BlockId, BlockSolver>,
+    /// The memories initialized by [MemoryInit][acir::circuit::Opcode::MemoryInit] opcodes, with their length
+    memories: HashMap<BlockId, (u32, BlockSolver)>,
 }
 
 impl Blocks {
     pub fn solve(
+        &mut self,
+        block: &MemoryBlock,
+        solved_witness: &mut BTreeMap<Witness, FieldElement>,
+    ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+        let solver = self.blocks.entry(block.id).or_default();
+        solver.solve(solved_witness, block)
+    }
+
+    /// Initializes the memory of a [MemoryInit][acir::circuit::Opcode::MemoryInit] opcode
+    /// once the values of all its elements are known.
+    pub fn init_memory(
         &mut self,
         id: BlockId,
-        trace: &[MemOp],
+        init: &[Expression],
+        solved_witness: &mut BTreeMap<Witness, FieldElement>,
+    ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+        if self.memories.contains_key(&id) {
+            return Err(malformed(id, 0, MemoryOpError::DuplicateMemory));
+        }
+        let values: Result<Vec<_>, _> =
+            init.iter().map(|value| get_value(value, solved_witness)).collect();
+        let values = match values {
+            Ok(values) => values,
+            Err(OpcodeResolutionError::OpcodeNotSolvable(not_solvable)) => {
+                return Ok(OpcodeResolution::Stalled(not_solvable))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut solver = BlockSolver::default();
+        for (index, value) in (0..).zip(values) {
+            solver.insert_value(index, value);
+        }
+        self.memories.insert(id, (init.len() as u32, solver));
+        Ok(OpcodeResolution::Solved)
+    }
+
+    /// Solves a [MemoryOp][acir::circuit::Opcode::MemoryOp] opcode on a memory initialized by a previous
+    /// [MemoryInit][acir::circuit::Opcode::MemoryInit] opcode.
+    ///
+    /// The operations on a memory must be solved in the order of the opcodes, their position is the number
+    /// of operations solved before them.
+    pub fn solve_memory_op(
+        &mut self,
+        id: BlockId,
+        op: &MemOp,
+        solved_witness: &mut BTreeMap<Witness, FieldElement>,
+    ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+        let Some((len, solver)) = self.memories.get_mut(&id) else {
+            return Err(malformed(id, 0, MemoryOpError::UninitializedMemory));
+        };
+        match solver.solve_operation(solved_witness, id, *len, op) {
+            Ok(()) => Ok(OpcodeResolution::Solved),
+            Err(OpcodeResolutionError::OpcodeNotSolvable(not_solvable)) => {
+                Ok(OpcodeResolution::Stalled(not_solvable))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Solves the reads of a [ROM][acir::circuit::Opcode::ROM] block.
+    ///
+    /// The memory of a ROM cannot change after its initialization phase, so each read is solved independently
+    /// of the others as soon as its index and the value at this index are known.
+    /// A read of a known value can also solve the initialization of the value.
+    pub fn solve_rom(
+        &self,
+        block: &MemoryBlock,
+        solved_witness: &mut BTreeMap<Witness, FieldElement>,
+    ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+        let init = init_values(block)?;
+        for (position, op) in block.trace.iter().enumerate().skip(init.len()) {
+            match op.operation.to_const() {
+                Some(operation) if operation.is_zero() => (),
+                Some(operation) if operation.is_one() => {
+                    return Err(malformed(
+                        block.id,
+                        position,
+                        MemoryOpError::WriteAfterInitialization,
+                    ))
+                }
+                Some(operation) => {
+                    return Err(malformed(
+                        block.id,
+                        position,
+                        MemoryOpError::InvalidOperation(operation),
+                    ))
+                }
+                None => {
+                    return Err(malformed(block.id, position, MemoryOpError::NonConstantOperation))
+                }
+            }
+        }
+
+        let mut progress = false;
+        let mut unsolved = None;
+        for (position, op) in block.trace.iter().enumerate().skip(init.len()) {
+            let index_expr = ArithmeticSolver::evaluate(&op.index, solved_witness);
+            let Some(index) = index_expr.to_const() else {
+                unsolved.get_or_insert(missing_assignment(&index_expr));
+                continue;
+            };
+            let init_value = to_index(index, block.len)
+                .map(|index| init[index])
+                .ok_or_else(|| out_of_bounds(block.id, position, index))?;
+
+            // Whichever of the initial value and the read value is known solves the other one
+            let init_expr = ArithmeticSolver::evaluate(init_value, solved_witness);
+            let value_expr = ArithmeticSolver::evaluate(&op.value, solved_witness);
+            let (known, unknown) = match (init_expr.to_const(), value_expr.to_const()) {
+                (Some(known), Some(value)) if known == value => continue,
+                (Some(_), Some(_)) => {
+                    return Err(malformed(block.id, position, MemoryOpError::IncorrectRead(index)))
+                }
+                (Some(known), None) => (known, value_expr),
+                (None, Some(known)) => (known, init_expr),
+                (None, None) => {
+                    unsolved.get_or_insert(missing_assignment(&value_expr));
+                    continue;
+                }
+            };
+            match ArithmeticSolver::solve(solved_witness, &(unknown - known))? {
+                OpcodeResolution::Stalled(not_solvable) => {
+                    unsolved.get_or_insert(not_solvable);
+                }
+                _ => progress = true,
+            }
+        }
+
+        Ok(match unsolved {
+            None => OpcodeResolution::Solved,
+            Some(_) if progress => OpcodeResolution::InProgress,
+            Some(not_solvable) => OpcodeResolution::Stalled(not_solvable),
+        })
+    }
+
+    /// Solves the operations of a [RAM][acir::circuit::Opcode::RAM] block.
+    ///
+    /// The operations after the initialization phase must be constant reads or writes,
+    /// they are solved in the order of the trace.
+    pub fn solve_ram(
+        &mut self,
+        block: &MemoryBlock,
         solved_witness: &mut BTreeMap<Witness, FieldElement>,
     ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-        let solver = self.blocks.entry(id).or_default();
-        solver.solve(solved_witness, trace)
+        let init_len = init_values(block)?.len();
+        for (position, op) in block.trace.iter().enumerate().skip(init_len) {
+            match op.operation.to_const() {
+                Some(operation) if operation.is_zero() || operation.is_one() => (),
+                Some(operation) => {
+                    return Err(malformed(
+                        block.id,
+                        position,
+                        MemoryOpError::InvalidOperation(operation),
+                    ))
+                }
+                None => {
+                    return Err(malformed(block.id, position, MemoryOpError::NonConstantOperation))
+                }
+            }
+        }
+
+        let solver = self.blocks.entry(block.id).or_default();
+        solver.solve(solved_witness, block)
+    }
+}
+
+/// Solves the reads of the [ReadMemory][acir::circuit::directives::Directive::ReadMemory] directive
+/// by replaying the trace of `block` from its start.
+///
+/// Unlike the [Block][acir::circuit::Opcode::Block] opcode, no state is kept between attempts,
+/// so the directive is in progress whenever a new read has been solved.
+pub(crate) fn read_memory(
+    solved_witness: &mut BTreeMap<Witness, FieldElement>,
+    block: &MemoryBlock,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let num_solved = solved_witness.len();
+    match BlockSolver::default().solve_helper(solved_witness, block) {
+        Ok(()) => Ok(OpcodeResolution::Solved),
+        Err(OpcodeResolutionError::OpcodeNotSolvable(_)) if solved_witness.len() > num_solved => {
+            Ok(OpcodeResolution::InProgress)
+        }
+        Err(OpcodeResolutionError::OpcodeNotSolvable(not_solvable)) => {
+            Ok(OpcodeResolution::Stalled(not_solvable))
+        }
+        Err(err) => Err(err),
     }
 }
 
+fn out_of_bounds(id: BlockId, position: usize, index: FieldElement) -> OpcodeResolutionError {
+    OpcodeResolutionError::IndexOutOfBounds { block_id: id.0, position, index }
+}
+
+fn malformed(id: BlockId, position: usize, reason: MemoryOpError) -> OpcodeResolutionError {
+    OpcodeResolutionError::MalformedMemoryOperation { block_id: id.0, position, reason }
+}
+
+fn missing_assignment(expr: &Expression) -> OpcodeNotSolvable {
+    let witness = ArithmeticSolver::any_witness_from_expression(expr)
+        .expect("expressions which are not constant have a witness");
+    OpcodeNotSolvable::MissingAssignment(witness.0)
+}
+
+// Converts `index` to a position in a block of `len` elements, or None if it is out of bounds
+fn to_index(index: FieldElement, len: u32) -> Option<usize> {
+    index.try_to_u64().filter(|index| *index < len as u64).map(|index| index as usize)
+}
+
+// Checks that the initialization phase of a ROM or RAM block writes each of its elements once,
+// at constant indices, and returns the expression written to each element.
+fn init_values(block: &MemoryBlock) -> Result<Vec<&Expression>, OpcodeResolutionError> {
+    block.sorted_init_phase().map_err(|(position, reason)| match reason {
+        MemoryOpError::InitializationOutOfBounds(index) => out_of_bounds(block.id, position, index),
+        reason => malformed(block.id, position, reason),
+    })
+}
+
 /// Maintains the state for solving Block opcode
 /// block_value is the value of the Block at the solved_operations step
 /// solved_operations is the number of solved elements in the block
@@ -55,45 +261,52 @@ impl BlockSolver {
     fn solve_helper(
         &mut self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        trace: &[MemOp],
+        block: &MemoryBlock,
     ) -> Result<(), OpcodeResolutionError> {
-        let missing_assignment = |witness: Option<Witness>| {
-            OpcodeResolutionError::OpcodeNotSolvable(OpcodeNotSolvable::MissingAssignment(
-                witness.unwrap().0,
-            ))
-        };
+        for block_op in block.trace.iter().skip(self.solved_operations) {
+            self.solve_operation(initial_witness, block.id, block.len, block_op)?;
+        }
+        Ok(())
+    }
 
-        for block_op in trace.iter().skip(self.solved_operations) {
-            let op_expr = ArithmeticSolver::evaluate(&block_op.operation, initial_witness);
-            let operation = op_expr.to_const().ok_or_else(|| {
-                missing_assignment(ArithmeticSolver::any_witness_from_expression(&op_expr))
-            })?;
-            let index_expr = ArithmeticSolver::evaluate(&block_op.index, initial_witness);
-            let index = index_expr.to_const().ok_or_else(|| {
-                missing_assignment(ArithmeticSolver::any_witness_from_expression(&index_expr))
-            })?;
-            let index = index.try_to_u64().unwrap() as u32;
-            let value = ArithmeticSolver::evaluate(&block_op.value, initial_witness);
-            let value_witness = ArithmeticSolver::any_witness_from_expression(&value);
-            if value.is_const() {
-                self.insert_value(index, value.q_c);
-            } else if operation.is_zero() && value.is_linear() {
-                match ArithmeticSolver::solve_fan_in_term(&value, initial_witness) {
-                    GateStatus::GateUnsolvable => return Err(missing_assignment(value_witness)),
-                    GateStatus::GateSolvable(sum, (coef, w)) => {
-                        let map_value =
-                            self.get_value(index).ok_or_else(|| missing_assignment(Some(w)))?;
-                        insert_value(&w, (map_value - sum - value.q_c) / coef, initial_witness)?;
-                    }
-                    GateStatus::GateSatisfied(sum) => {
-                        self.insert_value(index, sum + value.q_c);
-                    }
+    // Solves the next operation on a block of `len` elements, its position in the trace is the number of solved operations
+    fn solve_operation(
+        &mut self,
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        id: BlockId,
+        len: u32,
+        block_op: &MemOp,
+    ) -> Result<(), OpcodeResolutionError> {
+        let position = self.solved_operations;
+        let op_expr = ArithmeticSolver::evaluate(&block_op.operation, initial_witness);
+        let operation = op_expr.to_const().ok_or_else(|| missing_assignment(&op_expr))?;
+        if !operation.is_zero() && !operation.is_one() {
+            return Err(malformed(id, position, MemoryOpError::InvalidOperation(operation)));
+        }
+        let index_expr = ArithmeticSolver::evaluate(&block_op.index, initial_witness);
+        let index = index_expr.to_const().ok_or_else(|| missing_assignment(&index_expr))?;
+        let element =
+            to_index(index, len).ok_or_else(|| out_of_bounds(id, position, index))? as u32;
+
+        let value = ArithmeticSolver::evaluate(&block_op.value, initial_witness);
+        match (operation.is_one(), self.get_value(element), value.to_const()) {
+            (true, _, Some(value)) => self.insert_value(element, value),
+            (false, Some(memory), Some(value)) if memory != value => {
+                return Err(malformed(id, position, MemoryOpError::IncorrectRead(index)))
+            }
+            (false, Some(_), Some(_)) => (),
+            // A read of an element which has not been written yet sets its value
+            (false, None, Some(value)) => self.insert_value(element, value),
+            (false, Some(memory), None) => {
+                if let OpcodeResolution::Stalled(not_solvable) =
+                    ArithmeticSolver::solve(initial_witness, &(value - memory))?
+                {
+                    return Err(not_solvable.into());
                 }
-            } else {
-                return Err(missing_assignment(value_witness));
             }
-            self.solved_operations += 1;
+            (_, _, None) => return Err(missing_assignment(&value).into()),
         }
+        self.solved_operations += 1;
         Ok(())
     }
 
@@ -103,11 +316,11 @@ impl BlockSolver {
     pub(crate) fn solve(
         &mut self,
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
-        trace: &[MemOp],
+        block: &MemoryBlock,
     ) -> Result<OpcodeResolution, OpcodeResolutionError> {
         let initial_solved_operations = self.solved_operations;
 
-        match self.solve_helper(initial_witness, trace) {
+        match self.solve_helper(initial_witness, block) {
             Ok(()) => Ok(OpcodeResolution::Solved),
             Err(OpcodeResolutionError::OpcodeNotSolvable(err)) => {
                 if self.solved_operations > initial_solved_operations {
@@ -126,15 +339,38 @@ mod test {
     use std::collections::BTreeMap;
 
     use acir::{
-        circuit::opcodes::{BlockId, MemOp},
+        circuit::opcodes::{BlockId, MemOp, MemoryBlock},
         native_types::{Expression, Witness},
         FieldElement,
     };
 
-    use crate::pwg::insert_value;
+    use crate::{
+        pwg::{insert_value, OpcodeResolution},
+        MemoryOpError, OpcodeResolutionError,
+    };
 
     use super::Blocks;
 
+    fn op(operation: u128, index: Expression, value: Expression) -> MemOp {
+        MemOp { operation: Expression::from_field(FieldElement::from(operation)), index, value }
+    }
+
+    fn constant(value: u128) -> Expression {
+        Expression::from_field(FieldElement::from(value))
+    }
+
+    // A block whose elements are initialized with witnesses 1, 2 and 3
+    fn block(trace: Vec<MemOp>) -> MemoryBlock {
+        let mut init: Vec<_> =
+            (0..3).map(|i| op(1, constant(i), Expression::from(Witness(i as u32 + 1)))).collect();
+        init.extend(trace);
+        MemoryBlock { id: BlockId(7), len: 3, trace: init }
+    }
+
+    fn initial_witness() -> BTreeMap<Witness, FieldElement> {
+        (1..=3).map(|i| (Witness(i), FieldElement::from(10 * i as u128))).collect()
+    }
+
     #[test]
     fn test_solver() {
         let mut index = FieldElement::zero();
@@ -160,7 +396,7 @@ mod test {
             index: Expression::one(),
             value: Expression::from(Witness(4)),
         });
-        let id = BlockId::default();
+        let block = MemoryBlock { id: BlockId::default(), len: 3, trace };
         let mut initial_witness = BTreeMap::new();
         let mut value = FieldElement::zero();
         insert_value(&Witness(1), value, &mut initial_witness).unwrap();
@@ -169,7 +405,162 @@ mod test {
         value = value + value;
         insert_value(&Witness(3), value, &mut initial_witness).unwrap();
         let mut blocks = Blocks::default();
-        blocks.solve(id, &trace, &mut initial_witness).unwrap();
+        blocks.solve(&block, &mut initial_witness).unwrap();
         assert_eq!(initial_witness[&Witness(4)], FieldElement::one());
     }
+
+    #[test]
+    fn solves_rom_reads_in_any_order() {
+        // The index of the first read is only known after the second read is solved
+        let rom = block(vec![
+            op(0, Expression::from(Witness(5)), Expression::from(Witness(6))),
+            op(0, constant(1), Expression::from(Witness(4))),
+        ]);
+        let mut initial_witness = initial_witness();
+        let blocks = Blocks::default();
+        assert_eq!(blocks.solve_rom(&rom, &mut initial_witness), Ok(OpcodeResolution::InProgress));
+        assert_eq!(initial_witness[&Witness(4)], FieldElement::from(20_u128));
+
+        initial_witness.insert(Witness(5), FieldElement::from(2_u128));
+        assert_eq!(blocks.solve_rom(&rom, &mut initial_witness), Ok(OpcodeResolution::Solved));
+        assert_eq!(initial_witness[&Witness(6)], FieldElement::from(30_u128));
+    }
+
+    #[test]
+    fn rejects_invalid_rom_and_ram_operations() {
+        let blocks = Blocks::default();
+        let write_after_init = block(vec![op(1, constant(0), Expression::from(Witness(4)))]);
+        assert_eq!(
+            blocks.solve_rom(&write_after_init, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 3,
+                reason: MemoryOpError::WriteAfterInitialization
+            })
+        );
+
+        let out_of_bounds = block(vec![op(0, constant(3), Expression::from(Witness(4)))]);
+        let out_of_bounds_error = || OpcodeResolutionError::IndexOutOfBounds {
+            block_id: 7,
+            position: 3,
+            index: FieldElement::from(3_u128),
+        };
+        assert_eq!(
+            blocks.solve_rom(&out_of_bounds, &mut initial_witness()),
+            Err(out_of_bounds_error())
+        );
+        assert_eq!(
+            Blocks::default().solve_ram(&out_of_bounds, &mut initial_witness()),
+            Err(out_of_bounds_error())
+        );
+
+        let dynamic_operation = block(vec![MemOp {
+            operation: Expression::from(Witness(4)),
+            index: constant(0),
+            value: Expression::from(Witness(5)),
+        }]);
+        assert_eq!(
+            Blocks::default().solve_ram(&dynamic_operation, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 3,
+                reason: MemoryOpError::NonConstantOperation
+            })
+        );
+
+        let mut duplicate_init = block(vec![]);
+        duplicate_init.trace[2].index = constant(0);
+        assert_eq!(
+            blocks.solve_rom(&duplicate_init, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 2,
+                reason: MemoryOpError::DuplicateInitialization(FieldElement::zero())
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_block_operations() {
+        // Operations are either reads (0) or writes (1)
+        let invalid_operation = block(vec![op(2, constant(0), Expression::from(Witness(4)))]);
+        assert_eq!(
+            Blocks::default().solve(&invalid_operation, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 3,
+                reason: MemoryOpError::InvalidOperation(FieldElement::from(2_u128))
+            })
+        );
+
+        // Indices which do not fit in a u64 are out of bounds rather than truncated
+        let huge_index = -FieldElement::one();
+        let out_of_bounds = block(vec![op(0, Expression::from_field(huge_index), constant(0))]);
+        assert_eq!(
+            Blocks::default().solve(&out_of_bounds, &mut initial_witness()),
+            Err(OpcodeResolutionError::IndexOutOfBounds {
+                block_id: 7,
+                position: 3,
+                index: huge_index
+            })
+        );
+
+        let incorrect_read = block(vec![op(0, constant(1), constant(5))]);
+        assert_eq!(
+            Blocks::default().solve(&incorrect_read, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 3,
+                reason: MemoryOpError::IncorrectRead(FieldElement::one())
+            })
+        );
+    }
+
+    #[test]
+    fn solves_ram_operations_in_order() {
+        let ram = block(vec![
+            op(1, constant(0), constant(5)),
+            op(0, constant(0), Expression::from(Witness(4))),
+            op(0, constant(2), Expression::from(Witness(5))),
+        ]);
+        let mut initial_witness = initial_witness();
+        let result = Blocks::default().solve_ram(&ram, &mut initial_witness);
+        assert_eq!(result, Ok(OpcodeResolution::Solved));
+        assert_eq!(initial_witness[&Witness(4)], FieldElement::from(5_u128));
+        assert_eq!(initial_witness[&Witness(5)], FieldElement::from(30_u128));
+    }
+
+    #[test]
+    fn rejects_memory_operations_without_a_single_initialization() {
+        let read = op(0, constant(0), Expression::from(Witness(4)));
+        let mut blocks = Blocks::default();
+        assert_eq!(
+            blocks.solve_memory_op(BlockId(7), &read, &mut initial_witness()),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 0,
+                reason: MemoryOpError::UninitializedMemory
+            })
+        );
+
+        let init = [Expression::from(Witness(1))];
+        let mut initial_witness = initial_witness();
+        assert_eq!(
+            blocks.init_memory(BlockId(7), &init, &mut initial_witness),
+            Ok(OpcodeResolution::Solved)
+        );
+        assert_eq!(
+            blocks.solve_memory_op(BlockId(7), &read, &mut initial_witness),
+            Ok(OpcodeResolution::Solved)
+        );
+        assert_eq!(initial_witness[&Witness(4)], FieldElement::from(10_u128));
+        assert_eq!(
+            blocks.init_memory(BlockId(7), &init, &mut initial_witness),
+            Err(OpcodeResolutionError::MalformedMemoryOperation {
+                block_id: 7,
+                position: 0,
+                reason: MemoryOpError::DuplicateMemory
+            })
+        );
+    }
 }
@@ -1,20 +1,29 @@
-use std::{cmp::Ordering, collections::BTreeMap};
+use std::collections::BTreeMap;
 
 use acir::{
-    circuit::directives::{Directive, LogInfo},
-    native_types::Witness,
+    circuit::directives::{is_valid_radix, Directive},
+    native_types::{Expression, Witness},
     FieldElement,
 };
-use num_bigint::BigUint;
-use num_traits::Zero;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{One, Zero};
 
 use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
 
-use super::{get_value, insert_value, sorting::route, witness_to_value};
+use super::{
+    bigint::solve_bigint_quotient,
+    block::read_memory,
+    get_value, insert_value,
+    sorting::{compare_keys, route},
+    witness_to_value,
+};
 
 /// Attempts to solve the [`Directive`] opcode `directive`.
 /// If successful, `initial_witness` will be mutated to contain the new witness assignment.
 ///
+/// The [Log][Directive::Log] directive is not solved here, as it requires a sink for its output,
+/// see [`solve_log`][super::log::solve_log].
+///
 /// Returns `Ok(OpcodeResolution)` to signal whether the directive was successful solved.
 ///
 /// Returns `Err(OpcodeResolutionError)` if a circuit constraint is unsatisfied.
@@ -22,6 +31,11 @@ pub fn solve_directives(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
     directive: &Directive,
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    // Replaying a trace can solve some of its reads without solving all of them
+    if let Directive::ReadMemory(block) = directive {
+        return read_memory(initial_witness, block);
+    }
+
     match solve_directives_internal(initial_witness, directive) {
         Ok(_) => Ok(OpcodeResolution::Solved),
         Err(OpcodeResolutionError::OpcodeNotSolvable(unsolved)) => {
@@ -48,15 +62,10 @@ fn solve_directives_internal(
             let int_a = BigUint::from_bytes_be(&val_a.to_be_bytes());
             let int_b = BigUint::from_bytes_be(&val_b.to_be_bytes());
 
-            // If the predicate is `None`, then we simply return the value 1
-            // If the predicate is `Some` but we cannot find a value, then we return unresolved
-            let pred_value = match predicate {
-                Some(pred) => get_value(pred, initial_witness)?,
-                None => FieldElement::one(),
-            };
-
-            let (int_r, int_q) = if pred_value.is_zero() {
+            let (int_r, int_q) = if !predicate_value(predicate, initial_witness)? {
                 (BigUint::zero(), BigUint::zero())
+            } else if int_b.is_zero() {
+                return Err(OpcodeResolutionError::DivisionByZero);
             } else {
                 (&int_a % &int_b, &int_a / &int_b)
             };
@@ -74,18 +83,35 @@ fn solve_directives_internal(
 
             Ok(())
         }
-        Directive::ToLeRadix { a, b, radix } => {
-            let value_a = get_value(a, initial_witness)?;
-            let big_integer = BigUint::from_bytes_be(&value_a.to_be_bytes());
-
-            // Decompose the integer into its radix digits in little endian form.
-            let decomposed_integer = big_integer.to_radix_le(*radix);
+        Directive::SignedQuotient { a, b, q, r, predicate, bit_size } => {
+            // The operands of a disabled division may not be integers of `bit_size` bits
+            if !predicate_value(predicate, initial_witness)? {
+                insert_value(q, FieldElement::zero(), initial_witness)?;
+                insert_value(r, FieldElement::zero(), initial_witness)?;
+                return Ok(());
+            }
 
-            if b.len() < decomposed_integer.len() {
-                return Err(OpcodeResolutionError::UnsatisfiedConstrain);
+            let int_a = to_signed(get_value(a, initial_witness)?, *bit_size)?;
+            let int_b = to_signed(get_value(b, initial_witness)?, *bit_size)?;
+            if int_b.is_zero() {
+                return Err(OpcodeResolutionError::DivisionByZero);
             }
 
-            for (i, witness) in b.iter().enumerate() {
+            // Division and remainder of `BigInt` truncate toward zero
+            insert_value(q, from_signed(&int_a / &int_b, *bit_size), initial_witness)?;
+            insert_value(r, from_signed(&int_a % &int_b, *bit_size), initial_witness)?;
+
+            Ok(())
+        }
+        Directive::ToLeRadix { a, b, radix } | Directive::ToBeRadix { a, b, radix } => {
+            let decomposed_integer = to_radix(a, b, *radix, initial_witness)?;
+
+            // Order the witnesses from the least significant digit
+            let mut le_witnesses: Vec<_> = b.iter().collect();
+            if matches!(directive, Directive::ToBeRadix { .. }) {
+                le_witnesses.reverse();
+            }
+            for (i, witness) in le_witnesses.into_iter().enumerate() {
                 // Fetch the `i'th` digit from the decomposed integer list
                 // and convert it to a field element.
                 // If it is not available, which can happen when the decomposed integer
@@ -101,31 +127,27 @@ fn solve_directives_internal(
             Ok(())
         }
         Directive::PermutationSort { inputs: a, tuple, bits, sort_by } => {
-            let mut val_a = Vec::new();
-            let mut base = Vec::new();
-            for (i, element) in a.iter().enumerate() {
+            let mut keys = Vec::with_capacity(a.len());
+            for element in a {
                 assert_eq!(element.len(), *tuple as usize);
-                let mut element_val = Vec::with_capacity(*tuple as usize + 1);
-                for e in element {
-                    element_val.push(get_value(e, initial_witness)?);
-                }
-                let field_i = FieldElement::from(i as i128);
-                element_val.push(field_i);
-                base.push(field_i);
-                val_a.push(element_val);
+                let element_keys: Result<Vec<_>, _> = sort_by
+                    .iter()
+                    .map(|key| {
+                        let value = get_value(&element[key.index as usize], initial_witness)?;
+                        match key.signed_bit_size {
+                            Some(bit_size) => to_signed(value, bit_size),
+                            None => Ok(BigInt::from_bytes_be(Sign::Plus, &value.to_be_bytes())),
+                        }
+                    })
+                    .collect();
+                keys.push(element_keys?);
             }
-            val_a.sort_by(|a, b| {
-                for i in sort_by {
-                    let int_a = BigUint::from_bytes_be(&a[*i as usize].to_be_bytes());
-                    let int_b = BigUint::from_bytes_be(&b[*i as usize].to_be_bytes());
-                    let cmp = int_a.cmp(&int_b);
-                    if cmp != Ordering::Equal {
-                        return cmp;
-                    }
-                }
-                Ordering::Equal
-            });
-            let b = val_a.iter().map(|a| *a.last().unwrap()).collect();
+            // The sort is stable, so tuples with equal keys keep their order
+            let mut positions: Vec<usize> = (0..a.len()).collect();
+            positions.sort_by(|i, j| compare_keys(&keys[*i], &keys[*j], sort_by));
+
+            let base = (0..a.len()).map(|i| FieldElement::from(i as u128)).collect();
+            let b = positions.into_iter().map(|i| FieldElement::from(i as u128)).collect();
             let control = route(base, b);
             for (w, value) in bits.iter().zip(control) {
                 let value = if value { FieldElement::one() } else { FieldElement::zero() };
@@ -133,53 +155,171 @@ fn solve_directives_internal(
             }
             Ok(())
         }
-        Directive::Log(info) => {
-            let witnesses = match info {
-                LogInfo::FinalizedOutput(output_string) => {
-                    println!("{output_string}");
-                    return Ok(());
-                }
-                LogInfo::WitnessOutput(witnesses) => witnesses,
-            };
+        Directive::Log(_) => unreachable!("logs are solved by solve_log"),
+        Directive::BigIntQuotient { a, limb_bits, modulus, q, r } => {
+            solve_bigint_quotient(initial_witness, a, *limb_bits, modulus, q, r)
+        }
+        Directive::ReadMemory(_) => unreachable!("memory reads are solved by read_memory"),
+    }
+}
 
-            if witnesses.len() == 1 {
-                let witness = &witnesses[0];
-                let log_value = witness_to_value(initial_witness, *witness)?;
-                println!("{}", format_field_string(*log_value));
-                return Ok(());
-            }
+// Decomposes the value of `a` into its digits in base `radix`, in little endian form.
+// Fails if the radix is invalid or if the digits do not fit in `b`.
+fn to_radix(
+    a: &Expression,
+    b: &[Witness],
+    radix: u32,
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+) -> Result<Vec<u8>, OpcodeResolutionError> {
+    if !is_valid_radix(radix) {
+        return Err(OpcodeResolutionError::InvalidRadix(radix));
+    }
+    let value_a = get_value(a, initial_witness)?;
+    let big_integer = BigUint::from_bytes_be(&value_a.to_be_bytes());
 
-            // If multiple witnesses are to be fetched for a log directive,
-            // it assumed that an array is meant to be printed to standard output
-            //
-            // Collect all field element values corresponding to the given witness indices
-            // and convert them to hex strings.
-            let mut elements_as_hex = Vec::with_capacity(witnesses.len());
-            for witness in witnesses {
-                let element = witness_to_value(initial_witness, *witness)?;
-                elements_as_hex.push(format_field_string(*element));
-            }
+    let decomposed_integer = big_integer.to_radix_le(radix);
+    if b.len() < decomposed_integer.len() {
+        return Err(OpcodeResolutionError::UnsatisfiedConstrain);
+    }
+    Ok(decomposed_integer)
+}
+
+// Returns whether the predicate of a directive is enabled.
+// If the predicate is `None`, then we simply return true
+// If the predicate is `Some` but we cannot find a value, then we return unresolved
+fn predicate_value(
+    predicate: &Option<Expression>,
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+) -> Result<bool, OpcodeResolutionError> {
+    match predicate {
+        Some(pred) => Ok(!get_value(pred, initial_witness)?.is_zero()),
+        None => Ok(true),
+    }
+}
+
+// Reads `value` as an integer of `bit_size` bits in two's complement form
+fn to_signed(value: FieldElement, bit_size: u32) -> Result<BigInt, OpcodeResolutionError> {
+    if bit_size == 0 || bit_size >= FieldElement::max_num_bits() || value.num_bits() > bit_size {
+        return Err(OpcodeResolutionError::InvalidSignedInteger { value, bit_size });
+    }
+    let int_value = BigInt::from_bytes_be(Sign::Plus, &value.to_be_bytes());
+    if int_value.bit(u64::from(bit_size - 1)) {
+        Ok(int_value - (BigInt::one() << bit_size))
+    } else {
+        Ok(int_value)
+    }
+}
 
-            // Join all of the hex strings using a comma
-            let comma_separated_elements = elements_as_hex.join(", ");
+// Returns the two's complement form of `value` over `bit_size` bits, wrapping around on overflow
+fn from_signed(value: BigInt, bit_size: u32) -> FieldElement {
+    let modulus = BigInt::one() << bit_size;
+    let (_, bytes) = (((value % &modulus) + &modulus) % &modulus).to_bytes_be();
+    FieldElement::from_be_bytes_reduce(&bytes)
+}
 
-            let output_witnesses_string = "[".to_owned() + &comma_separated_elements + "]";
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
 
-            println!("{output_witnesses_string}");
+    use acir::{
+        circuit::directives::Directive,
+        native_types::{Expression, Witness},
+        FieldElement,
+    };
 
-            Ok(())
-        }
+    use super::solve_directives;
+    use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+    fn quotient(
+        a: u128,
+        b: u128,
+        bit_size: Option<u32>,
+    ) -> Result<(u128, u128), OpcodeResolutionError> {
+        let (a, b) = (Expression::from_field(a.into()), Expression::from_field(b.into()));
+        let (q, r) = (Witness(1), Witness(2));
+        let directive = match bit_size {
+            Some(bit_size) => Directive::SignedQuotient { a, b, q, r, predicate: None, bit_size },
+            None => Directive::Quotient { a, b, q, r, predicate: None },
+        };
+        let mut initial_witness = BTreeMap::new();
+        assert_eq!(solve_directives(&mut initial_witness, &directive)?, OpcodeResolution::Solved);
+        Ok((initial_witness[&q].to_u128(), initial_witness[&r].to_u128()))
     }
-}
 
-/// This trims any leading zeroes.
-/// A singular '0' will be prepended as well if the trimmed string has an odd length.
-/// A hex string's length needs to be even to decode into bytes, as two digits correspond to
-/// one byte.
-fn format_field_string(field: FieldElement) -> String {
-    let mut trimmed_field = field.to_hex().trim_start_matches('0').to_owned();
-    if trimmed_field.len() % 2 != 0 {
-        trimmed_field = "0".to_owned() + &trimmed_field
+    #[test]
+    fn rejects_division_by_zero() {
+        assert_eq!(quotient(7, 0, None), Err(OpcodeResolutionError::DivisionByZero));
+        assert_eq!(quotient(7, 0, Some(8)), Err(OpcodeResolutionError::DivisionByZero));
+
+        // The division is skipped when the predicate is zero
+        let directive = Directive::Quotient {
+            a: Expression::from_field(FieldElement::from(7u128)),
+            b: Expression::zero(),
+            q: Witness(1),
+            r: Witness(2),
+            predicate: Some(Expression::zero()),
+        };
+        assert_eq!(
+            solve_directives(&mut BTreeMap::new(), &directive),
+            Ok(OpcodeResolution::Solved)
+        );
+    }
+
+    #[test]
+    fn signed_division_truncates_toward_zero() {
+        // 8-bit integers in two's complement form: -7 is 0xf9, -3 is 0xfd, -2 is 0xfe, -1 is 0xff
+        assert_eq!(quotient(7, 2, Some(8)), Ok((3, 1)));
+        assert_eq!(quotient(0xf9, 2, Some(8)), Ok((0xfd, 0xff)));
+        assert_eq!(quotient(7, 0xfe, Some(8)), Ok((0xfd, 1)));
+        assert_eq!(quotient(0xf9, 0xfe, Some(8)), Ok((3, 0xff)));
+        // -128 / -1 overflows and wraps around to -128
+        assert_eq!(quotient(0x80, 0xff, Some(8)), Ok((0x80, 0)));
+
+        assert_eq!(
+            quotient(0x100, 2, Some(8)),
+            Err(OpcodeResolutionError::InvalidSignedInteger {
+                value: FieldElement::from(0x100u128),
+                bit_size: 8
+            })
+        );
+
+        // The operands are not checked when the predicate is zero
+        let directive = Directive::SignedQuotient {
+            a: Expression::from_field(FieldElement::from(0x100u128)),
+            b: Expression::from_field(FieldElement::from(2u128)),
+            q: Witness(1),
+            r: Witness(2),
+            predicate: Some(Expression::zero()),
+            bit_size: 8,
+        };
+        let mut initial_witness = BTreeMap::new();
+        assert_eq!(
+            solve_directives(&mut initial_witness, &directive),
+            Ok(OpcodeResolution::Solved)
+        );
+        assert_eq!(initial_witness[&Witness(1)], FieldElement::zero());
+        assert_eq!(initial_witness[&Witness(2)], FieldElement::zero());
+    }
+
+    #[test]
+    fn decomposes_in_big_endian_form() {
+        let b = vec![Witness(1), Witness(2), Witness(3)];
+        let a = Expression::from_field(FieldElement::from(0x0102u128));
+        let mut initial_witness = BTreeMap::new();
+        let directive = Directive::ToBeRadix { a: a.clone(), b: b.clone(), radix: 256 };
+        assert_eq!(
+            solve_directives(&mut initial_witness, &directive),
+            Ok(OpcodeResolution::Solved)
+        );
+        let digits: Vec<_> = b.iter().map(|witness| initial_witness[witness].to_u128()).collect();
+        assert_eq!(digits, vec![0, 1, 2]);
+
+        for radix in [0, 1, 257] {
+            let directive = Directive::ToBeRadix { a: a.clone(), b: b.clone(), radix };
+            assert_eq!(
+                solve_directives(&mut BTreeMap::new(), &directive),
+                Err(OpcodeResolutionError::InvalidRadix(radix))
+            );
+        }
     }
-    "0x".to_owned() + &trimmed_field
 }
@@ -0,0 +1,216 @@
+// Solvers for the black box functions over the embedded curve of the proving system.
+//
+// The embedded curve is Grumpkin, the short Weierstrass curve `y^2 = x^3 - 17` over the bn254 scalar field.
+// The group law does not depend on the constant term, which is only used to check that inputs are on the curve.
+// Points are given in affine form, and the point at infinity is represented by `(0, 0)`,
+// which is not on the curve as the constant term is non-zero.
+// Over other fields the embedded curve is not defined, so the solvers fail.
+
+use acir::{
+    acir_field::{FieldOptions, CHOSEN_FIELD},
+    circuit::opcodes::FunctionInput,
+    native_types::Witness,
+    BlackBoxFunc, FieldElement,
+};
+use std::collections::BTreeMap;
+
+use crate::{pwg::input_to_value, pwg::insert_value, pwg::OpcodeResolution, OpcodeResolutionError};
+
+type AffinePoint = (FieldElement, FieldElement);
+
+const CURVE_CONSTANT_TERM: i128 = -17;
+
+fn infinity() -> AffinePoint {
+    (FieldElement::zero(), FieldElement::zero())
+}
+
+fn is_infinity(point: &AffinePoint) -> bool {
+    point.0.is_zero() && point.1.is_zero()
+}
+
+fn is_on_curve(point: &AffinePoint) -> bool {
+    is_infinity(point)
+        || point.1 * point.1
+            == point.0 * point.0 * point.0 + FieldElement::from(CURVE_CONSTANT_TERM)
+}
+
+fn add(lhs: AffinePoint, rhs: AffinePoint) -> AffinePoint {
+    if is_infinity(&lhs) {
+        return rhs;
+    }
+    if is_infinity(&rhs) {
+        return lhs;
+    }
+    if lhs.0 == rhs.0 {
+        // The points are either equal or each other's negation
+        return if lhs.1 == rhs.1 { double(lhs) } else { infinity() };
+    }
+
+    let lambda = (rhs.1 - lhs.1) / (rhs.0 - lhs.0);
+    let x = lambda * lambda - lhs.0 - rhs.0;
+    let y = lambda * (lhs.0 - x) - lhs.1;
+    (x, y)
+}
+
+fn double(point: AffinePoint) -> AffinePoint {
+    // Points with a zero `y` coordinate have order 2
+    if point.1.is_zero() {
+        return infinity();
+    }
+
+    let x_squared = point.0 * point.0;
+    let lambda = (x_squared + x_squared + x_squared) / (point.1 + point.1);
+    let x = lambda * lambda - point.0 - point.0;
+    let y = lambda * (point.0 - x) - point.1;
+    (x, y)
+}
+
+// Double-and-add over the bits of the scalar, starting from the most significant bit
+fn scalar_mul(point: AffinePoint, scalar: FieldElement) -> AffinePoint {
+    scalar.bits().into_iter().fold(infinity(), |accumulator, bit| {
+        let accumulator = double(accumulator);
+        if bit {
+            add(accumulator, point)
+        } else {
+            accumulator
+        }
+    })
+}
+
+// Reads a point from its coordinates, which must be on the curve for the group law to apply
+fn read_point(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    x: &FunctionInput,
+    y: &FunctionInput,
+) -> Result<AffinePoint, OpcodeResolutionError> {
+    if !matches!(CHOSEN_FIELD, FieldOptions::BN254) {
+        return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            func,
+            format!("the embedded curve is only defined over bn254, not {CHOSEN_FIELD:?}"),
+        ));
+    }
+    let point = (input_to_value(initial_witness, x)?, input_to_value(initial_witness, y)?);
+    if !is_on_curve(&point) {
+        return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            func,
+            format!("the point ({}, {}) is not on the embedded curve", point.0, point.1),
+        ));
+    }
+    Ok(point)
+}
+
+fn insert_point(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    outputs: &(Witness, Witness),
+    point: AffinePoint,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    insert_value(&outputs.0, point.0, initial_witness)?;
+    insert_value(&outputs.1, point.1, initial_witness)?;
+    Ok(OpcodeResolution::Solved)
+}
+
+pub fn embedded_curve_add(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    input1_x: &FunctionInput,
+    input1_y: &FunctionInput,
+    input2_x: &FunctionInput,
+    input2_y: &FunctionInput,
+    outputs: &(Witness, Witness),
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let func = BlackBoxFunc::EmbeddedCurveAdd;
+    let lhs = read_point(initial_witness, func, input1_x, input1_y)?;
+    let rhs = read_point(initial_witness, func, input2_x, input2_y)?;
+    insert_point(initial_witness, outputs, add(lhs, rhs))
+}
+
+pub fn embedded_curve_double(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    input_x: &FunctionInput,
+    input_y: &FunctionInput,
+    outputs: &(Witness, Witness),
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let point = read_point(initial_witness, BlackBoxFunc::EmbeddedCurveDouble, input_x, input_y)?;
+    insert_point(initial_witness, outputs, double(point))
+}
+
+pub fn variable_base_scalar_mul(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    point_x: &FunctionInput,
+    point_y: &FunctionInput,
+    scalar: &FunctionInput,
+    outputs: &(Witness, Witness),
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let point = read_point(initial_witness, BlackBoxFunc::VariableBaseScalarMul, point_x, point_y)?;
+    let scalar = input_to_value(initial_witness, scalar)?;
+    insert_point(initial_witness, outputs, scalar_mul(point, scalar))
+}
+
+#[cfg(all(test, feature = "bn254"))]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{circuit::opcodes::FunctionInput, native_types::Witness, FieldElement};
+
+    use super::{add, double, embedded_curve_add, infinity, is_on_curve, scalar_mul, AffinePoint};
+    use crate::OpcodeResolutionError;
+
+    // The generator of Grumpkin, the embedded curve of bn254
+    fn generator() -> AffinePoint {
+        (
+            FieldElement::one(),
+            FieldElement::from_hex(
+                "0x0000000000000002cf135e7506a45d632d270d45f1181294833fc48d823f272c",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn follows_group_law() {
+        let g = generator();
+        assert!(is_on_curve(&g));
+
+        let two_g = double(g);
+        let three_g = add(two_g, g);
+        assert!(is_on_curve(&two_g));
+        assert!(is_on_curve(&three_g));
+        assert_eq!(add(g, g), two_g);
+        assert_eq!(add(g, two_g), three_g);
+        assert_eq!(add(g, infinity()), g);
+        assert_eq!(add(g, (g.0, -g.1)), infinity());
+    }
+
+    #[test]
+    fn multiplies_by_scalar() {
+        let g = generator();
+        assert_eq!(scalar_mul(g, FieldElement::zero()), infinity());
+        assert_eq!(scalar_mul(g, FieldElement::one()), g);
+        assert_eq!(scalar_mul(g, FieldElement::from(3_u128)), add(double(g), g));
+
+        // Multiplying by `a` then `b` is the same as multiplying by `a * b`
+        let a = FieldElement::from(123_456_789_u128);
+        let b = FieldElement::from(987_654_321_u128);
+        assert_eq!(scalar_mul(scalar_mul(g, a), b), scalar_mul(g, a * b));
+    }
+
+    #[test]
+    fn rejects_points_off_the_curve() {
+        let g = generator();
+        let off_curve = (g.0, g.1 + FieldElement::one());
+        assert!(!is_on_curve(&off_curve));
+
+        let mut initial_witness = BTreeMap::new();
+        let constant = |value| FunctionInput::constant(value, FieldElement::max_num_bits());
+        let result = embedded_curve_add(
+            &mut initial_witness,
+            &constant(g.0),
+            &constant(g.1),
+            &constant(off_curve.0),
+            &constant(off_curve.1),
+            &(Witness(1), Witness(2)),
+        );
+        assert!(matches!(result, Err(OpcodeResolutionError::BlackBoxFunctionFailed(..))));
+        assert!(initial_witness.is_empty());
+    }
+}
@@ -1,92 +1,671 @@
-use acir::{circuit::opcodes::BlackBoxFuncCall, native_types::Witness, FieldElement};
+use acir::{
+    circuit::{
+        black_box_functions::{HashInputPacking, HashOutputMode},
+        opcodes::FunctionInput,
+    },
+    native_types::Witness,
+    BlackBoxFunc, FieldElement,
+};
 use blake2::{Blake2s256, Digest};
-use sha2::Sha256;
+use num_bigint::BigUint;
+use sha2::{digest::generic_array::GenericArray, Sha256};
 use sha3::Keccak256;
 use std::collections::BTreeMap;
 
 use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
 
-use super::{insert_value, witness_to_value};
+use super::{input_to_value, insert_value};
 
 pub fn blake2s256(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    inputs: &[FunctionInput],
+    var_message_size: Option<&FunctionInput>,
+    input_packing: HashInputPacking,
+    output_mode: HashOutputMode,
+    outputs: &[Witness],
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    let hash = generic_hash_256::<Blake2s256>(initial_witness, func_call)?;
+    let func = BlackBoxFunc::Blake2s;
+    let message = message_inputs(initial_witness, func, inputs, var_message_size)?;
+    let hash = generic_hash_256::<Blake2s256>(initial_witness, func, message, input_packing)?;
 
-    for (output_witness, value) in func_call.outputs.iter().zip(hash.iter()) {
-        insert_value(
-            output_witness,
-            FieldElement::from_be_bytes_reduce(&[*value]),
-            initial_witness,
-        )?;
-    }
+    insert_digest(initial_witness, func, &hash, output_mode, outputs)?;
 
     Ok(OpcodeResolution::Solved)
 }
 
 pub fn sha256(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    inputs: &[FunctionInput],
+    var_message_size: Option<&FunctionInput>,
+    input_packing: HashInputPacking,
+    output_mode: HashOutputMode,
+    outputs: &[Witness],
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    let hash = generic_hash_256::<Sha256>(initial_witness, func_call)?;
+    let func = BlackBoxFunc::SHA256;
+    let message = message_inputs(initial_witness, func, inputs, var_message_size)?;
+    let hash = generic_hash_256::<Sha256>(initial_witness, func, message, input_packing)?;
 
-    for (output_witness, value) in func_call.outputs.iter().zip(hash.iter()) {
-        insert_value(
-            output_witness,
-            FieldElement::from_be_bytes_reduce(&[*value]),
-            initial_witness,
-        )?;
-    }
+    insert_digest(initial_witness, func, &hash, output_mode, outputs)?;
 
     Ok(OpcodeResolution::Solved)
 }
 
 pub fn keccak256(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    inputs: &[FunctionInput],
+    var_message_size: Option<&FunctionInput>,
+    input_packing: HashInputPacking,
+    output_mode: HashOutputMode,
+    outputs: &[Witness],
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    let hash = generic_hash_256::<Keccak256>(initial_witness, func_call)?;
+    let func = BlackBoxFunc::Keccak256;
+    let message = message_inputs(initial_witness, func, inputs, var_message_size)?;
+    let hash = generic_hash_256::<Keccak256>(initial_witness, func, message, input_packing)?;
 
-    for (output_witness, value) in func_call.outputs.iter().zip(hash.iter()) {
-        insert_value(
-            output_witness,
-            FieldElement::from_be_bytes_reduce(&[*value]),
-            initial_witness,
-        )?;
-    }
+    insert_digest(initial_witness, func, &hash, output_mode, outputs)?;
 
     Ok(OpcodeResolution::Solved)
 }
 
 pub fn hash_to_field_128_security(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    inputs: &[FunctionInput],
+    output: &Witness,
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    let hash = generic_hash_256::<Blake2s256>(initial_witness, func_call)?;
+    let hash = generic_hash_256::<Blake2s256>(
+        initial_witness,
+        BlackBoxFunc::HashToField128Security,
+        inputs,
+        HashInputPacking::Bytes,
+    )?;
 
     let reduced_res = FieldElement::from_be_bytes_reduce(&hash);
-    insert_value(&func_call.outputs[0], reduced_res, initial_witness)?;
+    insert_value(output, reduced_res, initial_witness)?;
+
+    Ok(OpcodeResolution::Solved)
+}
+
+/// Applies the SHA256 compression function to a single block.
+///
+/// `inputs` are the 16 words of the block and `hash_values` are the 8 words of the current state,
+/// the updated state is assigned to `outputs`.
+pub fn sha256_compression(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    inputs: &[FunctionInput],
+    hash_values: &[FunctionInput],
+    outputs: &[Witness],
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let func = BlackBoxFunc::Sha256Compression;
+    let block: [u32; 16] = to_words(initial_witness, func, inputs)?;
+    let mut state: [u32; 8] = to_words(initial_witness, func, hash_values)?;
+
+    let block_bytes: Vec<u8> = block.iter().flat_map(|word| word.to_be_bytes()).collect();
+    sha2::compress256(&mut state, &[*GenericArray::from_slice(&block_bytes)]);
+
+    insert_words(initial_witness, func, state, outputs)?;
+    Ok(OpcodeResolution::Solved)
+}
+
+/// Applies the Keccak-f\[1600\] permutation to the 25 lanes of `inputs`,
+/// the permuted lanes are assigned to `outputs`.
+pub fn keccakf1600(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    inputs: &[FunctionInput],
+    outputs: &[Witness],
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let func = BlackBoxFunc::Keccakf1600;
+    let mut state: [u64; 25] = to_words(initial_witness, func, inputs)?;
+
+    keccak::f1600(&mut state);
+
+    insert_words(initial_witness, func, state, outputs)?;
+    Ok(OpcodeResolution::Solved)
+}
+
+// Reads the values of `inputs` into an array of words,
+// returning an error if there are not `N` inputs or if a value does not fit into a word.
+fn to_words<W: TryFrom<u128> + Copy + Default, const N: usize>(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    inputs: &[FunctionInput],
+) -> Result<[W; N], OpcodeResolutionError> {
+    if inputs.len() != N {
+        return Err(OpcodeResolutionError::IncorrectNumFunctionArguments(N, func, inputs.len()));
+    }
+
+    let mut words = [W::default(); N];
+    for (word, input) in words.iter_mut().zip(inputs) {
+        let value = input_to_value(initial_witness, input)?;
+        *word =
+            value.try_into_u128().and_then(|value| W::try_from(value).ok()).ok_or_else(|| {
+                OpcodeResolutionError::BlackBoxFunctionFailed(
+                    func,
+                    format!(
+                        "input {value} does not fit into a {} bit word",
+                        std::mem::size_of::<W>() * 8
+                    ),
+                )
+            })?;
+    }
+    Ok(words)
+}
+
+// Assigns `words` to `outputs`, returning an error if there are not `N` outputs.
+fn insert_words<W: Into<u128>, const N: usize>(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    words: [W; N],
+    outputs: &[Witness],
+) -> Result<(), OpcodeResolutionError> {
+    if outputs.len() != N {
+        return Err(OpcodeResolutionError::IncorrectNumFunctionArguments(N, func, outputs.len()));
+    }
+
+    for (output_witness, word) in outputs.iter().zip(words) {
+        insert_value(output_witness, FieldElement::from(word.into()), initial_witness)?;
+    }
+    Ok(())
+}
+
+/// A two-to-one hash function used to compress the nodes of a merkle tree.
+///
+/// Backends can implement this using their native hash (e.g. Pedersen) in order to
+/// solve [`BlackBoxFunc::ComputeMerkleRoot`] with [`compute_merkle_root`].
+pub trait MerkleHasher {
+    fn hash(&self, left: &FieldElement, right: &FieldElement) -> FieldElement;
+}
+
+/// Compresses two nodes by hashing their big endian bytes with Blake2s
+/// and reducing the digest into a field element.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Blake2sMerkleHasher;
+
+impl MerkleHasher for Blake2sMerkleHasher {
+    fn hash(&self, left: &FieldElement, right: &FieldElement) -> FieldElement {
+        let mut hasher = Blake2s256::new();
+        hasher.update(left.to_be_bytes());
+        hasher.update(right.to_be_bytes());
+        FieldElement::from_be_bytes_reduce(&hasher.finalize())
+    }
+}
+
+/// Solves a [`BlackBoxFunc::ComputeMerkleRoot`] call using `hasher` to compress
+/// each level of the tree.
+///
+/// Returns an error if the leaf index does not fit into the depth of the provided hash path.
+pub fn compute_merkle_root(
+    hasher: &impl MerkleHasher,
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    leaf: &FunctionInput,
+    index: &FunctionInput,
+    hash_path: &[FunctionInput],
+    output: &Witness,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let index_value = input_to_value(initial_witness, index)?;
+    let depth = hash_path.len() as u32;
+    if index_value.num_bits() > depth {
+        return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            BlackBoxFunc::ComputeMerkleRoot,
+            format!("leaf index {index_value} does not fit into a merkle tree of depth {depth}"),
+        ));
+    }
+    let index_value = BigUint::from_bytes_be(&index_value.to_be_bytes());
+
+    let mut current = input_to_value(initial_witness, leaf)?;
+    for (level, sibling) in hash_path.iter().enumerate() {
+        let sibling = &input_to_value(initial_witness, sibling)?;
+        // If the bit is set, then the current node is the right child
+        current = if index_value.bit(level as u64) {
+            hasher.hash(sibling, &current)
+        } else {
+            hasher.hash(&current, sibling)
+        };
+    }
+
+    insert_value(output, current, initial_witness)?;
 
     Ok(OpcodeResolution::Solved)
 }
 
+/// Returns the inputs which make up the message of a hash function call.
+///
+/// If the call has a variable message size then only that many inputs are hashed,
+/// the remaining inputs are padding which fills the array up to its capacity.
+fn message_inputs<'a>(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    inputs: &'a [FunctionInput],
+    var_message_size: Option<&FunctionInput>,
+) -> Result<&'a [FunctionInput], OpcodeResolutionError> {
+    let message_size = match var_message_size {
+        Some(message_size) => input_to_value(initial_witness, message_size)?,
+        None => return Ok(inputs),
+    };
+    match message_size.try_into_u128() {
+        Some(size) if size <= inputs.len() as u128 => Ok(&inputs[..size as usize]),
+        _ => Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            func,
+            format!("message size {message_size} exceeds the {} provided inputs", inputs.len()),
+        )),
+    }
+}
+
+// Assigns `digest` to the outputs of a hash function call, in the shape of `output_mode`
+fn insert_digest(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    digest: &[u8; 32],
+    output_mode: HashOutputMode,
+    outputs: &[Witness],
+) -> Result<(), OpcodeResolutionError> {
+    let values: Vec<FieldElement> = match output_mode {
+        HashOutputMode::Bytes => {
+            digest.iter().map(|byte| FieldElement::from_be_bytes_reduce(&[*byte])).collect()
+        }
+        HashOutputMode::Limbs128 => {
+            digest.chunks(16).map(FieldElement::from_be_bytes_reduce).collect()
+        }
+        HashOutputMode::Field => vec![FieldElement::from_be_bytes_reduce(digest)],
+    };
+    if outputs.len() != values.len() {
+        return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            func,
+            format!(
+                "expected {} outputs for a {output_mode:?} digest, but got {}",
+                values.len(),
+                outputs.len()
+            ),
+        ));
+    }
+
+    for (output_witness, value) in outputs.iter().zip(values) {
+        insert_value(output_witness, value, initial_witness)?;
+    }
+    Ok(())
+}
+
 fn generic_hash_256<D: Digest>(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    func: BlackBoxFunc,
+    inputs: &[FunctionInput],
+    input_packing: HashInputPacking,
 ) -> Result<[u8; 32], OpcodeResolutionError> {
     let mut hasher = D::new();
 
-    // Read witness assignments into hasher.
-    for input in func_call.inputs.iter() {
-        let witness = input.witness;
-        let num_bits = input.num_bits as usize;
+    match input_packing {
+        HashInputPacking::Bytes => {
+            // Read witness assignments into hasher.
+            for input in inputs.iter() {
+                let num_bits = input.num_bits as usize;
 
-        let witness_assignment = witness_to_value(initial_witness, witness)?;
-        let bytes = witness_assignment.fetch_nearest_bytes(num_bits);
-        hasher.update(bytes);
+                let input_value = input_to_value(initial_witness, input)?;
+                let bytes = input_value.fetch_nearest_bytes(num_bits);
+                hasher.update(bytes);
+            }
+        }
+        HashInputPacking::Bits => {
+            hasher.update(pack_bits(initial_witness, func, inputs)?);
+        }
     }
 
     let result = hasher.finalize().as_slice().try_into().unwrap();
     Ok(result)
 }
+
+// Concatenates the `num_bits` least significant bits of each input, most significant bit first,
+// and splits the result into bytes.
+fn pack_bits(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    inputs: &[FunctionInput],
+) -> Result<Vec<u8>, OpcodeResolutionError> {
+    let mut message_bits = Vec::new();
+    for input in inputs {
+        let input_value = input_to_value(initial_witness, input)?;
+        if input_value.num_bits() > input.num_bits {
+            return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+                func,
+                format!("input {input_value} does not fit into {} bits", input.num_bits),
+            ));
+        }
+        let bits = input_value.bits();
+        // The input may have more bits than the field element, in which case the leading bits are zero
+        let num_leading_zeros = (input.num_bits as usize).saturating_sub(bits.len());
+        message_bits.extend(std::iter::repeat(false).take(num_leading_zeros));
+        message_bits.extend(&bits[bits.len().saturating_sub(input.num_bits as usize)..]);
+    }
+
+    if message_bits.len() % 8 != 0 {
+        return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+            func,
+            format!("message of {} bits cannot be split into whole bytes", message_bits.len()),
+        ));
+    }
+    let bytes = message_bits
+        .chunks(8)
+        .map(|byte_bits| byte_bits.iter().fold(0_u8, |byte, bit| (byte << 1) | *bit as u8))
+        .collect();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{
+        circuit::{
+            black_box_functions::{HashInputPacking, HashOutputMode},
+            opcodes::FunctionInput,
+        },
+        native_types::Witness,
+        BlackBoxFunc, FieldElement,
+    };
+
+    use sha2::{Digest, Sha256};
+
+    use super::{
+        compute_merkle_root, keccakf1600, sha256, sha256_compression, Blake2sMerkleHasher,
+        MerkleHasher,
+    };
+    use crate::OpcodeResolutionError;
+
+    // Solves a merkle root call on the tree of depth 2 whose leaf and index are
+    // stored in witnesses 1 and 2, and whose hash path is stored in witnesses 3 and 4.
+    fn solve_merkle_root(
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    ) -> Result<(), OpcodeResolutionError> {
+        let hash_path =
+            [FunctionInput::witness(Witness(3), 254), FunctionInput::witness(Witness(4), 254)];
+        compute_merkle_root(
+            &Blake2sMerkleHasher,
+            initial_witness,
+            &FunctionInput::witness(Witness(1), 254),
+            &FunctionInput::witness(Witness(2), 254),
+            &hash_path,
+            &Witness(5),
+        )
+        .map(|_| ())
+    }
+
+    #[test]
+    fn computes_merkle_root() {
+        let hasher = Blake2sMerkleHasher;
+        let leaf = FieldElement::from(10_u128);
+        let siblings = [FieldElement::from(20_u128), FieldElement::from(30_u128)];
+
+        // The leaf is the right child on the first level and the left child on the second level
+        let mut initial_witness = BTreeMap::from([
+            (Witness(1), leaf),
+            (Witness(2), FieldElement::from(1_u128)),
+            (Witness(3), siblings[0]),
+            (Witness(4), siblings[1]),
+        ]);
+        solve_merkle_root(&mut initial_witness).unwrap();
+
+        let expected_root = hasher.hash(&hasher.hash(&siblings[0], &leaf), &siblings[1]);
+        assert_eq!(initial_witness[&Witness(5)], expected_root);
+    }
+
+    #[test]
+    fn rejects_index_larger_than_tree() {
+        let mut initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(10_u128)),
+            (Witness(2), FieldElement::from(4_u128)),
+            (Witness(3), FieldElement::from(20_u128)),
+            (Witness(4), FieldElement::from(30_u128)),
+        ]);
+        let result = solve_merkle_root(&mut initial_witness);
+
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::ComputeMerkleRoot, _))
+        ));
+    }
+
+    #[test]
+    fn hashes_variable_length_message() {
+        let message = [1_u8, 2, 3, 4];
+        let inputs: Vec<_> =
+            (1..=4).map(|index| FunctionInput::witness(Witness(index), 8)).collect();
+        let message_size = FunctionInput::witness(Witness(5), 32);
+        let outputs: Vec<_> = (6..38).map(Witness).collect();
+
+        let initial_witness_with_size = |size: u128| {
+            let mut initial_witness: BTreeMap<_, _> = (1..=4)
+                .map(Witness)
+                .zip(message)
+                .map(|(witness, byte)| (witness, FieldElement::from(byte as u128)))
+                .collect();
+            initial_witness.insert(Witness(5), FieldElement::from(size));
+            initial_witness
+        };
+
+        // Only the first two bytes make up the message, the rest is padding
+        let mut initial_witness = initial_witness_with_size(2);
+        sha256(
+            &mut initial_witness,
+            &inputs,
+            Some(&message_size),
+            HashInputPacking::Bytes,
+            HashOutputMode::Bytes,
+            &outputs,
+        )
+        .unwrap();
+
+        let expected_hash = Sha256::digest(&message[..2]);
+        for (output, byte) in outputs.iter().zip(expected_hash) {
+            assert_eq!(initial_witness[output], FieldElement::from(byte as u128));
+        }
+
+        // The message cannot be longer than the inputs
+        let mut initial_witness = initial_witness_with_size(5);
+        let result = sha256(
+            &mut initial_witness,
+            &inputs,
+            Some(&message_size),
+            HashInputPacking::Bytes,
+            HashOutputMode::Bytes,
+            &outputs,
+        );
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::SHA256, _))
+        ));
+    }
+
+    #[test]
+    fn packs_digest_into_outputs() {
+        let inputs = [FunctionInput::constant(FieldElement::from(b'a' as u128), 8)];
+        let digest = Sha256::digest(b"a");
+
+        let mut initial_witness = BTreeMap::new();
+        let outputs = [Witness(1), Witness(2)];
+        sha256(
+            &mut initial_witness,
+            &inputs,
+            None,
+            HashInputPacking::Bytes,
+            HashOutputMode::Limbs128,
+            &outputs,
+        )
+        .unwrap();
+        assert_eq!(initial_witness[&Witness(1)], FieldElement::from_be_bytes_reduce(&digest[..16]));
+        assert_eq!(initial_witness[&Witness(2)], FieldElement::from_be_bytes_reduce(&digest[16..]));
+
+        let mut initial_witness = BTreeMap::new();
+        sha256(
+            &mut initial_witness,
+            &inputs,
+            None,
+            HashInputPacking::Bytes,
+            HashOutputMode::Field,
+            &[Witness(3)],
+        )
+        .unwrap();
+        assert_eq!(initial_witness[&Witness(3)], FieldElement::from_be_bytes_reduce(&digest));
+
+        // The number of outputs must match the output mode
+        let result = sha256(
+            &mut BTreeMap::new(),
+            &inputs,
+            None,
+            HashInputPacking::Bytes,
+            HashOutputMode::Field,
+            &outputs,
+        );
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::SHA256, _))
+        ));
+    }
+
+    #[test]
+    fn packs_inputs_at_bit_granularity() {
+        // The message is [0xab, 0xff]
+        let inputs = [
+            FunctionInput::constant(FieldElement::from(0xa_u128), 4),
+            FunctionInput::constant(FieldElement::from(0xb_u128), 4),
+            FunctionInput::constant(FieldElement::one(), 1),
+            FunctionInput::constant(FieldElement::from(0x7f_u128), 7),
+        ];
+        let outputs: Vec<_> = (0..32).map(Witness).collect();
+
+        let mut initial_witness = BTreeMap::new();
+        sha256(
+            &mut initial_witness,
+            &inputs,
+            None,
+            HashInputPacking::Bits,
+            HashOutputMode::Bytes,
+            &outputs,
+        )
+        .unwrap();
+
+        let expected_hash = Sha256::digest([0xab, 0xff]);
+        for (output, byte) in outputs.iter().zip(expected_hash) {
+            assert_eq!(initial_witness[output], FieldElement::from(byte as u128));
+        }
+
+        // The message must consist of whole bytes
+        let result = sha256(
+            &mut BTreeMap::new(),
+            &inputs[..3],
+            None,
+            HashInputPacking::Bits,
+            HashOutputMode::Bytes,
+            &outputs,
+        );
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::SHA256, _))
+        ));
+
+        // Inputs must fit into their number of bits
+        let inputs = [FunctionInput::constant(FieldElement::from(0x100_u128), 8)];
+        let result = sha256(
+            &mut BTreeMap::new(),
+            &inputs,
+            None,
+            HashInputPacking::Bits,
+            HashOutputMode::Bytes,
+            &outputs,
+        );
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::SHA256, _))
+        ));
+    }
+
+    #[test]
+    fn compresses_sha256_block() {
+        // The padded block of the message "abc"
+        let mut block = [0_u32; 16];
+        block[0] = 0x6162_6380;
+        block[15] = 0x18;
+        let initial_state: [u32; 8] = [
+            0x6a09_e667,
+            0xbb67_ae85,
+            0x3c6e_f372,
+            0xa54f_f53a,
+            0x510e_527f,
+            0x9b05_688c,
+            0x1f83_d9ab,
+            0x5be0_cd19,
+        ];
+
+        let inputs: Vec<_> =
+            (0..16).map(|index| FunctionInput::witness(Witness(index), 32)).collect();
+        let hash_values: Vec<_> =
+            (16..24).map(|index| FunctionInput::witness(Witness(index), 32)).collect();
+        let outputs: Vec<_> = (24..32).map(Witness).collect();
+        let mut initial_witness: BTreeMap<_, _> = (0..24)
+            .map(Witness)
+            .zip(block.iter().chain(&initial_state))
+            .map(|(witness, word)| (witness, FieldElement::from(*word as u128)))
+            .collect();
+
+        sha256_compression(&mut initial_witness, &inputs, &hash_values, &outputs).unwrap();
+
+        let expected_hash = Sha256::digest(b"abc");
+        for (output, expected_word) in outputs.iter().zip(expected_hash.chunks(4)) {
+            let expected_word = u32::from_be_bytes(expected_word.try_into().unwrap());
+            assert_eq!(initial_witness[output], FieldElement::from(expected_word as u128));
+        }
+    }
+
+    #[test]
+    fn permutes_keccak_state() {
+        let inputs: Vec<_> =
+            (0..25).map(|_| FunctionInput::constant(FieldElement::zero(), 64)).collect();
+        let outputs: Vec<_> = (0..25).map(Witness).collect();
+        let mut initial_witness = BTreeMap::new();
+
+        keccakf1600(&mut initial_witness, &inputs, &outputs).unwrap();
+
+        // The first two lanes of the permutation of the zero state
+        assert_eq!(initial_witness[&Witness(0)], FieldElement::from(0xF125_8F79_40E1_DDE7_u128));
+        assert_eq!(initial_witness[&Witness(1)], FieldElement::from(0x84D5_CCF9_33C0_478A_u128));
+
+        // Lanes must fit into 64 bits
+        let mut inputs = inputs;
+        inputs[0] = FunctionInput::constant(FieldElement::from(u64::MAX as u128 + 1), 64);
+        let result = keccakf1600(&mut BTreeMap::new(), &inputs, &outputs);
+        assert!(matches!(
+            result,
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(BlackBoxFunc::Keccakf1600, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_number_of_permutation_outputs() {
+        let words = |count: u32| -> Vec<_> {
+            (0..count).map(|_| FunctionInput::constant(FieldElement::zero(), 64)).collect()
+        };
+
+        for num_outputs in [7, 9] {
+            let outputs: Vec<_> = (0..num_outputs).map(Witness).collect();
+            let result = sha256_compression(&mut BTreeMap::new(), &words(16), &words(8), &outputs);
+            assert!(matches!(
+                result,
+                Err(OpcodeResolutionError::IncorrectNumFunctionArguments(
+                    8,
+                    BlackBoxFunc::Sha256Compression,
+                    got
+                )) if got == num_outputs as usize
+            ));
+        }
+
+        for num_outputs in [24, 26] {
+            let outputs: Vec<_> = (0..num_outputs).map(Witness).collect();
+            let result = keccakf1600(&mut BTreeMap::new(), &words(25), &outputs);
+            assert!(matches!(
+                result,
+                Err(OpcodeResolutionError::IncorrectNumFunctionArguments(
+                    25,
+                    BlackBoxFunc::Keccakf1600,
+                    got
+                )) if got == num_outputs as usize
+            ));
+        }
+    }
+}
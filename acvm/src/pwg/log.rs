@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+
+use acir::{
+    circuit::directives::{LogInfo, LogPlaceholder},
+    native_types::Witness,
+    FieldElement,
+};
+use num_bigint::BigUint;
+
+use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+use super::witness_to_value;
+
+/// A log emitted by a [Log][acir::circuit::directives::Directive::Log] directive while solving a circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// Index of the directive in the opcodes given to the solver.
+    ///
+    /// When the solver is restarted with the `unsolved_opcodes` of
+    /// [RequiresOracleData][crate::pwg::PartialWitnessGeneratorStatus::RequiresOracleData],
+    /// the index is relative to these opcodes rather than to the opcodes of the circuit.
+    pub opcode_index: usize,
+    pub output: LogOutput,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogOutput {
+    /// A string which was either known when the circuit was generated or formatted from witness values
+    String(String),
+    /// The values of the logged witnesses
+    Values(Vec<FieldElement>),
+}
+
+/// Receives the logs of a circuit while it is solved.
+///
+/// Closures taking a [`LogEvent`] can be used as sinks, for instance to collect the logs.
+pub trait LogSink {
+    fn log(&mut self, event: LogEvent);
+}
+
+impl<F: FnMut(LogEvent)> LogSink for F {
+    fn log(&mut self, event: LogEvent) {
+        self(event)
+    }
+}
+
+/// Prints the logs to standard output, a single value is printed as a hex string
+/// and multiple values as an array of hex strings.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutLogSink;
+
+impl LogSink for StdoutLogSink {
+    fn log(&mut self, event: LogEvent) {
+        match event.output {
+            LogOutput::String(output_string) => println!("{output_string}"),
+            LogOutput::Values(values) if values.len() == 1 => {
+                println!("{}", format_field_string(values[0]))
+            }
+            // If multiple witnesses are logged, it assumed that an array is meant to be printed
+            LogOutput::Values(values) => {
+                let elements_as_hex: Vec<_> = values.into_iter().map(format_field_string).collect();
+                println!("[{}]", elements_as_hex.join(", "))
+            }
+        }
+    }
+}
+
+/// Sends the output of the [Log][acir::circuit::directives::Directive::Log] directive at `opcode_index`
+/// to `log_sink`, once the values of its witnesses are known.
+pub fn solve_log(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    info: &LogInfo,
+    opcode_index: usize,
+    log_sink: &mut dyn LogSink,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    match log_output(initial_witness, info) {
+        Ok(output) => {
+            log_sink.log(LogEvent { opcode_index, output });
+            Ok(OpcodeResolution::Solved)
+        }
+        Err(OpcodeResolutionError::OpcodeNotSolvable(not_solvable)) => {
+            Ok(OpcodeResolution::Stalled(not_solvable))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn log_output(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    info: &LogInfo,
+) -> Result<LogOutput, OpcodeResolutionError> {
+    match info {
+        LogInfo::FinalizedOutput(output_string) => Ok(LogOutput::String(output_string.clone())),
+        LogInfo::WitnessOutput(witnesses) => {
+            Ok(LogOutput::Values(witness_values(initial_witness, witnesses)?))
+        }
+        LogInfo::FormattedOutput { template, placeholders, witnesses } => {
+            let expected = placeholders
+                .iter()
+                .try_fold(0_usize, |total, placeholder| {
+                    total.checked_add(placeholder.num_witnesses()?)
+                })
+                .ok_or_else(|| {
+                    OpcodeResolutionError::LogFailed(format!(
+                        "the placeholders of \"{template}\" hold too many witnesses"
+                    ))
+                })?;
+            if expected != witnesses.len() {
+                return Err(OpcodeResolutionError::LogFailed(format!(
+                    "the placeholders of \"{template}\" hold {expected} witnesses, but got {}",
+                    witnesses.len()
+                )));
+            }
+            let segments = split_template(template).map_err(OpcodeResolutionError::LogFailed)?;
+            if segments.len() != placeholders.len() + 1 {
+                return Err(OpcodeResolutionError::LogFailed(format!(
+                    "\"{template}\" has {} placeholders, but got {} types",
+                    segments.len() - 1,
+                    placeholders.len()
+                )));
+            }
+
+            let mut values = witness_values(initial_witness, witnesses)?.into_iter();
+            let mut output = segments[0].clone();
+            for (placeholder, segment) in placeholders.iter().zip(&segments[1..]) {
+                let value = format_value(placeholder, &mut values)
+                    .map_err(OpcodeResolutionError::LogFailed)?;
+                output.push_str(&value);
+                output.push_str(segment);
+            }
+            Ok(LogOutput::String(output))
+        }
+    }
+}
+
+fn witness_values(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    witnesses: &[Witness],
+) -> Result<Vec<FieldElement>, OpcodeResolutionError> {
+    witnesses.iter().map(|witness| witness_to_value(initial_witness, *witness).copied()).collect()
+}
+
+/// Splits a template into the text around its `{}` placeholders, unescaping `{{` and `}}`.
+fn split_template(template: &str) -> Result<Vec<String>, String> {
+    let mut segments = vec![String::new()];
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                segments.last_mut().unwrap().push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                segments.push(String::new());
+            }
+            ('{', _) | ('}', _) => return Err(format!("unmatched '{c}' in \"{template}\"")),
+            _ => segments.last_mut().unwrap().push(c),
+        }
+    }
+    Ok(segments)
+}
+
+/// Formats the next values as the type of `placeholder`
+fn format_value(
+    placeholder: &LogPlaceholder,
+    values: &mut impl Iterator<Item = FieldElement>,
+) -> Result<String, String> {
+    match placeholder {
+        LogPlaceholder::Field => Ok(format_field_string(next_value(values)?)),
+        LogPlaceholder::SignedInteger { bit_size } => {
+            let value = next_value(values)?;
+            if *bit_size == 0
+                || *bit_size >= FieldElement::max_num_bits()
+                || value.num_bits() > *bit_size
+            {
+                return Err(format!("{value} is not a signed integer of {bit_size} bits"));
+            }
+            // Values with their highest bit set are negative in two's complement
+            let value = BigUint::from_bytes_be(&value.to_be_bytes());
+            let modulus = BigUint::from(1u32) << *bit_size;
+            if value.bit(u64::from(*bit_size - 1)) {
+                Ok(format!("-{}", modulus - value))
+            } else {
+                Ok(value.to_string())
+            }
+        }
+        LogPlaceholder::String { len } => {
+            let bytes: Result<Vec<u8>, _> = (0..*len)
+                .map(|_| {
+                    let value = next_value(values)?;
+                    match value.try_to_u64() {
+                        Some(byte) if byte <= u8::MAX as u64 => Ok(byte as u8),
+                        _ => Err(format!("{value} is not a byte")),
+                    }
+                })
+                .collect();
+            String::from_utf8(bytes?).map_err(|err| err.to_string())
+        }
+        LogPlaceholder::Array { len, element } => {
+            let elements: Result<Vec<_>, _> =
+                (0..*len).map(|_| format_value(element, values)).collect();
+            Ok(format!("[{}]", elements?.join(", ")))
+        }
+    }
+}
+
+/// Returns the next value to format, or an error if the placeholders hold more values than the witnesses
+fn next_value(values: &mut impl Iterator<Item = FieldElement>) -> Result<FieldElement, String> {
+    values.next().ok_or_else(|| "not enough witnesses for the placeholders".to_owned())
+}
+
+/// This trims any leading zeroes.
+/// A singular '0' will be prepended as well if the trimmed string has an odd length.
+/// A hex string's length needs to be even to decode into bytes, as two digits correspond to
+/// one byte.
+fn format_field_string(field: FieldElement) -> String {
+    let mut trimmed_field = field.to_hex().trim_start_matches('0').to_owned();
+    if trimmed_field.len() % 2 != 0 {
+        trimmed_field = "0".to_owned() + &trimmed_field
+    }
+    "0x".to_owned() + &trimmed_field
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{
+        circuit::directives::{LogInfo, LogPlaceholder},
+        native_types::Witness,
+        FieldElement,
+    };
+
+    use super::{solve_log, LogEvent, LogOutput};
+    use crate::{pwg::OpcodeResolution, OpcodeNotSolvable, OpcodeResolutionError};
+
+    #[test]
+    fn sends_witness_values_to_the_sink() {
+        let info = LogInfo::WitnessOutput(vec![Witness(1), Witness(2)]);
+        let mut events = Vec::new();
+        let mut sink = |event| events.push(event);
+
+        let mut initial_witness = BTreeMap::from([(Witness(1), FieldElement::one())]);
+        assert_eq!(
+            solve_log(&initial_witness, &info, 3, &mut sink),
+            Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(2)))
+        );
+
+        initial_witness.insert(Witness(2), FieldElement::zero());
+        assert_eq!(solve_log(&initial_witness, &info, 3, &mut sink), Ok(OpcodeResolution::Solved));
+        assert_eq!(
+            events,
+            vec![LogEvent {
+                opcode_index: 3,
+                output: LogOutput::Values(vec![FieldElement::one(), FieldElement::zero()])
+            }]
+        );
+    }
+
+    #[test]
+    fn formats_witness_values_into_the_template() {
+        let info = LogInfo::FormattedOutput {
+            template: "{{{}}}: balance = {}, nonce = {}, tags = {}".to_owned(),
+            placeholders: vec![
+                LogPlaceholder::String { len: 2 },
+                LogPlaceholder::Field,
+                LogPlaceholder::SignedInteger { bit_size: 8 },
+                LogPlaceholder::Array {
+                    len: 2,
+                    element: Box::new(LogPlaceholder::SignedInteger { bit_size: 8 }),
+                },
+            ],
+            witnesses: (1..=6).map(Witness).collect(),
+        };
+        let values = [b'o' as u128, b'k' as u128, 255, 0xfe, 0x7f, 0x80];
+        let initial_witness = (1..=6)
+            .zip(values)
+            .map(|(witness, value)| (Witness(witness), FieldElement::from(value)))
+            .collect();
+
+        let mut events = Vec::new();
+        let mut sink = |event| events.push(event);
+        assert_eq!(solve_log(&initial_witness, &info, 0, &mut sink), Ok(OpcodeResolution::Solved));
+        assert_eq!(
+            events,
+            vec![LogEvent {
+                opcode_index: 0,
+                output: LogOutput::String(
+                    "{ok}: balance = 0xff, nonce = -2, tags = [127, -128]".to_owned()
+                )
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_formatted_logs() {
+        let initial_witness = BTreeMap::from([(Witness(1), FieldElement::from(256u128))]);
+        let log = |template: &str, placeholder| {
+            let info = LogInfo::FormattedOutput {
+                template: template.to_owned(),
+                placeholders: vec![placeholder],
+                witnesses: vec![Witness(1)],
+            };
+            solve_log(&initial_witness, &info, 0, &mut |_| {})
+        };
+
+        // The number of witnesses of this placeholder does not fit in a usize
+        let overflowing = (0..3).fold(LogPlaceholder::String { len: u32::MAX }, |element, _| {
+            LogPlaceholder::Array { len: u32::MAX, element: Box::new(element) }
+        });
+
+        assert!(log("{}", LogPlaceholder::Field).is_ok());
+        for (template, placeholder) in [
+            ("{} {}", LogPlaceholder::Field),
+            ("{", LogPlaceholder::Field),
+            ("{}", LogPlaceholder::String { len: 2 }),
+            ("{}", LogPlaceholder::String { len: 1 }),
+            ("{}", LogPlaceholder::SignedInteger { bit_size: 8 }),
+            ("{}", LogPlaceholder::SignedInteger { bit_size: u32::MAX }),
+            ("{}", overflowing),
+        ] {
+            assert!(matches!(log(template, placeholder), Err(OpcodeResolutionError::LogFailed(_))));
+        }
+
+        let info = LogInfo::FormattedOutput {
+            template: "{}".to_owned(),
+            placeholders: vec![LogPlaceholder::Field],
+            witnesses: vec![Witness(2)],
+        };
+        assert_eq!(
+            solve_log(&initial_witness, &info, 0, &mut |_| {}),
+            Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(2)))
+        );
+    }
+}
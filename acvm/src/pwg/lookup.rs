@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use acir::{
+    circuit::opcodes::{Lookup, LookupTable},
+    native_types::Witness,
+    FieldElement,
+};
+
+use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+use super::{arithmetic::ArithmeticSolver, get_value};
+
+/// Attempts to solve the [`Lookup`] opcode `lookup` against the lookup tables of the circuit.
+///
+/// Once the keys are known, the row they identify is used to solve the unknown values,
+/// or to check the values which are already known.
+///
+/// Returns `Err(OpcodeResolutionError)` if the table is not defined, cannot be looked up
+/// or the tuple is not one of its rows.
+pub fn solve_lookup(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    tables: &[LookupTable],
+    lookup: &Lookup,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    match solve_lookup_internal(initial_witness, tables, lookup) {
+        Ok(_) => Ok(OpcodeResolution::Solved),
+        Err(OpcodeResolutionError::OpcodeNotSolvable(unsolved)) => {
+            Ok(OpcodeResolution::Stalled(unsolved))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn solve_lookup_internal(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    tables: &[LookupTable],
+    lookup: &Lookup,
+) -> Result<(), OpcodeResolutionError> {
+    let failure =
+        |reason: String| OpcodeResolutionError::LookupFailed(lookup.table.clone(), reason);
+
+    let table = tables
+        .iter()
+        .find(|table| table.name == lookup.table)
+        .ok_or_else(|| failure("the table is not defined".to_owned()))?;
+    // Tables are checked in the same way by the fallback of lookups, so that both accept the same tuples
+    lookup.check_table(table).map_err(failure)?;
+
+    let keys = lookup
+        .keys
+        .iter()
+        .map(|key| get_value(key, initial_witness))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rows = table.rows.iter().filter(|row| row[..keys.len()] == keys[..]);
+
+    // When the values are already known they are checked against the row of the keys
+    let known_values: Option<Vec<FieldElement>> = lookup
+        .values
+        .iter()
+        .map(|value| ArithmeticSolver::evaluate(value, initial_witness).to_const())
+        .collect();
+    if let Some(values) = known_values {
+        return match rows.any(|row| row[keys.len()..] == values[..]) {
+            true => Ok(()),
+            false => Err(failure("the tuple is not a row of the table".to_owned())),
+        };
+    }
+
+    let row =
+        rows.next().ok_or_else(|| failure("no row of the table matches the keys".to_owned()))?;
+    for (value, expected) in lookup.values.iter().zip(&row[keys.len()..]) {
+        match ArithmeticSolver::solve(initial_witness, &(value.clone() - *expected)) {
+            Ok(OpcodeResolution::Stalled(not_solvable)) => return Err(not_solvable.into()),
+            Ok(_) => (),
+            Err(OpcodeResolutionError::UnsatisfiedConstrain) => {
+                return Err(failure("the values do not match the row of the keys".to_owned()))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{
+        circuit::opcodes::{Lookup, LookupTable},
+        native_types::{Expression, Witness},
+        FieldElement,
+    };
+
+    use super::solve_lookup;
+    use crate::{pwg::OpcodeResolution, OpcodeNotSolvable, OpcodeResolutionError};
+
+    fn square_table() -> LookupTable {
+        LookupTable {
+            name: "square".to_owned(),
+            rows: (0..8_u128)
+                .map(|x| vec![FieldElement::from(x), FieldElement::from(x * x)])
+                .collect(),
+        }
+    }
+
+    fn square_lookup() -> Lookup {
+        Lookup {
+            table: "square".to_owned(),
+            keys: vec![Expression::from(Witness(1))],
+            values: vec![Expression::from(Witness(2))],
+        }
+    }
+
+    #[test]
+    fn solves_values_from_keys() {
+        let tables = [square_table()];
+        let mut initial_witness = BTreeMap::new();
+        assert_eq!(
+            solve_lookup(&mut initial_witness, &tables, &square_lookup()),
+            Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(1)))
+        );
+
+        initial_witness.insert(Witness(1), FieldElement::from(5_u128));
+        assert_eq!(
+            solve_lookup(&mut initial_witness, &tables, &square_lookup()),
+            Ok(OpcodeResolution::Solved)
+        );
+        assert_eq!(initial_witness[&Witness(2)], FieldElement::from(25_u128));
+    }
+
+    #[test]
+    fn rejects_tuples_outside_of_the_table() {
+        let tables = [square_table()];
+
+        let mut initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(3_u128)),
+            (Witness(2), FieldElement::from(10_u128)),
+        ]);
+        assert!(matches!(
+            solve_lookup(&mut initial_witness, &tables, &square_lookup()),
+            Err(OpcodeResolutionError::LookupFailed(..))
+        ));
+
+        let mut initial_witness = BTreeMap::from([(Witness(1), FieldElement::from(8_u128))]);
+        assert!(matches!(
+            solve_lookup(&mut initial_witness, &tables, &square_lookup()),
+            Err(OpcodeResolutionError::LookupFailed(..))
+        ));
+
+        let mut initial_witness = BTreeMap::from([(Witness(1), FieldElement::from(3_u128))]);
+        assert!(matches!(
+            solve_lookup(&mut initial_witness, &[], &square_lookup()),
+            Err(OpcodeResolutionError::LookupFailed(..))
+        ));
+    }
+
+    #[test]
+    fn rejects_tables_with_duplicate_keys() {
+        let mut table = square_table();
+        table.rows.push(vec![FieldElement::from(3_u128), FieldElement::from(10_u128)]);
+        let tables = [table];
+
+        let mut initial_witness = BTreeMap::from([
+            (Witness(1), FieldElement::from(3_u128)),
+            (Witness(2), FieldElement::from(9_u128)),
+        ]);
+        assert!(matches!(
+            solve_lookup(&mut initial_witness, &tables, &square_lookup()),
+            Err(OpcodeResolutionError::LookupFailed(..))
+        ));
+    }
+}
@@ -6,13 +6,58 @@ use crate::{pwg::OpcodeResolution, OpcodeNotSolvable, OpcodeResolutionError};
 
 use super::{arithmetic::ArithmeticSolver, insert_value};
 
+/// Resolves the [oracles][acir::circuit::Opcode::Oracle] of a circuit while it is solved,
+/// instead of returning them to the caller.
+///
+/// Closures taking the name of an oracle and its input values can be used as resolvers.
+pub trait OracleResolver {
+    /// Returns the output values of the oracle `name` for the given input values,
+    /// or `None` if the oracle is unknown, in which case it is returned to the caller of the solver.
+    ///
+    /// Returns the reason for the failure if the oracle is known but cannot be resolved.
+    fn resolve(
+        &mut self,
+        name: &str,
+        inputs: &[FieldElement],
+    ) -> Result<Option<Vec<FieldElement>>, String>;
+}
+
+impl<F: FnMut(&str, &[FieldElement]) -> Result<Option<Vec<FieldElement>>, String>> OracleResolver
+    for F
+{
+    fn resolve(
+        &mut self,
+        name: &str,
+        inputs: &[FieldElement],
+    ) -> Result<Option<Vec<FieldElement>>, String> {
+        self(name, inputs)
+    }
+}
+
+/// Does not know any oracle, so that all of them are returned to the caller of the solver.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoOracleResolver;
+
+impl OracleResolver for NoOracleResolver {
+    fn resolve(
+        &mut self,
+        _name: &str,
+        _inputs: &[FieldElement],
+    ) -> Result<Option<Vec<FieldElement>>, String> {
+        Ok(None)
+    }
+}
+
 pub struct OracleSolver;
 
 impl OracleSolver {
     /// Derives the rest of the witness based on the initial low level variables
+    ///
+    /// Once the inputs are known, the output values which were not provided are requested from `oracle_resolver`.
     pub fn solve(
         initial_witness: &mut BTreeMap<Witness, FieldElement>,
         data: &mut OracleData,
+        oracle_resolver: &mut dyn OracleResolver,
     ) -> Result<OpcodeResolution, OpcodeResolutionError> {
         // Set input values
         for input in data.inputs.iter().skip(data.input_values.len()) {
@@ -26,6 +71,22 @@ impl OracleSolver {
 
         // If all of the inputs to the oracle have assignments
         if data.input_values.len() == data.inputs.len() {
+            if data.output_values.len() != data.outputs.len() {
+                let failed =
+                    |reason| OpcodeResolutionError::OracleFailed(data.name.clone(), reason);
+                if let Some(values) =
+                    oracle_resolver.resolve(&data.name, &data.input_values).map_err(failed)?
+                {
+                    if values.len() != data.outputs.len() {
+                        return Err(failed(format!(
+                            "expected {} outputs, but got {}",
+                            data.outputs.len(),
+                            values.len()
+                        )));
+                    }
+                    data.output_values = values;
+                }
+            }
             if data.output_values.len() == data.outputs.len() {
                 for (out, value) in data.outputs.iter().zip(data.output_values.iter()) {
                     insert_value(out, *value, initial_witness)?;
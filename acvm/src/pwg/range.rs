@@ -1,35 +1,60 @@
-use crate::{pwg::witness_to_value, pwg::OpcodeResolution, OpcodeResolutionError};
-use acir::{circuit::opcodes::BlackBoxFuncCall, native_types::Witness, BlackBoxFunc, FieldElement};
+use crate::{pwg::input_to_value, pwg::OpcodeResolution, OpcodeResolutionError};
+use acir::{circuit::opcodes::FunctionInput, native_types::Witness, FieldElement};
 use std::collections::BTreeMap;
 
 pub fn solve_range_opcode(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    func_call: &BlackBoxFuncCall,
+    input: &FunctionInput,
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    // TODO: this consistency check can be moved to a general function
-    let defined_input_size = BlackBoxFunc::RANGE
-        .definition()
-        .input_size
-        .fixed_size()
-        .expect("infallible: input for range gate is fixed");
-
-    let num_arguments = func_call.inputs.len();
-    if num_arguments != defined_input_size as usize {
-        return Err(OpcodeResolutionError::IncorrectNumFunctionArguments(
-            defined_input_size as usize,
-            BlackBoxFunc::RANGE,
-            num_arguments,
-        ));
+    let w_value = input_to_value(initial_witness, input)?;
+    if w_value.num_bits() > input.num_bits {
+        return Err(OpcodeResolutionError::UnsatisfiedConstrain);
     }
+    Ok(OpcodeResolution::Solved)
+}
 
-    // For the range constraint, we know that the input size should be one
-    assert_eq!(defined_input_size, 1);
-
-    let input = func_call.inputs.first().expect("infallible: checked that input size is 1");
-
-    let w_value = witness_to_value(initial_witness, input.witness)?;
-    if w_value.num_bits() > input.num_bits {
+/// Checks that the value of `input`, interpreted as an integer, lies between `min` and `max` inclusive.
+pub fn solve_bounded_range_opcode(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    input: &FunctionInput,
+    min: &FieldElement,
+    max: &FieldElement,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let w_value = input_to_value(initial_witness, input)?;
+    if w_value < *min || w_value > *max {
         return Err(OpcodeResolutionError::UnsatisfiedConstrain);
     }
     Ok(OpcodeResolution::Solved)
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{circuit::opcodes::FunctionInput, native_types::Witness, FieldElement};
+
+    use super::solve_bounded_range_opcode;
+    use crate::{pwg::OpcodeResolution, OpcodeResolutionError};
+
+    #[test]
+    fn checks_both_bounds() {
+        let min = FieldElement::from(10_u128);
+        let max = FieldElement::from(1000_u128);
+        let input = FunctionInput::witness(Witness(1), 10);
+
+        for (value, is_in_range) in
+            [(9_u128, false), (10, true), (500, true), (1000, true), (1001, false)]
+        {
+            let mut initial_witness = BTreeMap::from([(Witness(1), FieldElement::from(value))]);
+            let result = solve_bounded_range_opcode(&mut initial_witness, &input, &min, &max);
+            match is_in_range {
+                true => assert_eq!(result, Ok(OpcodeResolution::Solved)),
+                false => assert_eq!(result, Err(OpcodeResolutionError::UnsatisfiedConstrain)),
+            }
+        }
+
+        // Negative values are large integers
+        let mut initial_witness = BTreeMap::from([(Witness(1), -FieldElement::one())]);
+        assert!(solve_bounded_range_opcode(&mut initial_witness, &input, &min, &max).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::BTreeMap;
+
+use acir::{circuit::opcodes::RecursiveAggregation, native_types::Witness, FieldElement};
+
+use crate::{OpcodeNotSolvable, OpcodeResolutionError, PartialWitnessGenerator};
+
+use super::OpcodeResolution;
+
+/// Computes the output aggregation object of `aggregation` using the backend,
+/// once all of its inputs have been assigned.
+pub(super) fn solve(
+    backend: &impl PartialWitnessGenerator,
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    aggregation: &RecursiveAggregation,
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let inputs = aggregation.get_inputs_vec();
+    if let Some(unassigned_witness) =
+        inputs.iter().find(|witness| !initial_witness.contains_key(witness))
+    {
+        return Ok(OpcodeResolution::Stalled(OpcodeNotSolvable::MissingAssignment(
+            unassigned_witness.0,
+        )));
+    }
+
+    let RecursiveAggregation {
+        verification_key,
+        proof,
+        public_inputs,
+        input_aggregation_object,
+        output_aggregation_object,
+    } = aggregation;
+    backend.recursive_aggregation(
+        initial_witness,
+        verification_key,
+        proof,
+        public_inputs,
+        input_aggregation_object,
+        output_aggregation_object,
+    )
+}
@@ -1,63 +1,121 @@
-use acir::{circuit::opcodes::BlackBoxFuncCall, native_types::Witness, FieldElement};
+use acir::{
+    circuit::{black_box_functions::RecoverOutputMode, opcodes::FunctionInput},
+    native_types::Witness,
+    BlackBoxFunc, FieldElement,
+};
 use std::collections::BTreeMap;
 
-use crate::{pwg::witness_to_value, pwg::OpcodeResolution, OpcodeResolutionError};
+use crate::{pwg::input_to_value, pwg::insert_value, pwg::OpcodeResolution, OpcodeResolutionError};
+
+// Reads the byte values of `inputs`, returning an error if there are not exactly `N` of them
+fn to_u8_array<const N: usize>(
+    initial_witness: &BTreeMap<Witness, FieldElement>,
+    func: BlackBoxFunc,
+    inputs: &[FunctionInput],
+) -> Result<[u8; N], OpcodeResolutionError> {
+    if inputs.len() != N {
+        return Err(OpcodeResolutionError::IncorrectNumFunctionArguments(N, func, inputs.len()));
+    }
+
+    let mut result = [0u8; N];
+    for (byte, input) in result.iter_mut().zip(inputs) {
+        let input_value = input_to_value(initial_witness, input)?;
+        *byte = *input_value.to_be_bytes().last().unwrap();
+    }
+    Ok(result)
+}
 
 pub fn secp256k1_prehashed(
     initial_witness: &mut BTreeMap<Witness, FieldElement>,
-    gadget_call: &BlackBoxFuncCall,
+    public_key_x_inputs: &[FunctionInput],
+    public_key_y_inputs: &[FunctionInput],
+    signature_inputs: &[FunctionInput],
+    hashed_message_inputs: &[FunctionInput],
+    require_low_s: bool,
+    output: &Witness,
 ) -> Result<OpcodeResolution, OpcodeResolutionError> {
-    let mut inputs_iter = gadget_call.inputs.iter();
+    let func = BlackBoxFunc::EcdsaSecp256k1;
+    let pub_key_x: [u8; 32] = to_u8_array(initial_witness, func, public_key_x_inputs)?;
+    let pub_key_y: [u8; 32] = to_u8_array(initial_witness, func, public_key_y_inputs)?;
+    let signature: [u8; 64] = to_u8_array(initial_witness, func, signature_inputs)?;
+    let hashed_message: [u8; 32] = to_u8_array(initial_witness, func, hashed_message_inputs)?;
 
-    let mut pub_key_x = [0u8; 32];
-    for (i, pkx) in pub_key_x.iter_mut().enumerate() {
-        let _x_i = inputs_iter
-            .next()
-            .unwrap_or_else(|| panic!("pub_key_x should be 32 bytes long, found only {i} bytes"));
+    let result = ecdsa_secp256k1::verify_prehashed(
+        &hashed_message,
+        &pub_key_x,
+        &pub_key_y,
+        &signature,
+        require_low_s,
+    )
+    .is_ok();
 
-        let x_i = witness_to_value(initial_witness, _x_i.witness)?;
-        *pkx = *x_i.to_be_bytes().last().unwrap();
-    }
-
-    let mut pub_key_y = [0u8; 32];
-    for (i, pky) in pub_key_y.iter_mut().enumerate() {
-        let _y_i = inputs_iter
-            .next()
-            .unwrap_or_else(|| panic!("pub_key_y should be 32 bytes long, found only {i} bytes"));
+    initial_witness.insert(*output, FieldElement::from(result));
+    Ok(OpcodeResolution::Solved)
+}
 
-        let y_i = witness_to_value(initial_witness, _y_i.witness)?;
-        *pky = *y_i.to_be_bytes().last().unwrap();
+/// Recovers the public key which produced `signature_inputs` over `hashed_message_inputs`.
+///
+/// Depending on `output_mode`, either the 64 bytes of the public key
+/// or the 20 bytes of its Ethereum address are written to `outputs`.
+pub fn secp256k1_recover(
+    initial_witness: &mut BTreeMap<Witness, FieldElement>,
+    signature_inputs: &[FunctionInput],
+    recovery_id_input: &FunctionInput,
+    hashed_message_inputs: &[FunctionInput],
+    require_low_s: bool,
+    output_mode: RecoverOutputMode,
+    outputs: &[Witness],
+) -> Result<OpcodeResolution, OpcodeResolutionError> {
+    let func = BlackBoxFunc::EcdsaSecp256k1Recover;
+    let num_outputs = output_mode.output_size().0 as usize;
+    if outputs.len() != num_outputs {
+        return Err(OpcodeResolutionError::IncorrectNumFunctionArguments(
+            num_outputs,
+            func,
+            outputs.len(),
+        ));
     }
 
-    let mut signature = [0u8; 64];
-    for (i, sig) in signature.iter_mut().enumerate() {
-        let _sig_i = inputs_iter
-            .next()
-            .unwrap_or_else(|| panic!("signature should be 64 bytes long, found only {i} bytes"));
+    let signature: [u8; 64] = to_u8_array(initial_witness, func, signature_inputs)?;
+    let hashed_message: [u8; 32] = to_u8_array(initial_witness, func, hashed_message_inputs)?;
 
-        let sig_i = witness_to_value(initial_witness, _sig_i.witness)?;
-        *sig = *sig_i.to_be_bytes().last().unwrap()
-    }
+    let recovery_id = input_to_value(initial_witness, recovery_id_input)?;
+    let recovery_id = match recovery_id.try_to_u64() {
+        Some(recovery_id @ (0 | 1)) => recovery_id as u8,
+        _ => {
+            return Err(OpcodeResolutionError::BlackBoxFunctionFailed(
+                func,
+                format!("invalid recovery id {recovery_id}, expected 0 or 1"),
+            ))
+        }
+    };
 
-    let mut hashed_message = Vec::new();
-    for msg in inputs_iter {
-        let msg_i_field = witness_to_value(initial_witness, msg.witness)?;
-        let msg_i = *msg_i_field.to_be_bytes().last().unwrap();
-        hashed_message.push(msg_i);
-    }
+    let public_key =
+        ecdsa_secp256k1::recover_prehashed(&hashed_message, &signature, recovery_id, require_low_s)
+            .map_err(|reason| {
+                OpcodeResolutionError::BlackBoxFunctionFailed(func, reason.to_string())
+            })?;
 
-    let result =
-        ecdsa_secp256k1::verify_prehashed(&hashed_message, &pub_key_x, &pub_key_y, &signature)
-            .is_ok();
+    let output_bytes = match output_mode {
+        RecoverOutputMode::PublicKey => public_key.to_vec(),
+        RecoverOutputMode::Address => {
+            use sha3::{Digest, Keccak256};
+            // The address is the last 20 bytes of the hash of the public key
+            Keccak256::digest(public_key)[12..].to_vec()
+        }
+    };
 
-    initial_witness.insert(gadget_call.outputs[0], FieldElement::from(result));
+    for (output_witness, value) in outputs.iter().zip(output_bytes) {
+        insert_value(output_witness, FieldElement::from(value as u128), initial_witness)?;
+    }
     Ok(OpcodeResolution::Solved)
 }
 
 mod ecdsa_secp256k1 {
-    use std::convert::TryInto;
-
-    use k256::{ecdsa::Signature, Scalar};
+    use k256::{
+        ecdsa::{recoverable, Signature},
+        Scalar,
+    };
     use k256::{
         elliptic_curve::sec1::{Coordinates, ToEncodedPoint},
         AffinePoint, EncodedPoint, ProjectivePoint, PublicKey,
@@ -89,7 +147,14 @@ mod ecdsa_secp256k1 {
         {
             let signature_bytes: &[u8] = signature.as_ref();
             assert!(Signature::try_from(signature_bytes).unwrap() == signature);
-            verify_prehashed(&digest, x, y, signature_bytes).unwrap();
+            verify_prehashed(
+                &digest.into(),
+                x.as_slice().try_into().unwrap(),
+                y.as_slice().try_into().unwrap(),
+                signature_bytes.try_into().unwrap(),
+                true,
+            )
+            .unwrap();
         } else {
             unreachable!();
         }
@@ -98,33 +163,27 @@ mod ecdsa_secp256k1 {
     }
 
     /// Verify an ECDSA signature, given the hashed message
+    ///
+    /// Malformed signatures and public keys are reported as a failed verification.
     pub(super) fn verify_prehashed(
-        hashed_msg: &[u8],
-        public_key_x_bytes: &[u8],
-        public_key_y_bytes: &[u8],
-        signature: &[u8],
+        hashed_msg: &[u8; 32],
+        public_key_x_bytes: &[u8; 32],
+        public_key_y_bytes: &[u8; 32],
+        signature: &[u8; 64],
+        require_low_s: bool,
     ) -> Result<(), ()> {
         // Convert the inputs into k256 data structures
 
-        let signature = Signature::try_from(signature).unwrap();
-
-        let pub_key_x_arr: [u8; 32] = {
-            let pub_key_x_bytes: &[u8] = public_key_x_bytes;
-            pub_key_x_bytes.try_into().unwrap()
-        };
-        let pub_key_y_arr: [u8; 32] = {
-            let pub_key_y_bytes: &[u8] = public_key_y_bytes;
-            pub_key_y_bytes.try_into().unwrap()
-        };
+        let signature = Signature::try_from(signature.as_slice()).map_err(|_| ())?;
 
         let point = EncodedPoint::from_affine_coordinates(
-            &pub_key_x_arr.into(),
-            &pub_key_y_arr.into(),
+            &(*public_key_x_bytes).into(),
+            &(*public_key_y_bytes).into(),
             true,
         );
-        let pubkey = PublicKey::try_from(point).unwrap();
+        let pubkey = PublicKey::try_from(point).map_err(|_| ())?;
 
-        let z = Scalar::from_bytes_reduced(hashed_msg.into());
+        let z = Scalar::from_bytes_reduced(&(*hashed_msg).into());
 
         // Finished converting bytes into data structures
 
@@ -132,10 +191,11 @@ mod ecdsa_secp256k1 {
         let s = signature.s();
 
         // Ensure signature is "low S" normalized ala BIP 0062
-        if s.is_high().into() {
+        if require_low_s && bool::from(s.is_high()) {
             return Err(());
         }
 
+        // `s` is checked to be non-zero when parsing the signature
         let s_inv = s.invert().unwrap();
         let u1 = z * s_inv;
         let u2 = *r * s_inv;
@@ -152,4 +212,217 @@ mod ecdsa_secp256k1 {
         }
         Err(())
     }
+
+    /// Recover the uncompressed public key, `x` coordinate first, which signed the hashed message
+    pub(super) fn recover_prehashed(
+        hashed_msg: &[u8; 32],
+        signature: &[u8; 64],
+        recovery_id: u8,
+        require_low_s: bool,
+    ) -> Result<[u8; 64], &'static str> {
+        let signature =
+            Signature::try_from(signature.as_slice()).map_err(|_| "malformed signature")?;
+
+        // Ensure signature is "low S" normalized ala BIP 0062
+        if require_low_s && bool::from(signature.s().is_high()) {
+            return Err("signature has a high s value");
+        }
+
+        let recovery_id = recoverable::Id::new(recovery_id).map_err(|_| "invalid recovery id")?;
+        let signature = recoverable::Signature::new(&signature, recovery_id)
+            .map_err(|_| "malformed signature")?;
+        let verify_key = signature
+            .recover_verify_key_from_digest_bytes(&(*hashed_msg).into())
+            .map_err(|_| "no public key can be recovered from the signature")?;
+
+        match verify_key.to_encoded_point(false).coordinates() {
+            Coordinates::Uncompressed { x, y } => {
+                let mut public_key = [0u8; 64];
+                public_key[..32].copy_from_slice(x);
+                public_key[32..].copy_from_slice(y);
+                Ok(public_key)
+            }
+            _ => Err("no public key can be recovered from the signature"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use acir::{circuit::opcodes::FunctionInput, native_types::Witness, FieldElement};
+    use k256::{
+        ecdsa::{signature::Signer, Signature, SigningKey, VerifyingKey},
+        elliptic_curve::sec1::{Coordinates, ToEncodedPoint},
+    };
+    use sha2::{Digest, Sha256};
+
+    use super::{secp256k1_prehashed, secp256k1_recover, RecoverOutputMode};
+    use crate::OpcodeResolutionError;
+
+    // Returns the hashed message, the signature and the public key of a signed message
+    fn signed_message() -> ([u8; 32], [u8; 64], [u8; 64]) {
+        let signing_key = SigningKey::from_bytes(&[2u8; 32]).unwrap();
+        let message = b"ECDSA proves knowledge of a secret number";
+        // Signing hashes the message with SHA256
+        let signature: Signature = signing_key.sign(message);
+
+        let mut public_key = [0u8; 64];
+        let verify_key = VerifyingKey::from(&signing_key);
+        if let Coordinates::Uncompressed { x, y } = verify_key.to_encoded_point(false).coordinates()
+        {
+            public_key[..32].copy_from_slice(x);
+            public_key[32..].copy_from_slice(y);
+        }
+        (Sha256::digest(message).into(), signature.as_ref().try_into().unwrap(), public_key)
+    }
+
+    // Flips `s` to `n - s`, which results in a signature that is equally valid but has a high `s`
+    fn make_high_s(signature: [u8; 64]) -> [u8; 64] {
+        let signature = Signature::try_from(signature.as_slice()).unwrap();
+        let mut high_s_signature = [0u8; 64];
+        high_s_signature[..32].copy_from_slice(&signature.r().to_bytes());
+        high_s_signature[32..].copy_from_slice(&(-*signature.s()).to_bytes());
+        high_s_signature
+    }
+
+    // Assigns `bytes` to witnesses starting from `first_witness`
+    fn byte_inputs(
+        initial_witness: &mut BTreeMap<Witness, FieldElement>,
+        first_witness: u32,
+        bytes: &[u8],
+    ) -> Vec<FunctionInput> {
+        bytes
+            .iter()
+            .zip(first_witness..)
+            .map(|(byte, index)| {
+                initial_witness.insert(Witness(index), FieldElement::from(*byte as u128));
+                FunctionInput::witness(Witness(index), 8)
+            })
+            .collect()
+    }
+
+    fn verify(
+        hashed_message: &[u8; 32],
+        public_key: &[u8; 64],
+        signature: &[u8; 64],
+        require_low_s: bool,
+    ) -> FieldElement {
+        let mut initial_witness = BTreeMap::new();
+        let public_key_x = byte_inputs(&mut initial_witness, 1, &public_key[..32]);
+        let public_key_y = byte_inputs(&mut initial_witness, 33, &public_key[32..]);
+        let signature = byte_inputs(&mut initial_witness, 65, signature);
+        let hashed_message = byte_inputs(&mut initial_witness, 129, hashed_message);
+        secp256k1_prehashed(
+            &mut initial_witness,
+            &public_key_x,
+            &public_key_y,
+            &signature,
+            &hashed_message,
+            require_low_s,
+            &Witness(161),
+        )
+        .unwrap();
+        initial_witness[&Witness(161)]
+    }
+
+    fn recover(
+        hashed_message: &[u8; 32],
+        signature: &[u8; 64],
+        recovery_id: u8,
+        require_low_s: bool,
+        output_mode: RecoverOutputMode,
+    ) -> Result<Vec<u8>, OpcodeResolutionError> {
+        let mut initial_witness = BTreeMap::new();
+        let signature = byte_inputs(&mut initial_witness, 1, signature);
+        let recovery_id = byte_inputs(&mut initial_witness, 65, &[recovery_id]).remove(0);
+        let hashed_message = byte_inputs(&mut initial_witness, 66, hashed_message);
+        let num_outputs = output_mode.output_size().0 as u32;
+        let outputs: Vec<_> = (98..98 + num_outputs).map(Witness).collect();
+        secp256k1_recover(
+            &mut initial_witness,
+            &signature,
+            &recovery_id,
+            &hashed_message,
+            require_low_s,
+            output_mode,
+            &outputs,
+        )?;
+        Ok(outputs.iter().map(|output| initial_witness[output].to_u128() as u8).collect())
+    }
+
+    #[test]
+    fn verifies_signature_with_low_s_policy() {
+        let (hashed_message, signature, public_key) = signed_message();
+        assert_eq!(verify(&hashed_message, &public_key, &signature, true), FieldElement::one());
+
+        let high_s_signature = make_high_s(signature);
+        assert_eq!(
+            verify(&hashed_message, &public_key, &high_s_signature, true),
+            FieldElement::zero()
+        );
+        assert_eq!(
+            verify(&hashed_message, &public_key, &high_s_signature, false),
+            FieldElement::one()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_inputs_without_panicking() {
+        let (hashed_message, signature, public_key) = signed_message();
+        // The point (0, 0) is not on the curve
+        assert_eq!(verify(&hashed_message, &[0; 64], &signature, true), FieldElement::zero());
+        // A zero `r` is not a valid signature
+        assert_eq!(verify(&hashed_message, &public_key, &[0; 64], true), FieldElement::zero());
+        assert!(matches!(
+            recover(&hashed_message, &[0; 64], 0, true, RecoverOutputMode::PublicKey),
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(..))
+        ));
+        assert!(matches!(
+            recover(&hashed_message, &signature, 2, true, RecoverOutputMode::PublicKey),
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(..))
+        ));
+    }
+
+    #[test]
+    fn recovers_public_key_and_address() {
+        let (hashed_message, signature, public_key) = signed_message();
+        let recovery_id = (0..2)
+            .find(|&id| {
+                recover(&hashed_message, &signature, id, true, RecoverOutputMode::PublicKey)
+                    .unwrap()
+                    == public_key
+            })
+            .expect("one of the recovery ids should recover the public key");
+
+        let address =
+            recover(&hashed_message, &signature, recovery_id, true, RecoverOutputMode::Address)
+                .unwrap();
+        assert_eq!(address, sha3::Keccak256::digest(public_key)[12..]);
+
+        // The high `s` signature of the same message recovers the same key with the opposite recovery id
+        let high_s_signature = make_high_s(signature);
+        assert!(matches!(
+            recover(
+                &hashed_message,
+                &high_s_signature,
+                1 - recovery_id,
+                true,
+                RecoverOutputMode::PublicKey
+            ),
+            Err(OpcodeResolutionError::BlackBoxFunctionFailed(..))
+        ));
+        assert_eq!(
+            recover(
+                &hashed_message,
+                &high_s_signature,
+                1 - recovery_id,
+                false,
+                RecoverOutputMode::PublicKey
+            )
+            .unwrap(),
+            public_key
+        );
+    }
 }
@@ -1,6 +1,10 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use acir::FieldElement;
+use acir::{circuit::directives::SortKey, FieldElement};
+use num_bigint::BigInt;
 
 // A sorting network is a graph of connected switches
 // It is defined recursively so here we only keep track of the outer layer of switches
@@ -156,6 +160,17 @@ impl SortingNetwork {
     }
 }
 
+// Compares the keys of two tuples of the PermutationSort directive, from the primary key to the last one
+pub fn compare_keys(a: &[BigInt], b: &[BigInt], sort_by: &[SortKey]) -> Ordering {
+    for ((key_a, key_b), key) in a.iter().zip(b).zip(sort_by) {
+        let cmp = if key.descending { key_b.cmp(key_a) } else { key_a.cmp(key_b) };
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+    }
+    Ordering::Equal
+}
+
 // Computes the control bits of the sorting network which transform inputs into outputs
 // implementation is based on https://www.mdpi.com/2227-7080/10/1/16
 pub fn route(inputs: Vec<FieldElement>, outputs: Vec<FieldElement>) -> Vec<bool> {
@@ -245,8 +260,14 @@ pub fn route(inputs: Vec<FieldElement>, outputs: Vec<FieldElement>) -> Vec<bool>
 
 #[cfg(test)]
 mod test {
-    use crate::pwg::sorting::route;
-    use acir::FieldElement;
+    use std::collections::BTreeMap;
+
+    use crate::pwg::{directives::solve_directives, sorting::route};
+    use acir::{
+        circuit::directives::{Directive, SortKey},
+        native_types::{Expression, Witness},
+        FieldElement,
+    };
     use rand::prelude::*;
 
     fn execute_network(config: Vec<bool>, inputs: Vec<FieldElement>) -> Vec<FieldElement> {
@@ -389,4 +410,34 @@ mod test {
             assert_eq!(b, execute_network(c, a));
         }
     }
+
+    #[test]
+    fn sorts_by_descending_signed_keys() {
+        // (x, y) tuples sorted by x as descending 8-bit signed integers, then by y
+        let tuples: [(u128, u128); 5] = [(1, 5), (0xff, 2), (1, 3), (0x7f, 0), (0x80, 1)];
+        let inputs = tuples
+            .iter()
+            .map(|(x, y)| {
+                vec![Expression::from_field((*x).into()), Expression::from_field((*y).into())]
+            })
+            .collect();
+        let bits: Vec<_> = (0..switch_nb(tuples.len()) as u32).map(Witness).collect();
+        let directive = Directive::PermutationSort {
+            inputs,
+            tuple: 2,
+            bits: bits.clone(),
+            sort_by: vec![
+                SortKey { index: 0, descending: true, signed_bit_size: Some(8) },
+                SortKey::ascending(1),
+            ],
+        };
+        let mut initial_witness = BTreeMap::new();
+        solve_directives(&mut initial_witness, &directive).unwrap();
+
+        let config = bits.iter().map(|bit| initial_witness[bit].is_one()).collect();
+        let ys = tuples.iter().map(|(_, y)| FieldElement::from(*y)).collect();
+        let sorted: Vec<_> = execute_network(config, ys).into_iter().map(|y| y.to_u128()).collect();
+        // 127, 1, 1, -1, -128
+        assert_eq!(sorted, vec![0, 3, 5, 2, 1]);
+    }
 }
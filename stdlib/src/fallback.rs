@@ -1,10 +1,21 @@
 use crate::helpers::VariableStore;
 use acir::{
     acir_field::FieldElement,
-    circuit::{directives::Directive, Opcode},
+    circuit::{
+        directives::{is_valid_radix, Directive},
+        opcodes::check_range_bounds,
+        Opcode,
+    },
     native_types::{Expression, Witness},
 };
 
+mod bigint;
+mod lookup;
+mod memory;
+pub use bigint::bigint;
+pub use lookup::lookup;
+pub use memory::{ram, rom};
+
 // Perform bit decomposition on the provided expression
 #[deprecated(note = "use bit_decomposition function instead")]
 pub fn split(
@@ -18,6 +29,15 @@ pub fn split(
     bits
 }
 
+/// The order of the digits of a decomposition
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    /// The least significant digit comes first
+    Little,
+    /// The most significant digit comes first
+    Big,
+}
+
 // Generates opcodes and directives to bit decompose the input `gate`
 // Returns the bits and the updated witness counter
 // TODO:Ideally, we return the updated witness counter, or we require the input
@@ -26,49 +46,105 @@ pub fn split(
 pub(crate) fn bit_decomposition(
     gate: Expression,
     bit_size: u32,
+    num_witness: u32,
+) -> (Vec<Opcode>, Vec<Witness>, u32) {
+    radix_decomposition(gate, 2, bit_size, Endianness::Little, num_witness)
+}
+
+// Generates opcodes and directives to decompose the input `gate` into `num_digits` digits in base `radix`
+// Returns the digits, ordered according to `endianness`, and the updated witness counter
+pub fn radix_decomposition(
+    gate: Expression,
+    radix: u32,
+    num_digits: u32,
+    endianness: Endianness,
     mut num_witness: u32,
 ) -> (Vec<Opcode>, Vec<Witness>, u32) {
+    assert!(is_valid_radix(radix), "cannot decompose in base {radix}");
     let mut new_gates = Vec::new();
     let mut variables = VariableStore::new(&mut num_witness);
 
-    // First create a witness for each bit
-    let mut bit_vector = Vec::with_capacity(bit_size as usize);
-    for _ in 0..bit_size {
-        bit_vector.push(variables.new_variable())
+    // First create a witness for each digit
+    let mut digits = Vec::with_capacity(num_digits as usize);
+    for _ in 0..num_digits {
+        digits.push(variables.new_variable())
     }
+    let mut num_witness = variables.finalize();
 
-    // Next create a directive which computes those bits.
-    new_gates.push(Opcode::Directive(Directive::ToLeRadix {
-        a: gate.clone(),
-        b: bit_vector.clone(),
-        radix: 2,
+    // Next create a directive which computes those digits.
+    new_gates.push(Opcode::Directive(match endianness {
+        Endianness::Little => Directive::ToLeRadix { a: gate.clone(), b: digits.clone(), radix },
+        Endianness::Big => Directive::ToBeRadix { a: gate.clone(), b: digits.clone(), radix },
     }));
 
-    // Now apply constraints to the bits such that they are the bit decomposition
-    // of the input and each bit is actually a bit
-    let mut binary_exprs = Vec::new();
-    let mut bit_decomp_constraint = gate;
-    let mut two_pow: FieldElement = FieldElement::one();
-    let two = FieldElement::from(2_i128);
-    for &bit in &bit_vector {
-        // Bit constraint to ensure each bit is a zero or one; bit^2 - bit = 0
-        let mut expr = Expression::default();
-        expr.push_multiplication_term(FieldElement::one(), bit, bit);
-        expr.push_addition_term(-FieldElement::one(), bit);
-        binary_exprs.push(Opcode::Arithmetic(expr));
-
-        // Constraint to ensure that the bits are constrained to be a bit decomposition
+    // Now apply constraints to the digits such that they are the decomposition
+    // of the input and each digit is less than the radix
+    let mut digit_exprs = Vec::new();
+    let mut decomp_constraint = gate;
+    let mut radix_pow: FieldElement = FieldElement::one();
+    let field_radix = FieldElement::from(radix as u128);
+    let mut le_digits = digits.clone();
+    if endianness == Endianness::Big {
+        le_digits.reverse();
+    }
+    for digit in le_digits {
+        if radix == 2 {
+            // Bit constraint to ensure each bit is a zero or one; bit^2 - bit = 0
+            let mut expr = Expression::default();
+            expr.push_multiplication_term(FieldElement::one(), digit, digit);
+            expr.push_addition_term(-FieldElement::one(), digit);
+            digit_exprs.push(Opcode::Arithmetic(expr));
+        } else {
+            let (updated_witness_counter, range_gates) = bounded_range(
+                Expression::from(digit),
+                FieldElement::zero(),
+                field_radix - FieldElement::one(),
+                num_witness,
+            )
+            .expect("the digits of a valid radix have valid bounds");
+            num_witness = updated_witness_counter;
+            digit_exprs.extend(range_gates);
+        }
+
+        // Constraint to ensure that the digits are constrained to be a decomposition
         // of the input
-        // ie \sum 2^i * x_i = input
-        bit_decomp_constraint.push_addition_term(-two_pow, bit);
-        two_pow = two * two_pow;
+        // ie \sum radix^i * x_i = input
+        decomp_constraint.push_addition_term(-radix_pow, digit);
+        radix_pow = field_radix * radix_pow;
     }
 
-    new_gates.extend(binary_exprs);
-    bit_decomp_constraint.sort(); // TODO: we have an issue open to check if this is needed. Ideally, we remove it.
-    new_gates.push(Opcode::Arithmetic(bit_decomp_constraint));
+    new_gates.extend(digit_exprs);
+    decomp_constraint.sort(); // TODO: we have an issue open to check if this is needed. Ideally, we remove it.
+    new_gates.push(Opcode::Arithmetic(decomp_constraint));
 
-    (new_gates, bit_vector, variables.finalize())
+    (new_gates, digits, num_witness)
+}
+
+// Decomposes `gate` into `bit_size` bits, returning each bit as an expression.
+// Constants are decomposed at compile time, so they do not require any opcodes or new witnesses.
+fn operand_bit_decomposition(
+    gate: Expression,
+    bit_size: u32,
+    num_witness: u32,
+) -> (Vec<Opcode>, Vec<Expression>, u32) {
+    match gate.to_const() {
+        Some(constant) => {
+            let bits = constant.bits();
+            // `bits` is in big endian form, so we iterate over it in reverse.
+            let bits = (0..bit_size as usize)
+                .map(|i| {
+                    let bit = i < bits.len() && bits[bits.len() - 1 - i];
+                    Expression::from_field(FieldElement::from(bit))
+                })
+                .collect();
+            (Vec::new(), bits, num_witness)
+        }
+        None => {
+            let (new_gates, bits, updated_witness_counter) =
+                bit_decomposition(gate, bit_size, num_witness);
+            (new_gates, bits.into_iter().map(Expression::from).collect(), updated_witness_counter)
+        }
+    }
 }
 
 // Range constraint
@@ -77,6 +153,33 @@ pub fn range(gate: Expression, bit_size: u32, num_witness: u32) -> (u32, Vec<Opc
     (updated_witness_counter, new_gates)
 }
 
+// Constrains `min <= gate <= max` as integers, where the bounds are constants.
+//
+// Both `gate - min` and `max - gate` are range constrained to the bit size of `max - min`.
+// Since their sum is `max - min`, neither of them can wrap around the field modulus.
+// When `max - min` is one less than a power of two, the second range constraint is implied by the first.
+//
+// Returns an error if the bounds cannot be constrained, see `check_range_bounds`.
+pub fn bounded_range(
+    gate: Expression,
+    min: FieldElement,
+    max: FieldElement,
+    num_witness: u32,
+) -> Result<(u32, Vec<Opcode>), String> {
+    check_range_bounds(min, max)?;
+    let difference = max - min;
+    let bit_size = difference.num_bits();
+
+    let (num_witness, mut new_gates) = range(gate.clone() - min, bit_size, num_witness);
+    if (difference + FieldElement::one()).num_bits() > bit_size {
+        return Ok((num_witness, new_gates));
+    }
+    let (num_witness, upper_gates) =
+        range(&Expression::from_field(max) - &gate, bit_size, num_witness);
+    new_gates.extend(upper_gates);
+    Ok((num_witness, new_gates))
+}
+
 pub fn and(
     a: Expression,
     b: Expression,
@@ -87,10 +190,10 @@ pub fn and(
     // Decompose the operands into bits
     //
     let (extra_gates_a, a_bits, updated_witness_counter) =
-        bit_decomposition(a, bit_size, num_witness);
+        operand_bit_decomposition(a, bit_size, num_witness);
 
     let (extra_gates_b, b_bits, updated_witness_counter) =
-        bit_decomposition(b, bit_size, updated_witness_counter);
+        operand_bit_decomposition(b, bit_size, updated_witness_counter);
 
     assert_eq!(a_bits.len(), b_bits.len());
     assert_eq!(a_bits.len(), bit_size as usize);
@@ -103,8 +206,8 @@ pub fn and(
     // Additionally, we multiply by a power of 2 to build up the
     // expected output; ie result = \sum 2^i x_i * y_i
     let mut and_expr = Expression::default();
-    for (a_bit, b_bit) in a_bits.into_iter().zip(b_bits) {
-        and_expr.push_multiplication_term(two_pow, a_bit, b_bit);
+    for (a_bit, b_bit) in a_bits.iter().zip(&b_bits) {
+        and_expr = and_expr.add_mul(two_pow, &(a_bit * b_bit));
         two_pow = two * two_pow;
     }
     and_expr.push_addition_term(-FieldElement::one(), result);
@@ -129,9 +232,9 @@ pub fn xor(
     // Decompose the operands into bits
     //
     let (extra_gates_a, a_bits, updated_witness_counter) =
-        bit_decomposition(a, bit_size, num_witness);
+        operand_bit_decomposition(a, bit_size, num_witness);
     let (extra_gates_b, b_bits, updated_witness_counter) =
-        bit_decomposition(b, bit_size, updated_witness_counter);
+        operand_bit_decomposition(b, bit_size, updated_witness_counter);
 
     assert_eq!(a_bits.len(), b_bits.len());
     assert_eq!(a_bits.len(), bit_size as usize);
@@ -142,11 +245,11 @@ pub fn xor(
     // Build an xor expression
     // TODO: check this is the correct arithmetization
     let mut xor_expr = Expression::default();
-    for (a_bit, b_bit) in a_bits.into_iter().zip(b_bits) {
-        xor_expr.push_addition_term(two_pow, a_bit);
-        xor_expr.push_addition_term(two_pow, b_bit);
+    for (a_bit, b_bit) in a_bits.iter().zip(&b_bits) {
+        xor_expr = xor_expr.add_mul(two_pow, a_bit);
+        xor_expr = xor_expr.add_mul(two_pow, b_bit);
         two_pow = two * two_pow;
-        xor_expr.push_multiplication_term(-two_pow, a_bit, b_bit);
+        xor_expr = xor_expr.add_mul(-two_pow, &(a_bit * b_bit));
     }
     xor_expr.push_addition_term(-FieldElement::one(), result);
 
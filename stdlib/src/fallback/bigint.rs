@@ -0,0 +1,300 @@
+use super::range;
+use crate::helpers::VariableStore;
+use acir::{
+    acir_field::FieldElement,
+    circuit::{
+        directives::Directive,
+        opcodes::{BigIntCall, BigIntOp},
+        Opcode,
+    },
+    native_types::{Expression, Witness},
+};
+use num_bigint::BigUint;
+use num_traits::One;
+
+// Tracks the opcodes which constrain integers represented as little endian limbs of `limb_bits` bits
+struct LimbConstraints {
+    limb_bits: u32,
+    num_witness: u32,
+    opcodes: Vec<Opcode>,
+}
+
+fn two_pow(exponent: u32) -> FieldElement {
+    FieldElement::from(2_i128).pow(&FieldElement::from(exponent as i128))
+}
+
+// Number of bits needed to represent `n - 1`, so that a sum of `n` values of `b` bits fits in `b + ceil_log2(n)` bits
+fn ceil_log2(n: usize) -> u32 {
+    usize::BITS - n.saturating_sub(1).leading_zeros()
+}
+
+impl LimbConstraints {
+    fn new_limbs(&mut self, num_limbs: usize) -> Vec<Witness> {
+        let mut variables = VariableStore::new(&mut self.num_witness);
+        (0..num_limbs).map(|_| variables.new_variable()).collect()
+    }
+
+    fn range(&mut self, expr: Expression, bit_size: u32) {
+        let (updated_witness_counter, range_opcodes) = range(expr, bit_size, self.num_witness);
+        self.num_witness = updated_witness_counter;
+        self.opcodes.extend(range_opcodes);
+    }
+
+    fn range_limbs(&mut self, limbs: &[Witness]) {
+        for limb in limbs {
+            self.range(Expression::from(*limb), self.limb_bits);
+        }
+    }
+
+    // Splits the constant `value` into `num_limbs` limbs
+    fn constant_limbs(&self, value: &BigUint, num_limbs: usize) -> Vec<FieldElement> {
+        let mask = (BigUint::one() << self.limb_bits) - 1_u32;
+        (0..num_limbs)
+            .map(|i| {
+                let limb = (value >> (i as u32 * self.limb_bits)) & &mask;
+                FieldElement::from_be_bytes_reduce(&limb.to_bytes_be())
+            })
+            .collect()
+    }
+
+    // Computes the limbs of the quotient and the rest of the division of `\sum columns[i] * 2^(limb_bits * i)` by `modulus`
+    fn quotient(
+        &mut self,
+        columns: Vec<Expression>,
+        modulus: &BigUint,
+        q: Vec<Witness>,
+        r: Vec<Witness>,
+    ) {
+        self.opcodes.push(Opcode::Directive(Directive::BigIntQuotient {
+            a: columns,
+            limb_bits: self.limb_bits,
+            modulus: modulus.to_bytes_be(),
+            q,
+            r,
+        }));
+    }
+
+    // Constrains `\sum columns[i] * 2^(limb_bits * i)` to be zero as an integer,
+    // where each column is a signed integer with an absolute value below `2^column_bits`.
+    //
+    // Each column is shifted into the next one with a carry, the carries are range constrained
+    // so that the constraints cannot be satisfied modulo the field but not over the integers.
+    fn assert_zero(&mut self, columns: Vec<Expression>, column_bits: u32) -> Result<(), String> {
+        if column_bits + 2 >= FieldElement::max_num_bits() {
+            return Err(format!("limbs of {} bits are too large for the field", self.limb_bits));
+        }
+        // The carries are signed, so they are offset before being range constrained
+        let carry_bits = column_bits.saturating_sub(self.limb_bits) + 2;
+        let carry_offset = two_pow(carry_bits - 1);
+
+        let num_columns = columns.len();
+        let mut carry: Option<Witness> = None;
+        for (i, column) in columns.into_iter().enumerate() {
+            let mut expr = match carry {
+                Some(carry) => &column + carry,
+                None => column,
+            };
+            if i + 1 < num_columns {
+                let next_carry = self.new_limbs(1)[0];
+                expr.push_addition_term(-two_pow(self.limb_bits), next_carry);
+
+                let mut offset_carry = Expression::from(next_carry);
+                offset_carry.q_c = carry_offset;
+                self.range(offset_carry, carry_bits);
+                carry = Some(next_carry);
+            }
+            expr.sort();
+            self.opcodes.push(Opcode::Arithmetic(expr));
+        }
+        Ok(())
+    }
+
+    // Constrains `output` to be `a % modulus`, where `a = \sum a_columns[i] * 2^(limb_bits * i)` has
+    // at most `a_bits` bits and non-negative columns below `2^a_column_bits`.
+    fn mod_reduce(
+        &mut self,
+        a_columns: Vec<Expression>,
+        a_bits: u32,
+        a_column_bits: u32,
+        modulus: &BigUint,
+        output: &[Witness],
+    ) -> Result<(), String> {
+        let limb_bits = self.limb_bits;
+        let modulus_bits = modulus.bits() as u32;
+        if (output.len() as u64) * (limb_bits as u64) < modulus_bits as u64 {
+            return Err(format!(
+                "the output must have enough limbs to hold the modulus, got {} limbs of {limb_bits} bits",
+                output.len()
+            ));
+        }
+        let modulus_limbs =
+            self.constant_limbs(modulus, ((modulus_bits + limb_bits - 1) / limb_bits) as usize);
+
+        // a = q * modulus + output
+        let quotient_bits = a_bits.saturating_sub(modulus_bits - 1);
+        let q = self.new_limbs(((quotient_bits + limb_bits - 1) / limb_bits) as usize);
+        self.quotient(a_columns.clone(), modulus, q.clone(), output.to_vec());
+        self.range_limbs(&q);
+        self.range_limbs(output);
+
+        let num_columns = a_columns.len().max(q.len() + modulus_limbs.len() - 1).max(output.len());
+        let mut columns = a_columns;
+        columns.resize(num_columns, Expression::default());
+        for (i, q_limb) in q.iter().enumerate() {
+            for (j, modulus_limb) in modulus_limbs.iter().enumerate() {
+                columns[i + j].push_addition_term(-*modulus_limb, *q_limb);
+            }
+        }
+        for (column, output_limb) in columns.iter_mut().zip(output) {
+            column.push_addition_term(-FieldElement::one(), *output_limb);
+        }
+        let product_column_bits = 2 * limb_bits + ceil_log2(q.len().min(modulus_limbs.len()));
+        self.assert_zero(columns, a_column_bits.max(product_column_bits) + 1)?;
+
+        // output < modulus, i.e. modulus - 1 - output = s for some non-negative s
+        let max_output = modulus - 1_u32;
+        let s = self.new_limbs(output.len());
+        let differences: Vec<_> = self
+            .constant_limbs(&max_output, output.len())
+            .into_iter()
+            .zip(output)
+            .map(|(max_limb, output_limb)| {
+                let mut column = Expression::from_field(max_limb);
+                column.push_addition_term(-FieldElement::one(), *output_limb);
+                column
+            })
+            .collect();
+        let limbs_modulus = BigUint::one() << (limb_bits * output.len() as u32);
+        self.quotient(differences.clone(), &limbs_modulus, Vec::new(), s.clone());
+        self.range_limbs(&s);
+        let columns = differences
+            .into_iter()
+            .zip(&s)
+            .map(|(difference, s_limb)| {
+                let mut column = difference;
+                column.push_addition_term(-FieldElement::one(), *s_limb);
+                column
+            })
+            .collect();
+        self.assert_zero(columns, limb_bits + 2)
+    }
+
+    // Constrains `output` to be 1 if `lhs < rhs` and 0 otherwise
+    fn compare(&mut self, lhs: &[Witness], rhs: &[Witness], output: Witness) -> Result<(), String> {
+        let num_limbs = lhs.len().max(rhs.len());
+        let limb_difference = |i: usize| {
+            let mut column = Expression::default();
+            if let Some(lhs_limb) = lhs.get(i) {
+                column.push_addition_term(FieldElement::one(), *lhs_limb);
+            }
+            if let Some(rhs_limb) = rhs.get(i) {
+                column.push_addition_term(-FieldElement::one(), *rhs_limb);
+            }
+            column
+        };
+
+        // lhs - rhs + 2^(limb_bits * num_limbs) = is_greater_or_equal * 2^(limb_bits * num_limbs) + s,
+        // where s fits in `num_limbs` limbs, so that `is_greater_or_equal` is 1 if and only if `lhs >= rhs`
+        let mut columns: Vec<_> = (0..num_limbs).map(limb_difference).collect();
+        columns.push(Expression::one());
+        let is_greater_or_equal = self.new_limbs(1)[0];
+        let s = self.new_limbs(num_limbs);
+        let limbs_modulus = BigUint::one() << (self.limb_bits * num_limbs as u32);
+        self.quotient(columns.clone(), &limbs_modulus, vec![is_greater_or_equal], s.clone());
+        self.range_limbs(&s);
+
+        let mut boolean = Expression::default();
+        boolean.push_multiplication_term(
+            FieldElement::one(),
+            is_greater_or_equal,
+            is_greater_or_equal,
+        );
+        boolean.push_addition_term(-FieldElement::one(), is_greater_or_equal);
+        self.opcodes.push(Opcode::Arithmetic(boolean));
+
+        for (column, s_limb) in columns.iter_mut().zip(&s) {
+            column.push_addition_term(-FieldElement::one(), *s_limb);
+        }
+        columns[num_limbs].push_addition_term(-FieldElement::one(), is_greater_or_equal);
+        self.assert_zero(columns, self.limb_bits + 2)?;
+
+        // output = 1 - is_greater_or_equal
+        let mut output_expr = Expression::from(output);
+        output_expr.push_addition_term(FieldElement::one(), is_greater_or_equal);
+        output_expr.q_c = -FieldElement::one();
+        output_expr.sort();
+        self.opcodes.push(Opcode::Arithmetic(output_expr));
+        Ok(())
+    }
+}
+
+// Constrains a bigint operation using arithmetic over its limbs.
+//
+// The limbs of the operands are range constrained, so that an operand cannot be represented by
+// limbs which are too large for the values they encode.
+//
+// Returns an error if the call cannot be constrained, e.g. if the modulus is zero.
+pub fn bigint(call: &BigIntCall, num_witness: u32) -> Result<(u32, Vec<Opcode>), String> {
+    let BigIntCall { modulus, limb_bits, op } = call;
+    let limb_bits = *limb_bits;
+    let modulus = BigUint::from_bytes_be(modulus);
+    if limb_bits == 0 {
+        return Err("limbs must have a non-zero number of bits".to_owned());
+    }
+    if limb_bits >= FieldElement::max_num_bits() {
+        return Err(format!("limbs of {limb_bits} bits are too large for the field"));
+    }
+    if modulus.bits() == 0 {
+        return Err("the modulus must be non-zero".to_owned());
+    }
+
+    let mut constraints = LimbConstraints { limb_bits, num_witness, opcodes: Vec::new() };
+    match op {
+        BigIntOp::Add { lhs, rhs, output } => {
+            constraints.range_limbs(lhs);
+            constraints.range_limbs(rhs);
+            let num_limbs = lhs.len().max(rhs.len());
+            let columns = (0..num_limbs)
+                .map(|i| {
+                    let mut column = Expression::default();
+                    for limb in [lhs.get(i), rhs.get(i)].into_iter().flatten() {
+                        column.push_addition_term(FieldElement::one(), *limb);
+                    }
+                    column
+                })
+                .collect();
+            let a_bits = num_limbs as u32 * limb_bits + 1;
+            constraints.mod_reduce(columns, a_bits, limb_bits + 1, &modulus, output)?;
+        }
+        BigIntOp::Mul { lhs, rhs, output } => {
+            constraints.range_limbs(lhs);
+            constraints.range_limbs(rhs);
+            let mut columns = vec![Expression::default(); (lhs.len() + rhs.len()).max(1) - 1];
+            for (i, lhs_limb) in lhs.iter().enumerate() {
+                for (j, rhs_limb) in rhs.iter().enumerate() {
+                    columns[i + j].push_multiplication_term(
+                        FieldElement::one(),
+                        *lhs_limb,
+                        *rhs_limb,
+                    );
+                }
+            }
+            let a_bits = (lhs.len() + rhs.len()) as u32 * limb_bits;
+            let column_bits = 2 * limb_bits + ceil_log2(lhs.len().min(rhs.len()));
+            constraints.mod_reduce(columns, a_bits, column_bits, &modulus, output)?;
+        }
+        BigIntOp::ModReduce { input, output } => {
+            constraints.range_limbs(input);
+            let columns = input.iter().map(|limb| Expression::from(*limb)).collect();
+            let a_bits = input.len() as u32 * limb_bits;
+            constraints.mod_reduce(columns, a_bits, limb_bits, &modulus, output)?;
+        }
+        BigIntOp::Compare { lhs, rhs, output } => {
+            constraints.range_limbs(lhs);
+            constraints.range_limbs(rhs);
+            constraints.compare(lhs, rhs, *output)?;
+        }
+    }
+
+    Ok((constraints.num_witness, constraints.opcodes))
+}
@@ -0,0 +1,109 @@
+use crate::helpers::VariableStore;
+use acir::{
+    acir_field::FieldElement,
+    circuit::{
+        directives::Directive,
+        opcodes::{Lookup, LookupTable},
+        Opcode,
+    },
+    native_types::{Expression, Witness},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Constrains `is_zero` to be 1 if `expr` is zero and 0 otherwise
+fn is_zero(expr: Expression, variables: &mut VariableStore, opcodes: &mut Vec<Opcode>) -> Witness {
+    let difference = variables.new_variable();
+    let inverse = variables.new_variable();
+    let is_zero = variables.new_variable();
+
+    let mut difference_expr = expr;
+    difference_expr.push_addition_term(-FieldElement::one(), difference);
+    difference_expr.sort();
+    opcodes.push(Opcode::Arithmetic(difference_expr));
+    opcodes.push(Opcode::Directive(Directive::Invert { x: difference, result: inverse }));
+
+    // difference * inverse + is_zero - 1 = 0
+    let mut inverse_expr = Expression::default();
+    inverse_expr.push_multiplication_term(FieldElement::one(), difference, inverse);
+    inverse_expr.push_addition_term(FieldElement::one(), is_zero);
+    inverse_expr.q_c = -FieldElement::one();
+    opcodes.push(Opcode::Arithmetic(inverse_expr));
+
+    // difference * is_zero = 0
+    let mut zero_expr = Expression::default();
+    zero_expr.push_multiplication_term(FieldElement::one(), difference, is_zero);
+    opcodes.push(Opcode::Arithmetic(zero_expr));
+
+    is_zero
+}
+
+// Constrains the tuple of a lookup to be a row of `table` using arithmetic.
+//
+// Each row gets a selector which is 1 if the keys of the lookup match the keys of the row and 0 otherwise.
+// Exactly one selector must be set, and each value of the lookup is the sum of the values of the rows weighted
+// by their selectors. If the lookup has no keys, all of its entries are matched instead.
+//
+// Returns an error if the table cannot be looked up, e.g. if two of its rows have the same keys.
+pub fn lookup(
+    lookup: &Lookup,
+    table: &LookupTable,
+    mut num_witness: u32,
+) -> Result<(u32, Vec<Opcode>), String> {
+    lookup.check_table(table)?;
+    let entries: Vec<&Expression> = lookup.entries().collect();
+    let num_keys = lookup.num_keys();
+
+    // Duplicated rows would be selected together, so they are only constrained once
+    let mut distinct_rows = BTreeSet::new();
+    let rows: Vec<&[FieldElement]> =
+        table.rows.iter().map(Vec::as_slice).filter(|row| distinct_rows.insert(*row)).collect();
+
+    let mut opcodes = Vec::new();
+    let mut variables = VariableStore::new(&mut num_witness);
+
+    // Rows sharing a key value also share the check of this key
+    let mut key_matches: BTreeMap<(usize, FieldElement), Witness> = BTreeMap::new();
+    let mut selectors = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let mut selector: Option<Witness> = None;
+        for (column, value) in row[..num_keys].iter().enumerate() {
+            let key_match = *key_matches.entry((column, *value)).or_insert_with(|| {
+                is_zero(entries[column].clone() - *value, &mut variables, &mut opcodes)
+            });
+            selector = Some(match selector {
+                None => key_match,
+                Some(selector) => {
+                    let product = variables.new_variable();
+                    let mut product_expr = Expression::default();
+                    product_expr.push_multiplication_term(FieldElement::one(), selector, key_match);
+                    product_expr.push_addition_term(-FieldElement::one(), product);
+                    opcodes.push(Opcode::Arithmetic(product_expr));
+                    product
+                }
+            });
+        }
+        selectors.push(selector.expect("rows have at least one key"));
+    }
+
+    // Exactly one row matches the keys
+    let mut sum = Expression::from_field(-FieldElement::one());
+    for selector in &selectors {
+        sum.push_addition_term(FieldElement::one(), *selector);
+    }
+    sum.sort();
+    opcodes.push(Opcode::Arithmetic(sum));
+
+    // The values are the values of the matching row
+    for (column, entry) in entries.iter().enumerate().skip(num_keys) {
+        let mut value_expr = (*entry).clone();
+        for (row, selector) in rows.iter().zip(&selectors) {
+            if !row[column].is_zero() {
+                value_expr.push_addition_term(-row[column], *selector);
+            }
+        }
+        value_expr.sort();
+        opcodes.push(Opcode::Arithmetic(value_expr));
+    }
+
+    Ok((variables.finalize(), opcodes))
+}
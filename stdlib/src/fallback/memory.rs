@@ -0,0 +1,312 @@
+use crate::helpers::VariableStore;
+use acir::{
+    acir_field::FieldElement,
+    circuit::{
+        directives::{Directive, SortKey},
+        opcodes::{MemoryBlock, MemoryOpError},
+        Opcode,
+    },
+    native_types::{Expression, Witness},
+};
+
+use super::range;
+
+// Positions of the entries of a tuple of the sorted trace
+const INDEX: usize = 0;
+const COUNTER: usize = 1;
+const OPERATION: usize = 2;
+const VALUE: usize = 3;
+
+// Constrains the operations of a ROM block using arithmetic.
+//
+// The trace is sorted by index, so that the consistency of the reads only has to be checked between
+// consecutive accesses: an access to the same element as the previous one must have the same value.
+// The indices of the sorted trace must start at 0, end at `len - 1` and increase by at most one,
+// which also checks that they are within the bounds of the block.
+//
+// Returns the position in the trace of the first operation which cannot be constrained if the block is malformed.
+pub fn rom(
+    block: &MemoryBlock,
+    mut num_witness: u32,
+) -> Result<(u32, Vec<Opcode>), (usize, MemoryOpError)> {
+    check_init_phase(block)?;
+    for (position, op) in block.trace.iter().enumerate().skip(block.len as usize) {
+        match op.operation.to_const() {
+            Some(operation) if operation.is_zero() => (),
+            Some(operation) if operation.is_one() => {
+                return Err((position, MemoryOpError::WriteAfterInitialization))
+            }
+            Some(operation) => return Err((position, MemoryOpError::InvalidOperation(operation))),
+            None => return Err((position, MemoryOpError::NonConstantOperation)),
+        }
+    }
+    if block.trace.is_empty() {
+        return Ok((num_witness, Vec::new()));
+    }
+
+    let mut opcodes = vec![Opcode::Directive(Directive::ReadMemory(block.clone()))];
+    let mut variables = VariableStore::new(&mut num_witness);
+    let tuples = block
+        .trace
+        .iter()
+        .map(|op| {
+            let index = linear(&op.index, &mut variables, &mut opcodes);
+            vec![index, linear(&op.value, &mut variables, &mut opcodes)]
+        })
+        .collect();
+    let sorted = sort(tuples, vec![SortKey::ascending(INDEX as u32)], &mut variables, &mut opcodes);
+    let num_witness = variables.finalize();
+
+    let steps = constrain_indices(&sorted, block.len, &mut opcodes);
+    for (pair, step) in sorted.windows(2).zip(steps) {
+        // The tuples of a ROM are (index, value): (1 - step) * (value' - value) = 0
+        let same_element = &Expression::one() - &step;
+        let value_change = &pair[1][1] - &pair[0][1];
+        opcodes.push(Opcode::Arithmetic(&same_element * &value_change));
+    }
+
+    Ok((num_witness, opcodes))
+}
+
+// Constrains the operations of a RAM block using arithmetic.
+//
+// The trace is sorted by index and then by position in the trace, so that each read only has to be checked
+// against the previous access to the same element. The first access to each element is its initialization,
+// since the initialization phase is at the start of the trace.
+// The values of the reads are computed by the ReadMemory directive, the constraints only check their consistency.
+//
+// Returns the position in the trace of the first operation which cannot be constrained if the block is malformed.
+pub fn ram(
+    block: &MemoryBlock,
+    mut num_witness: u32,
+) -> Result<(u32, Vec<Opcode>), (usize, MemoryOpError)> {
+    check_init_phase(block)?;
+    if block.trace.is_empty() {
+        return Ok((num_witness, Vec::new()));
+    }
+
+    let mut opcodes = vec![Opcode::Directive(Directive::ReadMemory(block.clone()))];
+    let mut variables = VariableStore::new(&mut num_witness);
+    let mut tuples = Vec::with_capacity(block.trace.len());
+    for (position, op) in block.trace.iter().enumerate() {
+        let operation = linear(&op.operation, &mut variables, &mut opcodes);
+        if position >= block.len as usize {
+            match op.operation.to_const() {
+                Some(operation) if operation.is_zero() || operation.is_one() => (),
+                Some(operation) => {
+                    return Err((position, MemoryOpError::InvalidOperation(operation)))
+                }
+                None => {
+                    // operation * (operation - 1) = 0
+                    let boolean = &operation * &(operation.clone() - FieldElement::one());
+                    opcodes.push(Opcode::Arithmetic(boolean));
+                }
+            }
+        }
+        let index = linear(&op.index, &mut variables, &mut opcodes);
+        let counter = Expression::from_field(FieldElement::from(position as u128));
+        let value = linear(&op.value, &mut variables, &mut opcodes);
+        tuples.push(vec![index, counter, operation, value]);
+    }
+    let sort_by = vec![SortKey::ascending(INDEX as u32), SortKey::ascending(COUNTER as u32)];
+    let sorted = sort(tuples, sort_by, &mut variables, &mut opcodes);
+    let mut num_witness = variables.finalize();
+
+    // The gaps between the positions of consecutive accesses are less than the length of the trace
+    let counter_bits = u32::BITS - (block.trace.len() as u32).leading_zeros();
+    let steps = constrain_indices(&sorted, block.len, &mut opcodes);
+    for (pair, step) in sorted.windows(2).zip(steps) {
+        let same_element = &Expression::one() - &step;
+
+        // The accesses to the same element are sorted by their position in the trace:
+        // (1 - step) * (counter' - counter - 1) is a small non-negative integer
+        let gap = &pair[1][COUNTER] - &pair[0][COUNTER];
+        let (updated_witness_index, range_opcodes) =
+            range(&same_element * &(gap - FieldElement::one()), counter_bits, num_witness);
+        num_witness = updated_witness_index;
+        opcodes.extend(range_opcodes);
+
+        // A read has the value of the previous access to the same element:
+        // is_read = (1 - step) * (1 - operation') and is_read * (value' - value) = 0
+        let is_read = VariableStore::new(&mut num_witness).new_variable();
+        let mut is_read_expr = &same_element * &(&Expression::one() - &pair[1][OPERATION]);
+        is_read_expr.push_addition_term(-FieldElement::one(), is_read);
+        is_read_expr.sort();
+        opcodes.push(Opcode::Arithmetic(is_read_expr));
+        let value_change = &pair[1][VALUE] - &pair[0][VALUE];
+        opcodes.push(Opcode::Arithmetic(&Expression::from(is_read) * &value_change));
+    }
+
+    Ok((num_witness, opcodes))
+}
+
+// Checks that the first `len` operations of the trace write each element of the block once, at constant indices,
+// and that empty blocks are not accessed.
+fn check_init_phase(block: &MemoryBlock) -> Result<(), (usize, MemoryOpError)> {
+    if block.len == 0 && !block.trace.is_empty() {
+        return Err((0, MemoryOpError::EmptyMemoryAccess));
+    }
+    block.sorted_init_phase().map(|_| ())
+}
+
+// Returns a degree one expression equal to `expr`, so that the entries of the trace can be multiplied
+// in the constraints. If `expr` has degree two then it is assigned to a new witness.
+fn linear(
+    expr: &Expression,
+    variables: &mut VariableStore,
+    opcodes: &mut Vec<Opcode>,
+) -> Expression {
+    if expr.is_linear() {
+        return expr.clone();
+    }
+
+    let witness = variables.new_variable();
+    let mut constraint = expr.clone();
+    constraint.push_addition_term(-FieldElement::one(), witness);
+    constraint.sort();
+    opcodes.push(Opcode::Arithmetic(constraint));
+    Expression::from(witness)
+}
+
+// Constrains the indices of a sorted trace to go through each element of a block of `len` elements:
+// they start at 0, end at `len - 1` and increase by at most one between consecutive accesses.
+// Returns the increase of the index between consecutive accesses, which is either 0 or 1.
+fn constrain_indices(
+    sorted: &[Vec<Expression>],
+    len: u32,
+    opcodes: &mut Vec<Opcode>,
+) -> Vec<Expression> {
+    let first = &sorted[0][INDEX];
+    let last = &sorted[sorted.len() - 1][INDEX];
+    opcodes.push(Opcode::Arithmetic(first.clone()));
+    opcodes.push(Opcode::Arithmetic(last.clone() - FieldElement::from(len as u128 - 1)));
+
+    sorted
+        .windows(2)
+        .map(|pair| {
+            // step * (step - 1) = 0
+            let step = &pair[1][INDEX] - &pair[0][INDEX];
+            opcodes.push(Opcode::Arithmetic(&step * &(step.clone() - FieldElement::one())));
+            step
+        })
+        .collect()
+}
+
+// Returns the tuples sorted by the entries of `sort_by`, using a permutation network whose control bits
+// are computed by the PermutationSort directive.
+// The network only checks that the output is a permutation of the input, the order must be constrained separately.
+fn sort(
+    tuples: Vec<Vec<Expression>>,
+    sort_by: Vec<SortKey>,
+    variables: &mut VariableStore,
+    opcodes: &mut Vec<Opcode>,
+) -> Vec<Vec<Expression>> {
+    let bits: Vec<Witness> =
+        (0..switch_count(tuples.len())).map(|_| variables.new_variable()).collect();
+    if bits.is_empty() {
+        return tuples;
+    }
+
+    opcodes.push(Opcode::Directive(Directive::PermutationSort {
+        inputs: tuples.clone(),
+        tuple: tuples[0].len() as u32,
+        bits: bits.clone(),
+        sort_by,
+    }));
+    for bit in &bits {
+        // bit * bit - bit = 0
+        let mut boolean = Expression::default();
+        boolean.push_multiplication_term(FieldElement::one(), *bit, *bit);
+        boolean.push_addition_term(-FieldElement::one(), *bit);
+        opcodes.push(Opcode::Arithmetic(boolean));
+    }
+    permutation_network(tuples, &bits, variables, opcodes)
+}
+
+// Returns the number of switches of the permutation network for `n` inputs
+fn switch_count(n: usize) -> usize {
+    if n <= 1 {
+        return 0;
+    }
+    n / 2 + (n - 1) / 2 + switch_count(n / 2) + switch_count(n - n / 2)
+}
+
+// Permutes `inputs` with the network used by the PermutationSort directive, configured by `bits`.
+//
+// The network has an outer layer of switches on pairs of inputs, which route one input of each pair to
+// each of two sub-networks, and an outer layer of switches which merge the outputs of the sub-networks.
+// The switches are ordered as the input layer, the output layer, then the switches of each sub-network.
+fn permutation_network(
+    inputs: Vec<Vec<Expression>>,
+    bits: &[Witness],
+    variables: &mut VariableStore,
+    opcodes: &mut Vec<Opcode>,
+) -> Vec<Vec<Expression>> {
+    let n = inputs.len();
+    if n <= 1 {
+        return inputs;
+    }
+    let (input_bits, bits) = bits.split_at(n / 2);
+    let (output_bits, bits) = bits.split_at((n - 1) / 2);
+    let (first_bits, second_bits) = bits.split_at(switch_count(n / 2));
+
+    let mut inputs = inputs.into_iter();
+    let mut first = Vec::with_capacity(n / 2);
+    let mut second = Vec::with_capacity(n - n / 2);
+    for bit in input_bits {
+        let (x, y) = (inputs.next().unwrap(), inputs.next().unwrap());
+        let (x, y) = switch(*bit, x, y, variables, opcodes);
+        first.push(x);
+        second.push(y);
+    }
+    // The last input of an odd network is not switched
+    second.extend(inputs);
+
+    let mut first = permutation_network(first, first_bits, variables, opcodes).into_iter();
+    let mut second = permutation_network(second, second_bits, variables, opcodes).into_iter();
+
+    let mut outputs = Vec::with_capacity(n);
+    for bit in output_bits {
+        let (x, y) = (first.next().unwrap(), second.next().unwrap());
+        let (x, y) = switch(*bit, x, y, variables, opcodes);
+        outputs.push(x);
+        outputs.push(y);
+    }
+    // The last outputs of the sub-networks are not switched
+    outputs.extend(first);
+    outputs.extend(second);
+    outputs
+}
+
+// Routes the tuples `x` and `y` through a switch which swaps them if `bit` is set.
+fn switch(
+    bit: Witness,
+    x: Vec<Expression>,
+    y: Vec<Expression>,
+    variables: &mut VariableStore,
+    opcodes: &mut Vec<Opcode>,
+) -> (Vec<Expression>, Vec<Expression>) {
+    x.into_iter()
+        .zip(y)
+        .map(|(x, y)| {
+            // x' = x + bit * (y - x) and y' = y - bit * (y - x)
+            let swap = &Expression::from(bit) * &(&y - &x);
+            if swap.is_linear() {
+                // The entries are constants, so their outputs are linear in the bit
+                return (&x + &swap, &y - &swap);
+            }
+
+            let (first, second) = (variables.new_variable(), variables.new_variable());
+            let mut first_expr = &x + &swap;
+            first_expr.push_addition_term(-FieldElement::one(), first);
+            first_expr.sort();
+            opcodes.push(Opcode::Arithmetic(first_expr));
+            let mut second_expr = &x + &y;
+            second_expr.push_addition_term(-FieldElement::one(), first);
+            second_expr.push_addition_term(-FieldElement::one(), second);
+            second_expr.sort();
+            opcodes.push(Opcode::Arithmetic(second_expr));
+            (Expression::from(first), Expression::from(second))
+        })
+        .unzip()
+}